@@ -2,6 +2,7 @@ extern crate pnetlink;
 
 use pnetlink::packet::netlink::NetlinkConnection;
 use pnetlink::packet::route::link::{Links,Link};
+use pnetlink::packet::route::AddressFamily;
 use pnetlink::packet::route::addr::{Addresses,Addr};
 
 fn print_link(link: &Link) {
@@ -12,8 +13,8 @@ fn print_link(link: &Link) {
 
 fn print_addr(addr: &Addr) {
     let family = match addr.get_family() {
-        2 => "inet",
-        10 => "inet6",
+        AddressFamily::Inet => "inet",
+        AddressFamily::Inet6 => "inet6",
         _ => "unknown",
     };
     fn debug_or_empty<T: ::std::fmt::Debug>(prefix: &str, val: Option<T>) -> String {
@@ -11,11 +11,14 @@ extern crate libc;
 extern crate byteorder;
 extern crate bytes;
 extern crate mio;
-extern crate tokio_core;
-extern crate tokio_io;
 extern crate futures;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 pub mod socket;
 pub mod packet;
+#[cfg(any(feature = "tokio", feature = "async-io"))]
 pub mod tokio;
 pub mod util;
@@ -1,8 +1,8 @@
-use packet::netlink::{MutableNetlinkPacket,NetlinkPacket};
-use packet::netlink::NetlinkMsgFlags;
-use packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
-use ::socket::{NetlinkSocket,NetlinkProtocol};
-use packet::netlink::NetlinkConnection;
+use crate::packet::netlink::{MutableNetlinkPacket,NetlinkPacket};
+use crate::packet::netlink::NetlinkMsgFlags;
+use crate::packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
+use crate::socket::{NetlinkSocket,NetlinkProtocol};
+use crate::packet::netlink::NetlinkConnection;
 use pnet::packet::MutablePacket;
 use pnet::packet::Packet;
 use pnet::packet::PacketSize;
@@ -25,7 +25,7 @@ impl Audit for NetlinkConnection {
                 status.set_enabled(1);
                 status
             }).build();
-        try!(self.write(req.packet()));
+        self.write(req.packet())?;
         let reader = NetlinkReader::new(self);
         reader.read_to_end()
     }
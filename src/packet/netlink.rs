@@ -1,9 +1,10 @@
 //! Netlink packet handling
-use ::socket::{NetlinkSocket,NetlinkProtocol};
+use crate::socket::{NetlinkSocket,NetlinkProtocol,SockOpt,with_netns};
 use libc;
 use std::io;
 use std::io::{Read,BufRead,BufReader,Write};
 use std::marker::PhantomData;
+use std::path::{Path,PathBuf};
 use pnet::packet::{Packet,PacketSize,FromPacket};
 
 include!(concat!(env!("OUT_DIR"), "/netlink.rs"));
@@ -179,7 +180,7 @@ impl<R: Read> NetlinkReader<R> {
             }
             loop {
                 if let Some(pkt) = NetlinkPacket::new(&self.buf[self.read_at..]) {
-                    let len = ::util::align(pkt.get_length() as usize);
+                    let len = crate::util::align(pkt.get_length() as usize);
                     if len == 0 {
                         return Ok(None);
                     }
@@ -239,10 +240,31 @@ impl From<NetlinkSocket> for NetlinkConnection {
 }
 
 impl NetlinkConnection {
+    /// Connect to `NetlinkProtocol::Route` with no multicast groups
+    ///
+    /// Panics on failure, use `builder()` to handle errors.
     pub fn new() -> Self {
-        NetlinkConnection {
-            sock: NetlinkSocket::bind(NetlinkProtocol::Route, 0 as u32).unwrap(),
-        }
+        NetlinkConnection::builder().build().unwrap()
+    }
+
+    /// Returns a builder for connections to any protocol
+    pub fn builder() -> NetlinkConnectionBuilder {
+        NetlinkConnectionBuilder::new()
+    }
+
+    /// Get underlying socket
+    pub fn socket(&self) -> &NetlinkSocket {
+        &self.sock
+    }
+
+    /// Get underlying socket for changing options
+    pub fn socket_mut(&mut self) -> &mut NetlinkSocket {
+        &mut self.sock
+    }
+
+    /// Take underlying socket, e.g. to use it with `pnetlink::tokio`
+    pub fn into_socket(self) -> NetlinkSocket {
+        self.sock
     }
 
     pub fn send<'a,'b>(&'a mut self, msg: NetlinkPacket<'b>) -> NetlinkReader<&'a mut NetlinkConnection> {
@@ -267,6 +289,139 @@ impl ::std::io::Write for NetlinkConnection {
     }
 }
 
+/// NetlinkConnectionBuilder configures and opens a `NetlinkConnection`
+///
+/// ```no_run
+/// use pnetlink::socket::{NetlinkProtocol,SockOpt};
+/// use pnetlink::packet::netlink::NetlinkConnection;
+///
+/// let conn = NetlinkConnection::builder()
+///     .protocol(NetlinkProtocol::Audit)
+///     .recv_buffer_size(1 << 20)
+///     .sockopt(SockOpt::NoEnobufs, true)
+///     .build().unwrap();
+/// ```
+#[derive(Debug,Clone)]
+pub struct NetlinkConnectionBuilder {
+    protocol: NetlinkProtocol,
+    port_id: u32,
+    groups: u32,
+    memberships: Vec<u32>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    sockopts: Vec<(SockOpt, bool)>,
+    netns: Option<PathBuf>,
+    nonblocking: bool,
+}
+
+impl NetlinkConnectionBuilder {
+    /// Creates a builder for a blocking `NetlinkProtocol::Route` connection
+    /// with a kernel assigned port id and no multicast groups
+    pub fn new() -> Self {
+        NetlinkConnectionBuilder {
+            protocol: NetlinkProtocol::Route,
+            port_id: 0,
+            groups: 0,
+            memberships: vec![],
+            recv_buffer_size: None,
+            send_buffer_size: None,
+            sockopts: vec![],
+            netns: None,
+            nonblocking: false,
+        }
+    }
+
+    /// Set netlink protocol (family)
+    pub fn protocol(mut self, protocol: NetlinkProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Set port id (nl_pid), 0 lets the kernel pick a unique one
+    pub fn port_id(mut self, port_id: u32) -> Self {
+        self.port_id = port_id;
+        self
+    }
+
+    /// Set multicast groups bitmask given to bind() (groups 1 to 32)
+    pub fn groups(mut self, groups: u32) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// Join multicast group `group` by number after bind(), any group can be used
+    pub fn add_membership(mut self, group: u32) -> Self {
+        self.memberships.push(group);
+        self
+    }
+
+    /// Set SO_RCVBUF
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Set SO_SNDBUF
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Set boolean netlink socket option, e.g. `SockOpt::NoEnobufs`
+    pub fn sockopt(mut self, option: SockOpt, val: bool) -> Self {
+        self.sockopts.push((option, val));
+        self
+    }
+
+    /// Open the socket in network namespace `netns`
+    /// (e.g. `/var/run/netns/NAME` or `/proc/PID/ns/net`)
+    ///
+    /// `build()` switches the calling thread there only while binding. If it cannot
+    /// switch back, `build()` fails and the thread is left in `netns`.
+    pub fn netns<P: AsRef<Path>>(mut self, netns: P) -> Self {
+        self.netns = Some(netns.as_ref().to_owned());
+        self
+    }
+
+    /// Use non-blocking socket, reads return `WouldBlock` instead of waiting
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Open and configure the socket
+    pub fn build(&self) -> io::Result<NetlinkConnection> {
+        let sock = match self.netns {
+            Some(ref netns) => with_netns(netns, || self.bind())?,
+            None => self.bind()?,
+        };
+        Ok(NetlinkConnection::from(sock))
+    }
+
+    fn bind(&self) -> io::Result<NetlinkSocket> {
+        let mut sock = NetlinkSocket::bind_with_pid(self.protocol, self.port_id, self.groups, self.nonblocking)?;
+        if let Some(size) = self.recv_buffer_size {
+            sock.setrcvbuf(size as libc::c_int)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            sock.setsndbuf(size as libc::c_int)?;
+        }
+        for &(option, val) in self.sockopts.iter() {
+            sock.setsockopt(option, val)?;
+        }
+        for &group in self.memberships.iter() {
+            sock.add_membership(group)?;
+        }
+        Ok(sock)
+    }
+}
+
+impl Default for NetlinkConnectionBuilder {
+    fn default() -> Self {
+        NetlinkConnectionBuilder::new()
+    }
+}
+
 /// NetlinkRequestBuilder provides functions
 /// for building Netlink requests
 pub struct NetlinkRequestBuilder {
@@ -295,7 +450,7 @@ impl NetlinkRequestBuilder {
     pub fn append<P: PacketSize + Packet>(mut self, data: P) -> Self {
         let data = data.packet();
         let len = data.len();
-        let aligned_len = ::util::align(len as usize);
+        let aligned_len = crate::util::align(len as usize);
         {
             let mut pkt = MutableNetlinkPacket::new(&mut self.data).unwrap();
             let new_len = pkt.get_length() + aligned_len as u32;
@@ -331,3 +486,17 @@ impl<'a> ToIoError for NetlinkPacket<'a> {
         None
     }
 }
+
+#[test]
+fn builder_other_protocols() {
+    let conn = NetlinkConnection::builder()
+        .protocol(NetlinkProtocol::Generic)
+        .recv_buffer_size(65536)
+        .sockopt(SockOpt::ExtAck, true)
+        .build().unwrap();
+    drop(conn);
+    // two connections no longer clash on the port id
+    let _a = NetlinkConnection::builder().build().unwrap();
+    let _b = NetlinkConnection::builder().build().unwrap();
+    assert!(NetlinkConnection::builder().netns("/nonexistent").build().is_err());
+}
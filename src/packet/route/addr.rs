@@ -1,11 +1,13 @@
 //! Address operations
-use packet::route::{IfAddrCacheInfoPacket,MutableIfInfoPacket,IfAddrPacket,MutableIfAddrPacket,RtAttrIterator,RtAttrPacket,MutableRtAttrPacket,RtAttrMtuPacket};
-use packet::route::link::Link;
-use packet::netlink::{MutableNetlinkPacket,NetlinkPacket,NetlinkErrorPacket};
-use packet::netlink::NetlinkMsgFlags;
-use packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
-use socket::{NetlinkSocket,NetlinkProtocol};
-use packet::netlink::NetlinkConnection;
+use crate::packet::route::{AddressFamily,IfAddrCacheInfoPacket,MutableIfInfoPacket,IfAddrPacket,MutableIfAddrPacket,RtAttrIterator,RtAttrPacket,MutableRtAttrPacket,RtAttrMtuPacket};
+use crate::packet::route::link::Link;
+use crate::packet::route::prefix::IpPrefix;
+use crate::packet::route::route::WithPayload;
+use crate::packet::netlink::{MutableNetlinkPacket,NetlinkPacket,NetlinkErrorPacket};
+use crate::packet::netlink::NetlinkMsgFlags;
+use crate::packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
+use crate::socket::{NetlinkSocket,NetlinkProtocol};
+use crate::packet::netlink::NetlinkConnection;
 use pnet::packet::MutablePacket;
 use pnet::packet::Packet;
 use pnet::packet::PacketSize;
@@ -29,7 +31,7 @@ pub const RTM_GETADDR: u16 = 22;
    Intermediate values are also possible f.e. interior routes
    could be assigned a value between UNIVERSE and LINK.
 */
-#[derive(Debug,Copy,Clone)]
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
 #[repr(u8)]
 pub enum Scope {
     Universe=0,
@@ -128,92 +130,97 @@ impl ToByteVec for IpAddr {
 
 /// Address operations trait
 pub trait Addresses where Self: Read + Write {
-    fn iter_addrs<'a>(&'a mut self, family: Option<u8>) -> io::Result<Box<Iterator<Item = Addr> + 'a>>;
-    fn get_link_addrs<'a,'b>(&'a mut self, family: Option<u8>, link: &'b Link) -> io::Result<Box<Iterator<Item = Addr> + 'a>>;
-    fn add_addr<'a,'b>(&'a mut self, link: &'b Link, addr: IpAddr, dst_addr: Option<IpAddr>,
-                       scope: Scope, netmask_bits: u8) -> io::Result<()>;
+    fn iter_addrs<'a>(&'a mut self, family: Option<AddressFamily>) -> io::Result<Box<dyn Iterator<Item = Addr> + 'a>>;
+    fn get_link_addrs<'a,'b>(&'a mut self, family: Option<AddressFamily>, link: &'b Link) -> io::Result<Box<dyn Iterator<Item = Addr> + 'a>>;
+    fn add_addr<'a,'b>(&'a mut self, link: &'b Link, prefix: IpPrefix, dst_addr: Option<IpAddr>,
+                       scope: Scope) -> io::Result<()>;
+    fn delete_addr(&mut self, addr: &AddrInfo) -> io::Result<()>;
 }
 
 impl Addresses for NetlinkConnection {
     /// Iterate over all addresses
-    fn iter_addrs<'a>(&'a mut self, family: Option<u8>) -> io::Result<Box<Iterator<Item = Addr> + 'a>> {
-        let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-        let req = NetlinkRequestBuilder::new(RTM_GETADDR, NetlinkMsgFlags::NLM_F_DUMP)
-            .append({
-                let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                ifinfo.set_family(family.unwrap_or(0));
-                ifinfo
-            }).build();
-        try!(self.write(req.packet()));
+    fn iter_addrs<'a>(&'a mut self, family: Option<AddressFamily>) -> io::Result<Box<dyn Iterator<Item = Addr> + 'a>> {
+        let req = Addr::dump_request(family);
+        self.write(req.packet())?;
         let reader = NetlinkReader::new(self);
         let iter = AddrsIterator { iter: reader.into_iter() };
         Ok(Box::new(iter))
     }
 
     /// Iterate over `family` addresses for `link`
-    fn get_link_addrs<'a,'b>(&'a mut self, family: Option<u8>, link: &'b Link) -> io::Result<Box<Iterator<Item = Addr> + 'a>> {
+    fn get_link_addrs<'a,'b>(&'a mut self, family: Option<AddressFamily>, link: &'b Link) -> io::Result<Box<dyn Iterator<Item = Addr> + 'a>> {
         let idx = link.get_index();
-        let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-        let req = NetlinkRequestBuilder::new(RTM_GETADDR, NetlinkMsgFlags::NLM_F_DUMP)
-            .append({
-                let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                ifinfo.set_family(family.unwrap_or(0));
-                ifinfo
-            }).build();
-        try!(self.write(req.packet()));
+        let req = Addr::dump_request(family);
+        self.write(req.packet())?;
         let reader = NetlinkReader::new(self);
         let iter = AddrsIterator { iter: reader.into_iter() };
         Ok(Box::new(iter.filter(move |addr| addr.with_ifaddr(|ifa| ifa.get_index() == idx))))
     }
 
-    /// Add address `addr` to `link` with scope `scope`
+    /// Add address `prefix` to `link` with scope `scope`
     ///
-    /// * `dst_addr` - If `dst_addr` is not None, the IFA_ADDRESS will be set to `dst_addr`.
-    ///                If `dst_addr` is None, the IFA_ADDRESS will be set to `addr`.
-    /// * `netmask_bits` - The number of bits that should be set to 1 in the netmask.
-    fn add_addr<'a,'b>(&'a mut self, link: &'b Link, addr: IpAddr, dst_addr: Option<IpAddr>,
-                       scope: Scope, netmask_bits: u8) -> io::Result<()> {
-        let link_index = link.get_index();
-        let family = match addr {
-            IpAddr::V4(_) => 2,
-            IpAddr::V6(_) => 10,
-        };
-        let ip_addr_len = addr.bytes().len();
-        let mut buf = vec![0; MutableIfAddrPacket::minimum_packet_size()];
-        let mut rta_buf = vec![0; MutableRtAttrPacket::minimum_packet_size() + ip_addr_len];
-        let mut rta_buf1 = vec![0; MutableRtAttrPacket::minimum_packet_size() + ip_addr_len];
-        let req = IfAddrRequestBuilder::new().with_ifa(|mut ifaddr| {
-                ifaddr.set_index(link_index);
-                ifaddr.set_family(family);
-                ifaddr.set_scope(scope);
-                ifaddr.set_prefix_len(netmask_bits);
-        }).append({
-            {
-                let mut pkt = MutableRtAttrPacket::new(&mut rta_buf).unwrap();
-                pkt.set_rta_len(4 + ip_addr_len as u16);
-                pkt.set_rta_type(IFA_ADDRESS);
-                let mut pl = pkt.payload_mut();
-                pl.copy_from_slice(&dst_addr.as_ref().unwrap_or_else(|| &addr).bytes());
-            }
-            RtAttrPacket::new(&mut rta_buf).unwrap()
-        }).append({
-            {
-                let mut pkt = MutableRtAttrPacket::new(&mut rta_buf1).unwrap();
-                pkt.set_rta_len(4 + ip_addr_len as u16);
-                pkt.set_rta_type(IFA_LOCAL);
-                let mut pl = pkt.payload_mut();
-                pl.copy_from_slice(&addr.bytes());
-            }
-            RtAttrPacket::new(&mut rta_buf1).unwrap()
-        }).build();
-        let req = NetlinkRequestBuilder::new(RTM_NEWADDR, NetlinkMsgFlags::NLM_F_CREATE | NetlinkMsgFlags::NLM_F_EXCL | NetlinkMsgFlags::NLM_F_ACK)
-            .append(req).build();
-        self.write(req.packet());
+    /// Host bits of `prefix` are kept: 10.0.0.5/24 assigns 10.0.0.5 with a /24 netmask.
+    ///
+    /// * `dst_addr` - If `dst_addr` is not None, the IFA_ADDRESS will be set to `dst_addr`
+    ///                (peer address on point-to-point links), it must be of the prefix family.
+    ///                If `dst_addr` is None, the IFA_ADDRESS will be set to the prefix address.
+    fn add_addr<'a,'b>(&'a mut self, link: &'b Link, prefix: IpPrefix, dst_addr: Option<IpAddr>,
+                       scope: Scope) -> io::Result<()> {
+        let req = Addr::add_request(link.get_index(), prefix, dst_addr, scope)?;
+        self.write(req.packet())?;
+        let reader = NetlinkReader::new(self);
+        reader.read_to_end()
+    }
+
+    /// Delete address
+    fn delete_addr(&mut self, addr: &AddrInfo) -> io::Result<()> {
+        let req = addr.to_request(RTM_DELADDR, NetlinkMsgFlags::NLM_F_ACK);
+        self.write(req.packet())?;
         let reader = NetlinkReader::new(self);
         reader.read_to_end()
     }
 }
 
+/// Requests shared by the `Addresses` trait and async `Handle`
+impl Addr {
+    /// Wrap a RTM_NEWADDR or RTM_DELADDR message
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Option<Addr> {
+        match packet.get_kind() {
+            RTM_NEWADDR | RTM_DELADDR => Some(Addr { packet: packet }),
+            _ => None,
+        }
+    }
+
+    /// Request dumping addresses of `family`, or all if None
+    pub fn dump_request(family: Option<AddressFamily>) -> NetlinkPacket<'static> {
+        let ifa = IfAddrRequestBuilder::new().with_ifa(|mut ifaddr| {
+            ifaddr.set_family(family.unwrap_or(AddressFamily::Unspec));
+        }).build();
+        NetlinkRequestBuilder::new(RTM_GETADDR, NetlinkMsgFlags::NLM_F_DUMP).append(ifa).build()
+    }
+
+    /// Request adding address, see `Addresses::add_addr`
+    pub fn add_request(link_index: u32, prefix: IpPrefix, dst_addr: Option<IpAddr>, scope: Scope) -> io::Result<NetlinkPacket<'static>> {
+        let addr = prefix.addr();
+        if let Some(dst) = dst_addr {
+            if AddressFamily::from(dst) != prefix.family() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("peer {} does not match family of {}", dst, prefix)));
+            }
+        }
+        let ifa = IfAddrRequestBuilder::new().with_ifa(|mut ifaddr| {
+                ifaddr.set_index(link_index);
+                ifaddr.set_family(prefix.family());
+                ifaddr.set_scope(scope);
+                ifaddr.set_prefix_len(prefix.len());
+        }).append(RtAttrPacket::create_with_payload(IFA_ADDRESS, dst_addr.unwrap_or(addr)))
+          .append(RtAttrPacket::create_with_payload(IFA_LOCAL, addr))
+          .build();
+        Ok(NetlinkRequestBuilder::new(RTM_NEWADDR, NetlinkMsgFlags::NLM_F_CREATE | NetlinkMsgFlags::NLM_F_EXCL | NetlinkMsgFlags::NLM_F_ACK)
+            .append(ifa).build())
+    }
+}
+
 /// Address
 #[derive(Debug)]
 pub struct Addr {
@@ -222,7 +229,7 @@ pub struct Addr {
 
 impl Addr {
     /// get address family
-    pub fn get_family(&self) -> u8 {
+    pub fn get_family(&self) -> AddressFamily {
         self.with_ifaddr(|ifa| ifa.get_family())
     }
 
@@ -241,6 +248,15 @@ impl Addr {
         self.with_ifaddr(|ifa| ifa.get_scope())
     }
 
+    /// Get local address with prefix length, host bits included
+    ///
+    /// Falls back to IFA_ADDRESS if IFA_LOCAL is not present (IPv6).
+    pub fn get_prefix(&self) -> Option<IpPrefix> {
+        let prefix_len = self.get_prefix_len();
+        self.get_local_ip().or_else(|| self.get_ip())
+            .and_then(|ip| IpPrefix::new(ip, prefix_len).ok())
+    }
+
     /// get link index for address
     pub fn get_link_index(&self) -> u32 {
         self.with_ifaddr(|ifa| ifa.get_index())
@@ -255,6 +271,9 @@ impl Addr {
     ///
     pub fn get_ip(&self) -> Option<IpAddr> {
         let family = self.with_ifaddr(|ifa| ifa.get_family());
+        if !family.is_ip() {
+            return None;
+        }
         self.with_rta(IFA_ADDRESS, |rta| {
             Self::ip_from_family_and_bytes(family, rta.payload())
         })
@@ -263,6 +282,9 @@ impl Addr {
     /// See get_ip()
     pub fn get_local_ip(&self) -> Option<IpAddr> {
         let family = self.with_ifaddr(|ifa| ifa.get_family());
+        if !family.is_ip() {
+            return None;
+        }
         self.with_rta(IFA_LOCAL, |rta| {
             Self::ip_from_family_and_bytes(family, rta.payload())
         })
@@ -271,11 +293,25 @@ impl Addr {
     /// Get broadcast address
     pub fn get_broadcast_ip(&self) -> Option<IpAddr> {
         let family = self.with_ifaddr(|ifa| ifa.get_family());
+        if !family.is_ip() {
+            return None;
+        }
         self.with_rta(IFA_BROADCAST, |rta| {
             Self::ip_from_family_and_bytes(family, rta.payload())
         })
     }
 
+    /// Get anycast address
+    pub fn get_anycast_ip(&self) -> Option<IpAddr> {
+        let family = self.with_ifaddr(|ifa| ifa.get_family());
+        if !family.is_ip() {
+            return None;
+        }
+        self.with_rta(IFA_ANYCAST, |rta| {
+            Self::ip_from_family_and_bytes(family, rta.payload())
+        })
+    }
+
     pub fn get_label(&self) -> Option<String> {
         use std::ffi::CStr;
         self.with_rta(IFA_LABEL, |rta| {
@@ -284,7 +320,12 @@ impl Addr {
         })
     }
 
-    /* TODO: implement get_cache_info() */
+    /// Get address lifetimes and timestamps
+    pub fn get_cache_info(&self) -> Option<IfAddrCacheInfoPacket<'static>> {
+        self.with_rta(IFA_CACHEINFO, |rta| {
+            IfAddrCacheInfoPacket::owned(rta.payload().to_vec())
+        }).and_then(|info| info)
+    }
 
     // helper methods
     fn with_packet<T,F>(&self, cb: F) -> T
@@ -314,11 +355,11 @@ impl Addr {
     }
 
     /// Extract an IP address from a buffer.
-    pub fn ip_from_family_and_bytes(family: u8, bytes: &[u8]) -> IpAddr {
+    pub fn ip_from_family_and_bytes(family: AddressFamily, bytes: &[u8]) -> IpAddr {
         let mut cur = Cursor::new(bytes);
         match family {
-            2 /* AF_INET */ => IpAddr::V4(Ipv4Addr::from(cur.read_u32::<BigEndian>().unwrap())),
-            10 /* AF_INET6 */ => {
+            AddressFamily::Inet => IpAddr::V4(Ipv4Addr::from(cur.read_u32::<BigEndian>().unwrap())),
+            AddressFamily::Inet6 => {
                 let mut ip6addr: [u8;16] = [0;16];
                 &mut ip6addr[..].copy_from_slice(bytes);
                 IpAddr::V6(Ipv6Addr::from(ip6addr))
@@ -350,10 +391,10 @@ impl Addr {
                             _ => unreachable!(),
                         }
                         match ifa.get_family() {
-                            2 => {
+                            AddressFamily::Inet => {
                                 println!("{}", Ipv4Addr::from(cur.read_u32::<BigEndian>().unwrap()));
                             },
-                            10 => {
+                            AddressFamily::Inet6 => {
                                 let mut ip6addr: [u8;16] = [0;16];
                                 &mut ip6addr[..].copy_from_slice(rta.payload());
                                 println!("{}", Ipv6Addr::from(ip6addr));
@@ -384,18 +425,119 @@ impl Addr {
     }
 
     pub fn iter_addrs(conn: &mut NetlinkConnection) -> AddrsIterator<&mut NetlinkConnection> {
-        let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-        let req = NetlinkRequestBuilder::new(RTM_GETADDR, NetlinkMsgFlags::NLM_F_DUMP)
-        .append({
-            let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-            ifinfo.set_family(0 /* AF_UNSPEC */);
-            ifinfo
-        }).build();
-        let mut reply = conn.send(req);
+        let mut reply = conn.send(Addr::dump_request(None));
         AddrsIterator { iter: reply.into_iter() }
     }
 }
 
+/// Owned address description
+///
+/// Parsed once from an `Addr`; can be cloned, compared and hashed.
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub struct AddrInfo {
+    pub family: AddressFamily,
+    pub prefix_len: u8,
+    pub flags: IfAddrFlags,
+    pub scope: Scope,
+    pub index: u32,
+    /// IFA_ADDRESS: prefix address, or peer address on point-to-point links
+    pub address: Option<IpAddr>,
+    /// IFA_LOCAL: local interface address
+    pub local: Option<IpAddr>,
+    pub broadcast: Option<IpAddr>,
+    pub anycast: Option<IpAddr>,
+    pub label: Option<String>,
+    pub valid_lft: Option<u32>,
+    pub preferred_lft: Option<u32>,
+}
+
+impl<'a> From<&'a Addr> for AddrInfo {
+    fn from(addr: &'a Addr) -> Self {
+        use std::ffi::CStr;
+
+        addr.with_ifaddr(|ifa| {
+            let family = ifa.get_family();
+            let ip = |rta: &RtAttrPacket| match (family, rta.payload().len()) {
+                (AddressFamily::Inet, 4) | (AddressFamily::Inet6, 16) => {
+                    Some(Addr::ip_from_family_and_bytes(family, rta.payload()))
+                },
+                _ => None,
+            };
+            let mut info = AddrInfo {
+                family: family,
+                prefix_len: ifa.get_prefix_len(),
+                flags: ifa.get_flags(),
+                scope: ifa.get_scope(),
+                index: ifa.get_index(),
+                address: None,
+                local: None,
+                broadcast: None,
+                anycast: None,
+                label: None,
+                valid_lft: None,
+                preferred_lft: None,
+            };
+            for rta in RtAttrIterator::new(ifa.payload()) {
+                match rta.get_rta_type() {
+                    IFA_ADDRESS => info.address = ip(&rta),
+                    IFA_LOCAL => info.local = ip(&rta),
+                    IFA_BROADCAST => info.broadcast = ip(&rta),
+                    IFA_ANYCAST => info.anycast = ip(&rta),
+                    IFA_LABEL => {
+                        // malformed labels are skipped, invalid UTF-8 is replaced
+                        info.label = CStr::from_bytes_with_nul(rta.payload()).ok()
+                            .map(|cstr| cstr.to_string_lossy().into_owned());
+                    },
+                    IFA_CACHEINFO => {
+                        if let Some(ci) = IfAddrCacheInfoPacket::new(rta.payload()) {
+                            info.valid_lft = Some(ci.get_ifa_valid());
+                            info.preferred_lft = Some(ci.get_ifa_prefered());
+                        }
+                    },
+                    _ => {},
+                }
+            }
+            info
+        })
+    }
+}
+
+impl From<Addr> for AddrInfo {
+    fn from(addr: Addr) -> Self {
+        AddrInfo::from(&addr)
+    }
+}
+
+impl AddrInfo {
+    /// Get local address with prefix length, see `Addr::get_prefix`
+    pub fn prefix(&self) -> Option<IpPrefix> {
+        self.local.or(self.address)
+            .and_then(|ip| IpPrefix::new(ip, self.prefix_len).ok())
+    }
+
+    /// Build a request of `kind` (e.g. RTM_NEWADDR or RTM_DELADDR) for this address
+    pub fn to_request(&self, kind: u16, flags: NetlinkMsgFlags) -> NetlinkPacket<'static> {
+        let mut ifa = IfAddrRequestBuilder::new().with_ifa(|mut ifaddr| {
+            ifaddr.set_family(self.family);
+            ifaddr.set_prefix_len(self.prefix_len);
+            ifaddr.set_flags(self.flags);
+            ifaddr.set_scope(self.scope);
+            ifaddr.set_index(self.index);
+        });
+        let attrs = [(IFA_ADDRESS, self.address), (IFA_LOCAL, self.local),
+                     (IFA_BROADCAST, self.broadcast), (IFA_ANYCAST, self.anycast)];
+        for &(rta_type, ip) in attrs.iter() {
+            if let Some(ip) = ip {
+                ifa = ifa.append(RtAttrPacket::create_with_payload(rta_type, ip));
+            }
+        }
+        if let Some(ref label) = self.label {
+            ifa = ifa.append(RtAttrPacket::create_with_payload(IFA_LABEL, label.as_str()));
+        }
+        NetlinkRequestBuilder::new(kind, flags).append(ifa.build()).build()
+    }
+}
+
 struct IfAddrRequestBuilder {
     data: Vec<u8>,
 }
@@ -417,7 +559,7 @@ impl IfAddrRequestBuilder {
 
     pub fn append(mut self, rta: RtAttrPacket) -> Self {
         let len = rta.get_rta_len() as usize;
-        let aligned_len = ::util::align(len);
+        let aligned_len = crate::util::align(len);
         self.data.extend_from_slice(&rta.packet()[0..len]);
         // add padding for alignment
         for _ in len..aligned_len {
@@ -433,8 +575,8 @@ impl IfAddrRequestBuilder {
 
 #[test]
 fn dump_addrs() {
-    use packet::netlink::NetlinkConnection;
-    use packet::route::addr::Addresses;
+    use crate::packet::netlink::NetlinkConnection;
+    use crate::packet::route::addr::Addresses;
 
     let mut conn = NetlinkConnection::new();
     for addr in conn.iter_addrs(None).unwrap() {
@@ -445,7 +587,7 @@ fn dump_addrs() {
 /*
 #[test]
 fn check_lo_addr() {
-    use packet::route::link::LinkManager;
+    use crate::packet::route::link::LinkManager;
     let mut conn = NetlinkConnection::new();
     let lo = LinkManager::new(&mut conn).get_link_by_name("lo").unwrap();
     let mut addrs = AddrManager::new(&mut conn);
@@ -455,7 +597,7 @@ fn check_lo_addr() {
 
 #[test]
 fn add_lo_addr() {
-     use packet::route::link::LinkManager;
+     use crate::packet::route::link::LinkManager;
     let mut conn = NetlinkConnection::new();
     let lo = LinkManager::new(&mut conn).get_link_by_name("lo").unwrap();
     let mut addrman = AddrManager::new(&mut conn);
@@ -0,0 +1,475 @@
+//! Bonding (link aggregation) interfaces
+//!
+//! Slaves are enslaved and released with `Links::link_set_master`; the
+//! kernel requires them to be down while being enslaved.
+//!
+//! # Example
+//! ```no_run
+//! use std::time::Duration;
+//! use pnetlink::packet::netlink::NetlinkConnection;
+//! use pnetlink::packet::route::link::{Links,LinkBuilder};
+//! use pnetlink::packet::route::bond::{Bond,BondMode,LacpRate,XmitHashPolicy};
+//!
+//! let mut conn = NetlinkConnection::new();
+//! conn.new_link(&LinkBuilder::new("bond0").kind(Bond::new(BondMode::Ieee8023AD)
+//!     .miimon(Duration::from_millis(100)).lacp_rate(LacpRate::Fast)
+//!     .xmit_hash_policy(XmitHashPolicy::Layer34))).unwrap();
+//! let bond0 = conn.get_link_by_name("bond0").unwrap().unwrap();
+//! for name in &["eth0", "eth1"] {
+//!     let slave = conn.get_link_by_name(name).unwrap().unwrap();
+//!     conn.link_set_down(slave.get_index()).unwrap();
+//!     conn.link_set_master(slave.get_index(), Some(bond0.get_index())).unwrap();
+//! }
+//! ```
+use crate::packet::route::{RtAttrIterator,RtAttrPacket};
+use crate::packet::route::link::{LinkKind,push_opt};
+use byteorder::{ByteOrder,NativeEndian};
+use pnet::packet::Packet;
+use pnet::util::MacAddr;
+use std::time::Duration;
+
+pub const IFLA_BOND_UNSPEC: u16 = 0;
+pub const IFLA_BOND_MODE: u16 = 1;
+pub const IFLA_BOND_ACTIVE_SLAVE: u16 = 2;
+pub const IFLA_BOND_MIIMON: u16 = 3;
+pub const IFLA_BOND_UPDELAY: u16 = 4;
+pub const IFLA_BOND_DOWNDELAY: u16 = 5;
+pub const IFLA_BOND_USE_CARRIER: u16 = 6;
+pub const IFLA_BOND_ARP_INTERVAL: u16 = 7;
+pub const IFLA_BOND_ARP_IP_TARGET: u16 = 8;
+pub const IFLA_BOND_ARP_VALIDATE: u16 = 9;
+pub const IFLA_BOND_ARP_ALL_TARGETS: u16 = 10;
+pub const IFLA_BOND_PRIMARY: u16 = 11;
+pub const IFLA_BOND_PRIMARY_RESELECT: u16 = 12;
+pub const IFLA_BOND_FAIL_OVER_MAC: u16 = 13;
+pub const IFLA_BOND_XMIT_HASH_POLICY: u16 = 14;
+pub const IFLA_BOND_RESEND_IGMP: u16 = 15;
+pub const IFLA_BOND_NUM_PEER_NOTIF: u16 = 16;
+pub const IFLA_BOND_ALL_SLAVES_ACTIVE: u16 = 17;
+pub const IFLA_BOND_MIN_LINKS: u16 = 18;
+pub const IFLA_BOND_LP_INTERVAL: u16 = 19;
+pub const IFLA_BOND_PACKETS_PER_SLAVE: u16 = 20;
+pub const IFLA_BOND_AD_LACP_RATE: u16 = 21;
+pub const IFLA_BOND_AD_SELECT: u16 = 22;
+pub const IFLA_BOND_AD_INFO: u16 = 23;
+
+pub const IFLA_BOND_SLAVE_UNSPEC: u16 = 0;
+pub const IFLA_BOND_SLAVE_STATE: u16 = 1;
+pub const IFLA_BOND_SLAVE_MII_STATUS: u16 = 2;
+pub const IFLA_BOND_SLAVE_LINK_FAILURE_COUNT: u16 = 3;
+pub const IFLA_BOND_SLAVE_PERM_HWADDR: u16 = 4;
+pub const IFLA_BOND_SLAVE_QUEUE_ID: u16 = 5;
+pub const IFLA_BOND_SLAVE_AD_AGGREGATOR_ID: u16 = 6;
+
+/// Bonding mode
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum BondMode {
+    BalanceRr,
+    ActiveBackup,
+    BalanceXor,
+    Broadcast,
+    /// LACP
+    Ieee8023AD,
+    BalanceTlb,
+    BalanceAlb,
+    Other(u8),
+}
+
+impl BondMode {
+    pub fn new(val: u8) -> Self {
+        match val {
+            0 => BondMode::BalanceRr,
+            1 => BondMode::ActiveBackup,
+            2 => BondMode::BalanceXor,
+            3 => BondMode::Broadcast,
+            4 => BondMode::Ieee8023AD,
+            5 => BondMode::BalanceTlb,
+            6 => BondMode::BalanceAlb,
+            _ => BondMode::Other(val),
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match *self {
+            BondMode::BalanceRr => 0,
+            BondMode::ActiveBackup => 1,
+            BondMode::BalanceXor => 2,
+            BondMode::Broadcast => 3,
+            BondMode::Ieee8023AD => 4,
+            BondMode::BalanceTlb => 5,
+            BondMode::BalanceAlb => 6,
+            BondMode::Other(val) => val,
+        }
+    }
+}
+
+/// Slave selection for transmission in xor and 802.3ad modes
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum XmitHashPolicy {
+    Layer2,
+    Layer34,
+    Layer23,
+    Encap23,
+    Encap34,
+    VlanSrcMac,
+    Other(u8),
+}
+
+impl XmitHashPolicy {
+    pub fn new(val: u8) -> Self {
+        match val {
+            0 => XmitHashPolicy::Layer2,
+            1 => XmitHashPolicy::Layer34,
+            2 => XmitHashPolicy::Layer23,
+            3 => XmitHashPolicy::Encap23,
+            4 => XmitHashPolicy::Encap34,
+            5 => XmitHashPolicy::VlanSrcMac,
+            _ => XmitHashPolicy::Other(val),
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match *self {
+            XmitHashPolicy::Layer2 => 0,
+            XmitHashPolicy::Layer34 => 1,
+            XmitHashPolicy::Layer23 => 2,
+            XmitHashPolicy::Encap23 => 3,
+            XmitHashPolicy::Encap34 => 4,
+            XmitHashPolicy::VlanSrcMac => 5,
+            XmitHashPolicy::Other(val) => val,
+        }
+    }
+}
+
+/// Rate of LACPDUs requested from the partner
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum LacpRate {
+    /// Every 30 seconds
+    Slow,
+    /// Every second
+    Fast,
+    Other(u8),
+}
+
+impl LacpRate {
+    pub fn new(val: u8) -> Self {
+        match val {
+            0 => LacpRate::Slow,
+            1 => LacpRate::Fast,
+            _ => LacpRate::Other(val),
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match *self {
+            LacpRate::Slow => 0,
+            LacpRate::Fast => 1,
+            LacpRate::Other(val) => val,
+        }
+    }
+}
+
+/// 802.3ad aggregator selection
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum AdSelect {
+    Stable,
+    Bandwidth,
+    Count,
+    Other(u8),
+}
+
+impl AdSelect {
+    pub fn new(val: u8) -> Self {
+        match val {
+            0 => AdSelect::Stable,
+            1 => AdSelect::Bandwidth,
+            2 => AdSelect::Count,
+            _ => AdSelect::Other(val),
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match *self {
+            AdSelect::Stable => 0,
+            AdSelect::Bandwidth => 1,
+            AdSelect::Count => 2,
+            AdSelect::Other(val) => val,
+        }
+    }
+}
+
+fn millis(duration: Duration) -> u32 {
+    (duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64) as u32
+}
+
+/// Bond options, unset ones keep the kernel defaults
+///
+/// Settings read back from a link are a `BondInfo`; the kernel refuses a
+/// mode change on a bond that is up.
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+pub struct Bond {
+    mode: Option<BondMode>,
+    miimon: Option<Duration>,
+    updelay: Option<Duration>,
+    downdelay: Option<Duration>,
+    xmit_hash_policy: Option<XmitHashPolicy>,
+    lacp_rate: Option<LacpRate>,
+    ad_select: Option<AdSelect>,
+    primary: Option<u32>,
+    min_links: Option<u32>,
+}
+
+impl Bond {
+    pub fn new(mode: BondMode) -> Self {
+        Bond { mode: Some(mode), ..Bond::default() }
+    }
+
+    /// MII link monitoring interval
+    pub fn miimon(mut self, interval: Duration) -> Self {
+        self.miimon = Some(interval);
+        self
+    }
+
+    /// Time a slave must be up before it is used
+    pub fn updelay(mut self, delay: Duration) -> Self {
+        self.updelay = Some(delay);
+        self
+    }
+
+    /// Time a slave must be down before it is disabled
+    pub fn downdelay(mut self, delay: Duration) -> Self {
+        self.downdelay = Some(delay);
+        self
+    }
+
+    pub fn xmit_hash_policy(mut self, policy: XmitHashPolicy) -> Self {
+        self.xmit_hash_policy = Some(policy);
+        self
+    }
+
+    pub fn lacp_rate(mut self, rate: LacpRate) -> Self {
+        self.lacp_rate = Some(rate);
+        self
+    }
+
+    pub fn ad_select(mut self, select: AdSelect) -> Self {
+        self.ad_select = Some(select);
+        self
+    }
+
+    /// Index of the preferred slave in active-backup mode
+    pub fn primary(mut self, index: u32) -> Self {
+        self.primary = Some(index);
+        self
+    }
+
+    pub fn min_links(mut self, min_links: u32) -> Self {
+        self.min_links = Some(min_links);
+        self
+    }
+}
+
+impl LinkKind for Bond {
+    fn kind(&self) -> &str {
+        "bond"
+    }
+
+    fn info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        let mut data = vec![];
+        // mode first, the kernel rejects options not valid for the current mode
+        push_opt(&mut data, IFLA_BOND_MODE, self.mode.map(|mode| mode.value()));
+        push_opt(&mut data, IFLA_BOND_XMIT_HASH_POLICY, self.xmit_hash_policy.map(|policy| policy.value()));
+        push_opt(&mut data, IFLA_BOND_AD_LACP_RATE, self.lacp_rate.map(|rate| rate.value()));
+        push_opt(&mut data, IFLA_BOND_AD_SELECT, self.ad_select.map(|select| select.value()));
+        push_opt(&mut data, IFLA_BOND_MIIMON, self.miimon.map(millis));
+        push_opt(&mut data, IFLA_BOND_UPDELAY, self.updelay.map(millis));
+        push_opt(&mut data, IFLA_BOND_DOWNDELAY, self.downdelay.map(millis));
+        push_opt(&mut data, IFLA_BOND_PRIMARY, self.primary);
+        push_opt(&mut data, IFLA_BOND_MIN_LINKS, self.min_links);
+        data
+    }
+}
+
+/// Bond settings of an existing link
+#[derive(Debug,Clone,Default,PartialEq,Eq,Hash)]
+pub struct BondInfo {
+    pub mode: Option<BondMode>,
+    pub miimon: Option<Duration>,
+    pub updelay: Option<Duration>,
+    pub downdelay: Option<Duration>,
+    pub xmit_hash_policy: Option<XmitHashPolicy>,
+    pub lacp_rate: Option<LacpRate>,
+    pub ad_select: Option<AdSelect>,
+    pub primary: Option<u32>,
+    /// Index of the slave currently in use
+    pub active_slave: Option<u32>,
+    pub min_links: Option<u32>,
+}
+
+impl BondInfo {
+    /// Parse `IFLA_INFO_DATA` of a "bond" link
+    pub fn from_info_data(data: &[u8]) -> Self {
+        let mut info = BondInfo::default();
+        for rta in RtAttrIterator::new(data) {
+            let payload = rta.payload();
+            if payload.is_empty() {
+                continue;
+            }
+            let u32_val = if payload.len() >= 4 { Some(NativeEndian::read_u32(payload)) } else { None };
+            let ms = u32_val.map(|val| Duration::from_millis(val as u64));
+            match rta.get_rta_type() {
+                IFLA_BOND_MODE => info.mode = Some(BondMode::new(payload[0])),
+                IFLA_BOND_MIIMON => info.miimon = ms,
+                IFLA_BOND_UPDELAY => info.updelay = ms,
+                IFLA_BOND_DOWNDELAY => info.downdelay = ms,
+                IFLA_BOND_XMIT_HASH_POLICY => info.xmit_hash_policy = Some(XmitHashPolicy::new(payload[0])),
+                IFLA_BOND_AD_LACP_RATE => info.lacp_rate = Some(LacpRate::new(payload[0])),
+                IFLA_BOND_AD_SELECT => info.ad_select = Some(AdSelect::new(payload[0])),
+                IFLA_BOND_PRIMARY => info.primary = u32_val,
+                IFLA_BOND_ACTIVE_SLAVE => info.active_slave = u32_val,
+                IFLA_BOND_MIN_LINKS => info.min_links = u32_val,
+                _ => {},
+            }
+        }
+        info
+    }
+}
+
+/// Role of a slave
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum BondSlaveState {
+    Active,
+    Backup,
+    Other(u8),
+}
+
+/// MII link state of a slave
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum MiiStatus {
+    Up,
+    /// Down, waiting for `downdelay`
+    Fail,
+    Down,
+    /// Up, waiting for `updelay`
+    Back,
+    Other(u8),
+}
+
+/// State of a bond slave
+#[derive(Debug,Clone,Default,PartialEq,Eq,Hash)]
+pub struct BondSlave {
+    pub state: Option<BondSlaveState>,
+    pub mii_status: Option<MiiStatus>,
+    pub link_failure_count: Option<u32>,
+    /// Address of the slave before the bond changed it
+    pub perm_hwaddr: Option<MacAddr>,
+    pub queue_id: Option<u16>,
+    /// 802.3ad aggregator the slave belongs to
+    pub aggregator_id: Option<u16>,
+}
+
+impl BondSlave {
+    /// Parse `IFLA_INFO_SLAVE_DATA` of a bond slave
+    pub fn from_slave_data(data: &[u8]) -> Self {
+        let mut slave = BondSlave::default();
+        for rta in RtAttrIterator::new(data) {
+            let payload = rta.payload();
+            match rta.get_rta_type() {
+                IFLA_BOND_SLAVE_STATE if payload.len() >= 1 => slave.state = Some(match payload[0] {
+                    0 => BondSlaveState::Active,
+                    1 => BondSlaveState::Backup,
+                    val => BondSlaveState::Other(val),
+                }),
+                IFLA_BOND_SLAVE_MII_STATUS if payload.len() >= 1 => slave.mii_status = Some(match payload[0] {
+                    0 => MiiStatus::Up,
+                    1 => MiiStatus::Fail,
+                    2 => MiiStatus::Down,
+                    3 => MiiStatus::Back,
+                    val => MiiStatus::Other(val),
+                }),
+                IFLA_BOND_SLAVE_LINK_FAILURE_COUNT if payload.len() >= 4 => {
+                    slave.link_failure_count = Some(NativeEndian::read_u32(payload));
+                },
+                IFLA_BOND_SLAVE_PERM_HWADDR if payload.len() >= 6 => {
+                    slave.perm_hwaddr = Some(MacAddr::new(payload[0], payload[1], payload[2], payload[3], payload[4], payload[5]));
+                },
+                IFLA_BOND_SLAVE_QUEUE_ID if payload.len() >= 2 => slave.queue_id = Some(NativeEndian::read_u16(payload)),
+                IFLA_BOND_SLAVE_AD_AGGREGATOR_ID if payload.len() >= 2 => {
+                    slave.aggregator_id = Some(NativeEndian::read_u16(payload));
+                },
+                _ => {},
+            }
+        }
+        slave
+    }
+}
+
+mod tests {
+    #[test]
+    fn bond_encoding() {
+        use crate::packet::route::link::{Link,LinkBuilder};
+        use crate::packet::route::bond::{Bond,BondInfo,BondMode,LacpRate,AdSelect,XmitHashPolicy};
+        use std::time::Duration;
+
+        let bond = Bond::new(BondMode::Ieee8023AD).miimon(Duration::from_millis(100))
+            .updelay(Duration::from_millis(200)).downdelay(Duration::from_millis(300))
+            .xmit_hash_policy(XmitHashPolicy::Layer34).lacp_rate(LacpRate::Fast)
+            .ad_select(AdSelect::Bandwidth).min_links(1);
+        let link = Link::from_packet(LinkBuilder::new("pnl-bond0").kind(bond).request().unwrap()).unwrap();
+        assert_eq!(link.get_bond(), Some(BondInfo {
+            mode: Some(BondMode::Ieee8023AD),
+            miimon: Some(Duration::from_millis(100)),
+            updelay: Some(Duration::from_millis(200)),
+            downdelay: Some(Duration::from_millis(300)),
+            xmit_hash_policy: Some(XmitHashPolicy::Layer34),
+            lacp_rate: Some(LacpRate::Fast),
+            ad_select: Some(AdSelect::Bandwidth),
+            min_links: Some(1),
+            ..BondInfo::default()
+        }));
+        assert!(link.get_bond_slave().is_none());
+        // the mode is left out unless given
+        let link = Link::from_packet(LinkBuilder::new("pnl-bond0").kind(Bond::default().min_links(2)).request().unwrap()).unwrap();
+        assert_eq!(link.get_bond(), Some(BondInfo { min_links: Some(2), ..BondInfo::default() }));
+    }
+
+    #[test]
+    // root permissions required
+    fn create_bond() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::{Links,LinkBuilder,LinkType};
+        use crate::packet::route::bond::{Bond,BondMode,BondSlaveState};
+        use std::time::Duration;
+
+        let mut conn = NetlinkConnection::new();
+        conn.new_link(&LinkBuilder::new("pnl-bondm0")
+                      .kind(Bond::new(BondMode::ActiveBackup).miimon(Duration::from_millis(100)))).unwrap();
+        let bond = conn.get_link_by_name("pnl-bondm0").unwrap().unwrap();
+        let mut slaves = vec![];
+        for name in &["pnl-bonds0", "pnl-bonds1"] {
+            conn.new_link(&LinkBuilder::new(name).kind(LinkType::Dummy)).unwrap();
+            let slave = conn.get_link_by_name(name).unwrap().unwrap();
+            conn.link_set_master(slave.get_index(), Some(bond.get_index())).unwrap();
+            slaves.push(slave.get_index());
+        }
+        conn.link_set_up(bond.get_index()).unwrap();
+
+        let info = conn.get_link_by_index(bond.get_index()).unwrap().unwrap().get_bond().unwrap();
+        assert_eq!(info.mode, Some(BondMode::ActiveBackup));
+        assert_eq!(info.miimon, Some(Duration::from_millis(100)));
+        let states: Vec<_> = slaves.iter()
+            .map(|&index| conn.get_link_by_index(index).unwrap().unwrap().get_bond_slave().unwrap().state)
+            .collect();
+        assert_eq!(states.iter().filter(|&&state| state == Some(BondSlaveState::Active)).count(), 1);
+        assert_eq!(states.iter().filter(|&&state| state == Some(BondSlaveState::Backup)).count(), 1);
+
+        conn.link_set_master(slaves[1], None).unwrap();
+        let slave = conn.get_link_by_index(slaves[1]).unwrap().unwrap();
+        assert!(slave.get_bond_slave().is_none());
+        conn.delete_link(bond).unwrap();
+        for &index in slaves.iter() {
+            let slave = conn.get_link_by_index(index).unwrap().unwrap();
+            conn.delete_link(slave).unwrap();
+        }
+    }
+}
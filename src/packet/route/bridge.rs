@@ -0,0 +1,430 @@
+//! Linux bridges
+//!
+//! Bridge options are set on creation through `LinkBuilder::kind` and
+//! changed later with `Links::link_set_options`. Ports are enslaved with
+//! `Links::link_set_master`, and configured with `Links::link_set_bridge_port`.
+//!
+//! # Example
+//! ```no_run
+//! use std::time::Duration;
+//! use pnetlink::packet::netlink::NetlinkConnection;
+//! use pnetlink::packet::route::link::{Links,LinkBuilder};
+//! use pnetlink::packet::route::bridge::{Bridge,BridgePort};
+//!
+//! let mut conn = NetlinkConnection::new();
+//! conn.new_link(&LinkBuilder::new("br0")
+//!     .kind(Bridge::new().stp(true).forward_delay(Duration::from_secs(4)).vlan_filtering(true))
+//!     .up(true)).unwrap();
+//! let br0 = conn.get_link_by_name("br0").unwrap().unwrap();
+//! let eth1 = conn.get_link_by_name("eth1").unwrap().unwrap();
+//! conn.link_set_master(eth1.get_index(), Some(br0.get_index())).unwrap();
+//! conn.link_set_bridge_port(eth1.get_index(), &BridgePort::new().cost(100).learning(false)).unwrap();
+//! ```
+use crate::packet::route::{RtAttrIterator,RtAttrPacket,NLA_F_NESTED};
+use crate::packet::route::link::{LinkKind,IFLA_PROTINFO,nested,push_opt};
+use byteorder::{ByteOrder,NativeEndian};
+use pnet::packet::Packet;
+use std::time::Duration;
+
+pub const IFLA_BR_UNSPEC: u16 = 0;
+pub const IFLA_BR_FORWARD_DELAY: u16 = 1;
+pub const IFLA_BR_HELLO_TIME: u16 = 2;
+pub const IFLA_BR_MAX_AGE: u16 = 3;
+pub const IFLA_BR_AGEING_TIME: u16 = 4;
+pub const IFLA_BR_STP_STATE: u16 = 5;
+pub const IFLA_BR_PRIORITY: u16 = 6;
+pub const IFLA_BR_VLAN_FILTERING: u16 = 7;
+pub const IFLA_BR_VLAN_PROTOCOL: u16 = 8;
+pub const IFLA_BR_GROUP_FWD_MASK: u16 = 9;
+pub const IFLA_BR_ROOT_ID: u16 = 10;
+pub const IFLA_BR_BRIDGE_ID: u16 = 11;
+pub const IFLA_BR_ROOT_PORT: u16 = 12;
+pub const IFLA_BR_ROOT_PATH_COST: u16 = 13;
+pub const IFLA_BR_MCAST_SNOOPING: u16 = 23;
+pub const IFLA_BR_VLAN_DEFAULT_PVID: u16 = 39;
+
+pub const IFLA_BRPORT_UNSPEC: u16 = 0;
+pub const IFLA_BRPORT_STATE: u16 = 1;
+pub const IFLA_BRPORT_PRIORITY: u16 = 2;
+pub const IFLA_BRPORT_COST: u16 = 3;
+pub const IFLA_BRPORT_MODE: u16 = 4;
+pub const IFLA_BRPORT_GUARD: u16 = 5;
+pub const IFLA_BRPORT_PROTECT: u16 = 6;
+pub const IFLA_BRPORT_FAST_LEAVE: u16 = 7;
+pub const IFLA_BRPORT_LEARNING: u16 = 8;
+pub const IFLA_BRPORT_UNICAST_FLOOD: u16 = 9;
+pub const IFLA_BRPORT_PROXYARP: u16 = 10;
+pub const IFLA_BRPORT_MCAST_FLOOD: u16 = 27;
+pub const IFLA_BRPORT_BCAST_FLOOD: u16 = 30;
+pub const IFLA_BRPORT_NEIGH_SUPPRESS: u16 = 32;
+pub const IFLA_BRPORT_ISOLATED: u16 = 33;
+
+// timers are exchanged in clock_t, i.e. USER_HZ (100) ticks per second
+fn to_clock_t(duration: Duration) -> u32 {
+    (duration.as_secs() * 100 + (duration.subsec_nanos() / 10_000_000) as u64) as u32
+}
+
+fn from_clock_t(ticks: u32) -> Duration {
+    Duration::from_millis(ticks as u64 * 10)
+}
+
+/// Bridge options, unset ones keep the kernel defaults
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+pub struct Bridge {
+    stp: Option<bool>,
+    forward_delay: Option<Duration>,
+    hello_time: Option<Duration>,
+    max_age: Option<Duration>,
+    ageing_time: Option<Duration>,
+    priority: Option<u16>,
+    vlan_filtering: Option<bool>,
+    default_pvid: Option<u16>,
+    multicast_snooping: Option<bool>,
+}
+
+impl Bridge {
+    pub fn new() -> Self {
+        Bridge::default()
+    }
+
+    /// Enable kernel spanning tree protocol
+    pub fn stp(mut self, on: bool) -> Self {
+        self.stp = Some(on);
+        self
+    }
+
+    pub fn forward_delay(mut self, delay: Duration) -> Self {
+        self.forward_delay = Some(delay);
+        self
+    }
+
+    pub fn hello_time(mut self, time: Duration) -> Self {
+        self.hello_time = Some(time);
+        self
+    }
+
+    pub fn max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+
+    /// Time learned FDB entries are kept
+    pub fn ageing_time(mut self, time: Duration) -> Self {
+        self.ageing_time = Some(time);
+        self
+    }
+
+    /// Bridge priority for STP root election
+    pub fn priority(mut self, priority: u16) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn vlan_filtering(mut self, on: bool) -> Self {
+        self.vlan_filtering = Some(on);
+        self
+    }
+
+    /// VLAN assigned to untagged traffic of new ports, 0 for none
+    pub fn default_pvid(mut self, pvid: u16) -> Self {
+        self.default_pvid = Some(pvid);
+        self
+    }
+
+    pub fn multicast_snooping(mut self, on: bool) -> Self {
+        self.multicast_snooping = Some(on);
+        self
+    }
+}
+
+impl LinkKind for Bridge {
+    fn kind(&self) -> &str {
+        "bridge"
+    }
+
+    fn info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        let mut data = vec![];
+        push_opt(&mut data, IFLA_BR_FORWARD_DELAY, self.forward_delay.map(to_clock_t));
+        push_opt(&mut data, IFLA_BR_HELLO_TIME, self.hello_time.map(to_clock_t));
+        push_opt(&mut data, IFLA_BR_MAX_AGE, self.max_age.map(to_clock_t));
+        push_opt(&mut data, IFLA_BR_AGEING_TIME, self.ageing_time.map(to_clock_t));
+        push_opt(&mut data, IFLA_BR_STP_STATE, self.stp.map(|on| on as u32));
+        push_opt(&mut data, IFLA_BR_PRIORITY, self.priority);
+        push_opt(&mut data, IFLA_BR_VLAN_FILTERING, self.vlan_filtering.map(|on| on as u8));
+        push_opt(&mut data, IFLA_BR_VLAN_DEFAULT_PVID, self.default_pvid);
+        push_opt(&mut data, IFLA_BR_MCAST_SNOOPING, self.multicast_snooping.map(|on| on as u8));
+        data
+    }
+}
+
+/// Bridge settings of an existing link
+#[derive(Debug,Clone,Default,PartialEq,Eq,Hash)]
+pub struct BridgeInfo {
+    pub stp: Option<bool>,
+    pub forward_delay: Option<Duration>,
+    pub hello_time: Option<Duration>,
+    pub max_age: Option<Duration>,
+    pub ageing_time: Option<Duration>,
+    pub priority: Option<u16>,
+    pub vlan_filtering: Option<bool>,
+    pub default_pvid: Option<u16>,
+    pub multicast_snooping: Option<bool>,
+}
+
+impl BridgeInfo {
+    /// Parse `IFLA_INFO_DATA` of a "bridge" link
+    pub fn from_info_data(data: &[u8]) -> Self {
+        let mut info = BridgeInfo::default();
+        for rta in RtAttrIterator::new(data) {
+            let payload = rta.payload();
+            match rta.get_rta_type() {
+                IFLA_BR_FORWARD_DELAY if payload.len() >= 4 => info.forward_delay = Some(from_clock_t(NativeEndian::read_u32(payload))),
+                IFLA_BR_HELLO_TIME if payload.len() >= 4 => info.hello_time = Some(from_clock_t(NativeEndian::read_u32(payload))),
+                IFLA_BR_MAX_AGE if payload.len() >= 4 => info.max_age = Some(from_clock_t(NativeEndian::read_u32(payload))),
+                IFLA_BR_AGEING_TIME if payload.len() >= 4 => info.ageing_time = Some(from_clock_t(NativeEndian::read_u32(payload))),
+                IFLA_BR_STP_STATE if payload.len() >= 4 => info.stp = Some(NativeEndian::read_u32(payload) != 0),
+                IFLA_BR_PRIORITY if payload.len() >= 2 => info.priority = Some(NativeEndian::read_u16(payload)),
+                IFLA_BR_VLAN_FILTERING if payload.len() >= 1 => info.vlan_filtering = Some(payload[0] != 0),
+                IFLA_BR_VLAN_DEFAULT_PVID if payload.len() >= 2 => info.default_pvid = Some(NativeEndian::read_u16(payload)),
+                IFLA_BR_MCAST_SNOOPING if payload.len() >= 1 => info.multicast_snooping = Some(payload[0] != 0),
+                _ => {},
+            }
+        }
+        info
+    }
+}
+
+/// STP state of a bridge port
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum StpState {
+    Disabled,
+    Listening,
+    Learning,
+    Forwarding,
+    Blocking,
+    Other(u8),
+}
+
+impl StpState {
+    pub fn new(val: u8) -> Self {
+        match val {
+            0 => StpState::Disabled,
+            1 => StpState::Listening,
+            2 => StpState::Learning,
+            3 => StpState::Forwarding,
+            4 => StpState::Blocking,
+            _ => StpState::Other(val),
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match *self {
+            StpState::Disabled => 0,
+            StpState::Listening => 1,
+            StpState::Learning => 2,
+            StpState::Forwarding => 3,
+            StpState::Blocking => 4,
+            StpState::Other(val) => val,
+        }
+    }
+}
+
+/// Bridge port settings, also used for the values read back
+///
+/// Unset fields are left unchanged when applied, and None when read if the
+/// kernel does not report them.
+#[derive(Debug,Clone,Default,PartialEq,Eq,Hash)]
+pub struct BridgePort {
+    pub state: Option<StpState>,
+    pub cost: Option<u32>,
+    pub priority: Option<u16>,
+    pub learning: Option<bool>,
+    pub unicast_flood: Option<bool>,
+    pub multicast_flood: Option<bool>,
+    pub broadcast_flood: Option<bool>,
+    /// Send frames back out of the port they came in on
+    pub hairpin: Option<bool>,
+    /// Only forward to and from non-isolated ports
+    pub isolated: Option<bool>,
+    /// Answer ARP and neighbour solicitations from the bridge's neighbour table
+    pub neigh_suppress: Option<bool>,
+}
+
+impl BridgePort {
+    pub fn new() -> Self {
+        BridgePort::default()
+    }
+
+    pub fn state(mut self, state: StpState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// STP path cost
+    pub fn cost(mut self, cost: u32) -> Self {
+        self.cost = Some(cost);
+        self
+    }
+
+    /// STP port priority
+    pub fn priority(mut self, priority: u16) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn learning(mut self, on: bool) -> Self {
+        self.learning = Some(on);
+        self
+    }
+
+    /// Flood unknown unicast, multicast and broadcast traffic to the port
+    pub fn flood(mut self, unicast: bool, multicast: bool, broadcast: bool) -> Self {
+        self.unicast_flood = Some(unicast);
+        self.multicast_flood = Some(multicast);
+        self.broadcast_flood = Some(broadcast);
+        self
+    }
+
+    pub fn hairpin(mut self, on: bool) -> Self {
+        self.hairpin = Some(on);
+        self
+    }
+
+    pub fn isolated(mut self, on: bool) -> Self {
+        self.isolated = Some(on);
+        self
+    }
+
+    pub fn neigh_suppress(mut self, on: bool) -> Self {
+        self.neigh_suppress = Some(on);
+        self
+    }
+
+    fn flags(&self) -> [(u16, Option<bool>); 7] {
+        [(IFLA_BRPORT_LEARNING, self.learning), (IFLA_BRPORT_UNICAST_FLOOD, self.unicast_flood),
+         (IFLA_BRPORT_MCAST_FLOOD, self.multicast_flood), (IFLA_BRPORT_BCAST_FLOOD, self.broadcast_flood),
+         (IFLA_BRPORT_MODE, self.hairpin), (IFLA_BRPORT_ISOLATED, self.isolated),
+         (IFLA_BRPORT_NEIGH_SUPPRESS, self.neigh_suppress)]
+    }
+
+    /// Build the nested `IFLA_PROTINFO` attribute of an `AF_BRIDGE` request
+    pub fn protinfo(&self) -> RtAttrPacket<'static> {
+        let mut attrs = vec![];
+        push_opt(&mut attrs, IFLA_BRPORT_STATE, self.state.map(|state| state.value()));
+        push_opt(&mut attrs, IFLA_BRPORT_PRIORITY, self.priority);
+        push_opt(&mut attrs, IFLA_BRPORT_COST, self.cost);
+        for &(kind, val) in self.flags().iter() {
+            push_opt(&mut attrs, kind, val.map(|on| on as u8));
+        }
+        // without the nested flag the kernel takes the payload for a bare STP state
+        nested(IFLA_PROTINFO | NLA_F_NESTED, &attrs)
+    }
+
+    /// Parse `IFLA_BRPORT_*` attributes, from `IFLA_PROTINFO` or `IFLA_INFO_SLAVE_DATA`
+    pub fn from_attrs(data: &[u8]) -> Self {
+        let mut port = BridgePort::default();
+        for rta in RtAttrIterator::new(data) {
+            let payload = rta.payload();
+            if payload.is_empty() {
+                continue;
+            }
+            let flag = Some(payload[0] != 0);
+            match rta.get_rta_type() {
+                IFLA_BRPORT_STATE => port.state = Some(StpState::new(payload[0])),
+                IFLA_BRPORT_PRIORITY if payload.len() >= 2 => port.priority = Some(NativeEndian::read_u16(payload)),
+                IFLA_BRPORT_COST if payload.len() >= 4 => port.cost = Some(NativeEndian::read_u32(payload)),
+                IFLA_BRPORT_LEARNING => port.learning = flag,
+                IFLA_BRPORT_UNICAST_FLOOD => port.unicast_flood = flag,
+                IFLA_BRPORT_MCAST_FLOOD => port.multicast_flood = flag,
+                IFLA_BRPORT_BCAST_FLOOD => port.broadcast_flood = flag,
+                IFLA_BRPORT_MODE => port.hairpin = flag,
+                IFLA_BRPORT_ISOLATED => port.isolated = flag,
+                IFLA_BRPORT_NEIGH_SUPPRESS => port.neigh_suppress = flag,
+                _ => {},
+            }
+        }
+        port
+    }
+}
+
+mod tests {
+    #[test]
+    fn bridge_encoding() {
+        use crate::packet::route::link::{Link,LinkBuilder};
+        use crate::packet::route::bridge::{Bridge,BridgeInfo};
+        use std::time::Duration;
+
+        let bridge = Bridge::new().stp(true).forward_delay(Duration::from_millis(4500))
+            .ageing_time(Duration::from_secs(30)).priority(4096)
+            .vlan_filtering(true).default_pvid(10).multicast_snooping(false);
+        let link = Link::from_packet(LinkBuilder::new("pnl-br0").kind(bridge).request().unwrap()).unwrap();
+        assert_eq!(link.get_bridge(), Some(BridgeInfo {
+            stp: Some(true),
+            forward_delay: Some(Duration::from_millis(4500)),
+            ageing_time: Some(Duration::from_secs(30)),
+            priority: Some(4096),
+            vlan_filtering: Some(true),
+            default_pvid: Some(10),
+            multicast_snooping: Some(false),
+            ..BridgeInfo::default()
+        }));
+        assert!(link.get_vlan().is_none());
+    }
+
+    #[test]
+    fn bridge_port_encoding() {
+        use crate::packet::route::NLA_F_NESTED;
+        use crate::packet::route::link::IFLA_PROTINFO;
+        use crate::packet::route::bridge::{BridgePort,StpState};
+        use pnet::packet::Packet;
+
+        let port = BridgePort::new().state(StpState::Blocking).cost(200).priority(16)
+            .learning(false).flood(true, false, true).hairpin(true).isolated(false).neigh_suppress(true);
+        let rta = port.protinfo();
+        assert_eq!(rta.get_rta_type(), IFLA_PROTINFO | NLA_F_NESTED);
+        assert_eq!(BridgePort::from_attrs(rta.payload()), port);
+        assert_eq!(BridgePort::from_attrs(BridgePort::new().protinfo().payload()), BridgePort::default());
+    }
+
+    #[test]
+    // root permissions required
+    fn create_bridge() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::{Links,LinkBuilder,LinkType};
+        use crate::packet::route::bridge::{Bridge,BridgePort};
+        use std::time::Duration;
+
+        let mut conn = NetlinkConnection::new();
+        conn.new_link(&LinkBuilder::new("pnl-br1")
+                      .kind(Bridge::new().forward_delay(Duration::from_secs(4)).vlan_filtering(true))).unwrap();
+        conn.new_link(&LinkBuilder::new("pnl-brport0").kind(LinkType::Dummy)).unwrap();
+        let bridge = conn.get_link_by_name("pnl-br1").unwrap().unwrap();
+        let port = conn.get_link_by_name("pnl-brport0").unwrap().unwrap();
+        let info = bridge.get_bridge().unwrap();
+        assert_eq!(info.forward_delay, Some(Duration::from_secs(4)));
+        assert_eq!(info.vlan_filtering, Some(true));
+
+        conn.link_set_options(bridge.get_index(), &Bridge::new().vlan_filtering(false).default_pvid(20)).unwrap();
+        let info = conn.get_link_by_name("pnl-br1").unwrap().unwrap().get_bridge().unwrap();
+        assert_eq!(info.vlan_filtering, Some(false));
+        assert_eq!(info.default_pvid, Some(20));
+        assert_eq!(info.forward_delay, Some(Duration::from_secs(4)));
+
+        conn.link_set_master(port.get_index(), Some(bridge.get_index())).unwrap();
+        let port = conn.get_link_by_name("pnl-brport0").unwrap().unwrap();
+        assert_eq!(port.get_master(), Some(bridge.get_index()));
+        conn.link_set_bridge_port(port.get_index(), &BridgePort::new().cost(42).learning(false).isolated(true)).unwrap();
+        let settings = conn.get_link_by_name("pnl-brport0").unwrap().unwrap().get_bridge_port().unwrap();
+        assert_eq!(settings.cost, Some(42));
+        assert_eq!(settings.learning, Some(false));
+        assert_eq!(settings.isolated, Some(true));
+        assert_eq!(settings.unicast_flood, Some(true));
+        conn.link_set_master(port.get_index(), None).unwrap();
+        let port = conn.get_link_by_name("pnl-brport0").unwrap().unwrap();
+        assert_eq!(port.get_master(), None);
+        assert!(port.get_bridge_port().is_none());
+
+        conn.delete_link(port).unwrap();
+        conn.delete_link(bridge).unwrap();
+    }
+}
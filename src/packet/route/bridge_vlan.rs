@@ -0,0 +1,451 @@
+//! VLAN membership of bridge ports, i.e. `bridge vlan`
+//!
+//! Membership is added and deleted through `AF_BRIDGE` link requests
+//! carrying `IFLA_BRIDGE_VLAN_INFO` in `IFLA_AF_SPEC`, and dumped the same
+//! way with `RTEXT_FILTER_BRVLAN_COMPRESSED`. Per-VLAN STP state is only
+//! available with the `RTM_*VLAN` messages (Linux 5.10+).
+//!
+//! # Example
+//! ```no_run
+//! use pnetlink::packet::netlink::NetlinkConnection;
+//! use pnetlink::packet::route::link::Links;
+//! use pnetlink::packet::route::bridge_vlan::{BridgeVlan,BridgeVlans};
+//!
+//! let mut conn = NetlinkConnection::new();
+//! let swp1 = conn.get_link_by_name("swp1").unwrap().unwrap();
+//! conn.add_bridge_vlan(&BridgeVlan::new(swp1.get_index(), 10).pvid(true).untagged(true)).unwrap();
+//! conn.add_bridge_vlan(&BridgeVlan::range(swp1.get_index(), 100, 199)).unwrap();
+//! for vlan in conn.get_bridge_vlans(Some(swp1.get_index())).unwrap() {
+//!     println!("{:?}", vlan);
+//! }
+//! ```
+use crate::packet::route::{AddressFamily,BrVlanMsgPacket,MutableBrVlanMsgPacket,RtAttrIterator,RtAttrPacket,
+                           NLA_F_NESTED,NLA_TYPE_MASK};
+use crate::packet::route::bridge::StpState;
+use crate::packet::route::link::{IfInfoPacketBuilder,Link,Links,nested,RTM_NEWLINK,RTM_GETLINK,RTM_SETLINK,RTM_DELLINK,
+                                 IFLA_AF_SPEC,IFLA_EXT_MASK};
+use crate::packet::route::route::{ToPayload,WithPayload};
+use crate::packet::netlink::{NetlinkConnection,NetlinkMsgFlags,NetlinkPacket,NetlinkReader,NetlinkRequestBuilder};
+use byteorder::{ByteOrder,NativeEndian};
+use pnet::packet::Packet;
+use std::collections::HashSet;
+use std::io::{self,Write};
+
+pub const RTM_NEWVLAN: u16 = 112;
+pub const RTM_DELVLAN: u16 = 113;
+pub const RTM_GETVLAN: u16 = 114;
+
+pub const RTEXT_FILTER_VF: u32 = 1 << 0;
+pub const RTEXT_FILTER_BRVLAN: u32 = 1 << 1;
+pub const RTEXT_FILTER_BRVLAN_COMPRESSED: u32 = 1 << 2;
+
+pub const IFLA_BRIDGE_FLAGS: u16 = 0;
+pub const IFLA_BRIDGE_MODE: u16 = 1;
+pub const IFLA_BRIDGE_VLAN_INFO: u16 = 2;
+
+pub const BRIDGE_FLAGS_MASTER: u16 = 1;
+pub const BRIDGE_FLAGS_SELF: u16 = 2;
+
+pub const BRIDGE_VLANDB_UNSPEC: u16 = 0;
+pub const BRIDGE_VLANDB_ENTRY: u16 = 1;
+
+pub const BRIDGE_VLANDB_ENTRY_UNSPEC: u16 = 0;
+pub const BRIDGE_VLANDB_ENTRY_INFO: u16 = 1;
+pub const BRIDGE_VLANDB_ENTRY_RANGE: u16 = 2;
+pub const BRIDGE_VLANDB_ENTRY_STATE: u16 = 3;
+
+bitflags! {
+    /// `struct bridge_vlan_info` flags
+    pub struct BridgeVlanFlags: u16 {
+        const MASTER = 0x1;
+        /// VLAN assigned to untagged ingress traffic
+        const PVID = 0x2;
+        /// Egress untagged
+        const UNTAGGED = 0x4;
+        const RANGE_BEGIN = 0x8;
+        const RANGE_END = 0x10;
+        /// Global bridge VLAN entry
+        const BRENTRY = 0x20;
+        const ONLY_OPTS = 0x40;
+    }
+}
+
+/// VLAN or VLAN range of a bridge port, or of the bridge itself
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub struct BridgeVlan {
+    /// Port index, or bridge index with `bridge_self`
+    pub index: u32,
+    pub vid: u16,
+    /// Last VLAN of the range, `vid` for a single VLAN
+    pub vid_end: u16,
+    pub pvid: bool,
+    pub untagged: bool,
+    /// VLAN of the bridge device itself (`self` in bridge(8))
+    pub bridge_self: bool,
+    /// Per-VLAN STP state, only reported by `get_bridge_vlan_states`
+    pub state: Option<StpState>,
+}
+
+impl BridgeVlan {
+    /// Single VLAN `vid` on port `index`
+    pub fn new(index: u32, vid: u16) -> Self {
+        BridgeVlan::range(index, vid, vid)
+    }
+
+    /// VLANs `vid` to `vid_end` on port `index`
+    pub fn range(index: u32, vid: u16, vid_end: u16) -> Self {
+        BridgeVlan {
+            index: index,
+            vid: vid,
+            vid_end: vid_end,
+            pvid: false,
+            untagged: false,
+            bridge_self: false,
+            state: None,
+        }
+    }
+
+    pub fn pvid(mut self, pvid: bool) -> Self {
+        self.pvid = pvid;
+        self
+    }
+
+    pub fn untagged(mut self, untagged: bool) -> Self {
+        self.untagged = untagged;
+        self
+    }
+
+    /// Target the bridge device itself rather than a port
+    pub fn bridge_self(mut self, bridge_self: bool) -> Self {
+        self.bridge_self = bridge_self;
+        self
+    }
+
+    pub fn contains(&self, vid: u16) -> bool {
+        self.vid <= vid && vid <= self.vid_end
+    }
+
+    fn flags(&self) -> BridgeVlanFlags {
+        let mut flags = BridgeVlanFlags::empty();
+        flags.set(BridgeVlanFlags::PVID, self.pvid);
+        flags.set(BridgeVlanFlags::UNTAGGED, self.untagged);
+        flags
+    }
+
+    fn info_rta(kind: u16, flags: BridgeVlanFlags, vid: u16) -> RtAttrPacket<'static> {
+        let info: &[&dyn ToPayload] = &[&flags.bits(), &vid];
+        RtAttrPacket::create_with_payload(kind, info)
+    }
+
+    /// Build `RTM_SETLINK`/`RTM_DELLINK` request adding or deleting this VLAN
+    pub fn to_request(&self, kind: u16, flags: NetlinkMsgFlags) -> NetlinkPacket<'static> {
+        let target = if self.bridge_self { BRIDGE_FLAGS_SELF } else { BRIDGE_FLAGS_MASTER };
+        let mut attrs = vec![RtAttrPacket::create_with_payload(IFLA_BRIDGE_FLAGS, target)];
+        if self.vid == self.vid_end {
+            attrs.push(BridgeVlan::info_rta(IFLA_BRIDGE_VLAN_INFO, self.flags(), self.vid));
+        } else {
+            attrs.push(BridgeVlan::info_rta(IFLA_BRIDGE_VLAN_INFO, self.flags() | BridgeVlanFlags::RANGE_BEGIN, self.vid));
+            attrs.push(BridgeVlan::info_rta(IFLA_BRIDGE_VLAN_INFO, self.flags() | BridgeVlanFlags::RANGE_END, self.vid_end));
+        }
+        NetlinkRequestBuilder::new(kind, flags)
+            .append(IfInfoPacketBuilder::new()
+                    .set_family(AddressFamily::Bridge)
+                    .set_index(self.index)
+                    .append(nested(IFLA_AF_SPEC, &attrs))
+                    .build())
+            .build()
+    }
+
+    /// Request adding this VLAN
+    pub fn add_request(&self) -> NetlinkPacket<'static> {
+        self.to_request(RTM_SETLINK, NetlinkMsgFlags::NLM_F_ACK)
+    }
+
+    /// Request deleting this VLAN
+    pub fn delete_request(&self) -> NetlinkPacket<'static> {
+        self.to_request(RTM_DELLINK, NetlinkMsgFlags::NLM_F_ACK)
+    }
+
+    /// Request setting STP state of this VLAN with `RTM_NEWVLAN`
+    pub fn set_state_request(&self, state: StpState) -> NetlinkPacket<'static> {
+        let info = BridgeVlan::info_rta(BRIDGE_VLANDB_ENTRY_INFO, BridgeVlanFlags::empty(), self.vid);
+        let state = RtAttrPacket::create_with_payload(BRIDGE_VLANDB_ENTRY_STATE, state.value());
+        let range = if self.vid_end != self.vid {
+            Some(RtAttrPacket::create_with_payload(BRIDGE_VLANDB_ENTRY_RANGE, self.vid_end))
+        } else {
+            None
+        };
+        let entry: &[&dyn ToPayload] = &[&info, &range, &state];
+        NetlinkRequestBuilder::new(RTM_NEWVLAN, NetlinkMsgFlags::NLM_F_ACK)
+            .append(BrVlanMsgPacketBuilder::new(self.index)
+                    .append(RtAttrPacket::create_with_payload(BRIDGE_VLANDB_ENTRY | NLA_F_NESTED, entry))
+                    .build())
+            .build()
+    }
+
+    /// Request dumping VLANs with the `IFLA_AF_SPEC` interface
+    pub fn dump_request() -> NetlinkPacket<'static> {
+        NetlinkRequestBuilder::new(RTM_GETLINK, NetlinkMsgFlags::NLM_F_DUMP)
+            .append(IfInfoPacketBuilder::new()
+                    .set_family(AddressFamily::Bridge)
+                    .append(RtAttrPacket::create_with_payload(IFLA_EXT_MASK, RTEXT_FILTER_BRVLAN_COMPRESSED))
+                    .build())
+            .build()
+    }
+
+    /// Request dumping VLANs with `RTM_GETVLAN`, of link `index` or all
+    pub fn dump_states_request(index: Option<u32>) -> NetlinkPacket<'static> {
+        NetlinkRequestBuilder::new(RTM_GETVLAN, NetlinkMsgFlags::NLM_F_DUMP)
+            .append(BrVlanMsgPacketBuilder::new(index.unwrap_or(0)).build())
+            .build()
+    }
+
+    /// Parse VLANs from an `AF_BRIDGE` `RTM_NEWLINK` message
+    pub fn from_link_packet(packet: NetlinkPacket<'static>) -> Vec<BridgeVlan> {
+        let link = match Link::from_packet(packet) {
+            Some(link) => link,
+            None => return vec![],
+        };
+        let index = link.get_index();
+        // ports are reported with their bridge as master, the bridge without
+        let bridge_self = link.get_master().is_none();
+        let spec = match link.get_af_spec() {
+            Some(spec) => spec,
+            None => return vec![],
+        };
+        let mut vlans = vec![];
+        let mut begin = None;
+        for rta in RtAttrIterator::new(&spec) {
+            if rta.get_rta_type() != IFLA_BRIDGE_VLAN_INFO || rta.payload().len() < 4 {
+                continue;
+            }
+            let flags = BridgeVlanFlags::from_bits_truncate(NativeEndian::read_u16(&rta.payload()[0..2]));
+            let vid = NativeEndian::read_u16(&rta.payload()[2..4]);
+            if flags.contains(BridgeVlanFlags::RANGE_BEGIN) {
+                begin = Some(vid);
+                continue;
+            }
+            let mut vlan = BridgeVlan::range(index, begin.take().unwrap_or(vid), vid)
+                .pvid(flags.contains(BridgeVlanFlags::PVID))
+                .untagged(flags.contains(BridgeVlanFlags::UNTAGGED));
+            vlan.bridge_self = bridge_self;
+            vlans.push(vlan);
+        }
+        vlans
+    }
+
+    /// Parse VLANs from a `RTM_NEWVLAN` message
+    ///
+    /// The message does not tell bridges from ports, `bridge_self` is false.
+    pub fn from_vlan_packet(packet: NetlinkPacket<'static>) -> Vec<BridgeVlan> {
+        if packet.get_kind() != RTM_NEWVLAN {
+            return vec![];
+        }
+        let msg = match BrVlanMsgPacket::new(packet.payload()) {
+            Some(msg) => msg,
+            None => return vec![],
+        };
+        let index = msg.get_index();
+        let mut vlans = vec![];
+        for entry in RtAttrIterator::new(msg.payload()) {
+            if entry.get_rta_type() & NLA_TYPE_MASK != BRIDGE_VLANDB_ENTRY {
+                continue;
+            }
+            let mut vlan = None;
+            let mut vid_end = None;
+            let mut state = None;
+            for rta in RtAttrIterator::new(entry.payload()) {
+                let payload = rta.payload();
+                match rta.get_rta_type() & NLA_TYPE_MASK {
+                    BRIDGE_VLANDB_ENTRY_INFO if payload.len() >= 4 => {
+                        let flags = BridgeVlanFlags::from_bits_truncate(NativeEndian::read_u16(&payload[0..2]));
+                        vlan = Some(BridgeVlan::new(index, NativeEndian::read_u16(&payload[2..4]))
+                                    .pvid(flags.contains(BridgeVlanFlags::PVID))
+                                    .untagged(flags.contains(BridgeVlanFlags::UNTAGGED)));
+                    },
+                    BRIDGE_VLANDB_ENTRY_RANGE if payload.len() >= 2 => vid_end = Some(NativeEndian::read_u16(payload)),
+                    BRIDGE_VLANDB_ENTRY_STATE if payload.len() >= 1 => state = Some(StpState::new(payload[0])),
+                    _ => {},
+                }
+            }
+            if let Some(mut vlan) = vlan {
+                vlan.vid_end = vid_end.unwrap_or(vlan.vid);
+                vlan.state = state;
+                vlans.push(vlan);
+            }
+        }
+        vlans
+    }
+}
+
+struct BrVlanMsgPacketBuilder {
+    data: Vec<u8>,
+}
+
+impl BrVlanMsgPacketBuilder {
+    fn new(index: u32) -> Self {
+        let mut data = vec![0; MutableBrVlanMsgPacket::minimum_packet_size()];
+        {
+            let mut pkt = MutableBrVlanMsgPacket::new(&mut data[..]).unwrap();
+            pkt.set_family(AddressFamily::Bridge);
+            pkt.set_index(index);
+        }
+        BrVlanMsgPacketBuilder { data: data }
+    }
+
+    fn append(mut self, rta: RtAttrPacket) -> Self {
+        let len = rta.get_rta_len() as usize;
+        let aligned_len = crate::util::align(len);
+        self.data.extend_from_slice(&rta.packet()[0..len]);
+        // add padding for alignment
+        for _ in len..aligned_len {
+            self.data.push(0);
+        }
+        self
+    }
+
+    fn build(self) -> BrVlanMsgPacket<'static> {
+        BrVlanMsgPacket::owned(self.data).unwrap()
+    }
+}
+
+/// Bridge VLAN operations
+pub trait BridgeVlans where Self: Links {
+    /// VLANs of port `index`, or of all ports and bridges
+    fn get_bridge_vlans(&mut self, index: Option<u32>) -> io::Result<Vec<BridgeVlan>>;
+    /// VLANs with their STP state, needs `RTM_GETVLAN` support
+    fn get_bridge_vlan_states(&mut self, index: Option<u32>) -> io::Result<Vec<BridgeVlan>>;
+    fn add_bridge_vlan(&mut self, vlan: &BridgeVlan) -> io::Result<()>;
+    fn delete_bridge_vlan(&mut self, vlan: &BridgeVlan) -> io::Result<()>;
+    /// Set STP state of an existing VLAN
+    fn set_bridge_vlan_state(&mut self, vlan: &BridgeVlan, state: StpState) -> io::Result<()>;
+}
+
+fn collect_vlans<F>(conn: &mut NetlinkConnection, req: NetlinkPacket<'static>, reply: u16, parse: F)
+    -> io::Result<Vec<BridgeVlan>> where F: Fn(NetlinkPacket<'static>) -> Vec<BridgeVlan> {
+    conn.write(req.packet())?;
+    let mut vlans = vec![];
+    for pkt in NetlinkReader::new(conn) {
+        if let Some(err) = pkt.to_io_error() {
+            return Err(err);
+        }
+        if pkt.get_kind() == reply {
+            vlans.extend(parse(pkt));
+        }
+    }
+    Ok(vlans)
+}
+
+impl BridgeVlans for NetlinkConnection {
+    fn get_bridge_vlans(&mut self, index: Option<u32>) -> io::Result<Vec<BridgeVlan>> {
+        let vlans = collect_vlans(self, BridgeVlan::dump_request(), RTM_NEWLINK, BridgeVlan::from_link_packet)?;
+        Ok(vlans.into_iter().filter(|vlan| index.map_or(true, |index| vlan.index == index)).collect())
+    }
+
+    fn get_bridge_vlan_states(&mut self, index: Option<u32>) -> io::Result<Vec<BridgeVlan>> {
+        let mut vlans = collect_vlans(self, BridgeVlan::dump_states_request(index), RTM_NEWVLAN, BridgeVlan::from_vlan_packet)?;
+        let bridges: HashSet<u32> = self.iter_links()?
+            .filter(|link| link.get_kind().as_ref().map(|kind| kind.as_str()) == Some("bridge"))
+            .map(|link| link.get_index())
+            .collect();
+        for vlan in vlans.iter_mut() {
+            vlan.bridge_self = bridges.contains(&vlan.index);
+        }
+        Ok(vlans)
+    }
+
+    fn add_bridge_vlan(&mut self, vlan: &BridgeVlan) -> io::Result<()> {
+        self.write(vlan.add_request().packet())?;
+        NetlinkReader::new(self).read_to_end()
+    }
+
+    fn delete_bridge_vlan(&mut self, vlan: &BridgeVlan) -> io::Result<()> {
+        self.write(vlan.delete_request().packet())?;
+        NetlinkReader::new(self).read_to_end()
+    }
+
+    fn set_bridge_vlan_state(&mut self, vlan: &BridgeVlan, state: StpState) -> io::Result<()> {
+        self.write(vlan.set_state_request(state).packet())?;
+        NetlinkReader::new(self).read_to_end()
+    }
+}
+
+mod tests {
+    #[test]
+    fn bridge_vlan_encoding() {
+        use crate::packet::netlink::NetlinkMsgFlags;
+        use crate::packet::route::bridge_vlan::BridgeVlan;
+        use crate::packet::route::link::RTM_NEWLINK;
+
+        let vlans = [BridgeVlan::new(7, 10).pvid(true).untagged(true), BridgeVlan::range(7, 100, 199)];
+        for vlan in vlans.iter() {
+            // same attributes as the kernel's dump replies
+            let reply = vlan.to_request(RTM_NEWLINK, NetlinkMsgFlags::empty());
+            let mut parsed = BridgeVlan::from_link_packet(reply);
+            assert_eq!(parsed.len(), 1);
+            // no IFLA_MASTER in the request
+            assert!(parsed[0].bridge_self);
+            parsed[0].bridge_self = false;
+            assert_eq!(parsed[0], *vlan);
+        }
+        assert!(vlans[1].contains(150) && !vlans[1].contains(200));
+    }
+
+    #[test]
+    fn bridge_vlan_dump_mask() {
+        use crate::packet::route::{IfInfoPacket,RtAttrIterator};
+        use crate::packet::route::bridge_vlan::{BridgeVlan,RTEXT_FILTER_BRVLAN_COMPRESSED};
+        use crate::packet::route::link::IFLA_EXT_MASK;
+        use byteorder::{ByteOrder,NativeEndian};
+        use pnet::packet::Packet;
+
+        let req = BridgeVlan::dump_request();
+        let ifi = IfInfoPacket::new(req.payload()).unwrap();
+        let mask = RtAttrIterator::new(ifi.payload())
+            .find(|rta| rta.get_rta_type() == IFLA_EXT_MASK)
+            .map(|rta| NativeEndian::read_u32(rta.payload()));
+        // the kernel's RTEXT_FILTER_BRVLAN_COMPRESSED, not RTEXT_FILTER_SKIP_STATS
+        assert_eq!(mask, Some(4));
+        assert_eq!(RTEXT_FILTER_BRVLAN_COMPRESSED, 4);
+    }
+
+    #[test]
+    // root permissions required
+    fn bridge_vlan_membership() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::bridge::{Bridge,StpState};
+        use crate::packet::route::bridge_vlan::{BridgeVlan,BridgeVlans};
+        use crate::packet::route::link::{Links,LinkBuilder,LinkType};
+
+        let mut conn = NetlinkConnection::new();
+        conn.new_link(&LinkBuilder::new("pnl-vbr0").kind(Bridge::new().vlan_filtering(true))).unwrap();
+        let bridge = conn.get_link_by_name("pnl-vbr0").unwrap().unwrap();
+        conn.new_link(&LinkBuilder::new("pnl-vport0").kind(LinkType::Dummy).master(bridge.get_index())).unwrap();
+        let port = conn.get_link_by_name("pnl-vport0").unwrap().unwrap().get_index();
+
+        conn.add_bridge_vlan(&BridgeVlan::new(port, 10).pvid(true).untagged(true)).unwrap();
+        conn.add_bridge_vlan(&BridgeVlan::range(port, 100, 103)).unwrap();
+        conn.delete_bridge_vlan(&BridgeVlan::new(port, 1)).unwrap();
+        let vlans = conn.get_bridge_vlans(Some(port)).unwrap();
+        assert_eq!(vlans, vec![BridgeVlan::new(port, 10).pvid(true).untagged(true), BridgeVlan::range(port, 100, 103)]);
+        conn.delete_bridge_vlan(&BridgeVlan::range(port, 101, 102)).unwrap();
+        let vlans = conn.get_bridge_vlans(Some(port)).unwrap();
+        assert_eq!(vlans.len(), 3);
+
+        match conn.get_bridge_vlan_states(Some(port)) {
+            Ok(_) => {
+                conn.set_bridge_vlan_state(&BridgeVlan::new(port, 100), StpState::Blocking).unwrap();
+                let states = conn.get_bridge_vlan_states(Some(port)).unwrap();
+                let vlan = states.iter().find(|vlan| vlan.contains(100)).unwrap();
+                assert_eq!(vlan.state, Some(StpState::Blocking));
+                assert!(!vlan.bridge_self);
+            },
+            // RTM_GETVLAN needs Linux 5.10
+            Err(ref e) if e.raw_os_error() == Some(::libc::EOPNOTSUPP) => {},
+            Err(e) => panic!("{}", e),
+        }
+        let port = conn.get_link_by_index(port).unwrap().unwrap();
+        conn.delete_link(port).unwrap();
+        conn.delete_link(bridge).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+//! Typed rtnetlink notifications
+//!
+//! ```no_run
+//! use pnetlink::packet::route::event::{EventIterator,EventSet,RtnlEvent};
+//!
+//! for event in EventIterator::new(EventSet::LINK | EventSet::ADDR).unwrap() {
+//!     match event.unwrap() {
+//!         RtnlEvent::LinkAdded(link) => println!("link {:?}", link.get_name()),
+//!         RtnlEvent::AddrAdded(addr) => println!("addr {:?}", addr.get_prefix()),
+//!         _ => {},
+//!     }
+//! }
+//! ```
+use crate::packet::netlink::{NetlinkConnection,NetlinkPacket,MutableNetlinkPacket};
+use crate::packet::route::AddressFamily;
+use crate::packet::route::addr::{Addr,RTM_NEWADDR,RTM_DELADDR};
+use crate::packet::route::link::{Link,RTM_NEWLINK,RTM_DELLINK};
+use crate::packet::route::neighbour::{Neighbour,RTM_NEWNEIGH,RTM_DELNEIGH};
+use crate::packet::route::route::{Route,RTM_NEWROUTE,RTM_DELROUTE};
+use crate::packet::route::rule::{Rule,RTM_NEWRULE,RTM_DELRULE};
+use pnet::packet::Packet;
+
+use std::collections::VecDeque;
+use std::io;
+
+/* multicast group numbers, for NETLINK_ADD_MEMBERSHIP */
+pub const RTNLGRP_LINK: u32 = 1;
+pub const RTNLGRP_NEIGH: u32 = 3;
+pub const RTNLGRP_IPV4_IFADDR: u32 = 5;
+pub const RTNLGRP_IPV4_ROUTE: u32 = 7;
+pub const RTNLGRP_IPV4_RULE: u32 = 8;
+pub const RTNLGRP_IPV6_IFADDR: u32 = 9;
+pub const RTNLGRP_IPV6_ROUTE: u32 = 11;
+pub const RTNLGRP_IPV6_RULE: u32 = 19;
+
+bitflags! {
+    /// Events to subscribe to
+    pub struct EventSet: u32 {
+        const LINK = 0x1;
+        const IPV4_ADDR = 0x2;
+        const IPV6_ADDR = 0x4;
+        const ADDR = Self::IPV4_ADDR.bits | Self::IPV6_ADDR.bits;
+        const IPV4_ROUTE = 0x8;
+        const IPV6_ROUTE = 0x10;
+        const ROUTE = Self::IPV4_ROUTE.bits | Self::IPV6_ROUTE.bits;
+        const NEIGH = 0x20;
+        const IPV4_RULE = 0x40;
+        const IPV6_RULE = 0x80;
+        const RULE = Self::IPV4_RULE.bits | Self::IPV6_RULE.bits;
+    }
+}
+
+impl EventSet {
+    /// Multicast groups delivering these events
+    pub fn groups(&self) -> Vec<u32> {
+        let map = [(EventSet::LINK, RTNLGRP_LINK),
+                   (EventSet::IPV4_ADDR, RTNLGRP_IPV4_IFADDR), (EventSet::IPV6_ADDR, RTNLGRP_IPV6_IFADDR),
+                   (EventSet::IPV4_ROUTE, RTNLGRP_IPV4_ROUTE), (EventSet::IPV6_ROUTE, RTNLGRP_IPV6_ROUTE),
+                   (EventSet::NEIGH, RTNLGRP_NEIGH),
+                   (EventSet::IPV4_RULE, RTNLGRP_IPV4_RULE), (EventSet::IPV6_RULE, RTNLGRP_IPV6_RULE)];
+        map.iter().filter(|&&(set, _)| self.contains(set)).map(|&(_, group)| group).collect()
+    }
+
+    /// Join multicast groups for these events on `conn`
+    pub fn subscribe(&self, conn: &mut NetlinkConnection) -> io::Result<()> {
+        for group in self.groups() {
+            conn.socket_mut().add_membership(group)?;
+        }
+        Ok(())
+    }
+}
+
+/// rtnetlink notification
+#[derive(Debug)]
+pub enum RtnlEvent {
+    /// Link was created or changed (RTM_NEWLINK)
+    LinkAdded(Link),
+    LinkRemoved(Link),
+    AddrAdded(Addr),
+    AddrRemoved(Addr),
+    RouteAdded(Route),
+    RouteRemoved(Route),
+    /// Neighbour was created or changed state (RTM_NEWNEIGH)
+    NeighChanged(Neighbour),
+    NeighRemoved(Neighbour),
+    RuleAdded(Rule),
+    RuleRemoved(Rule),
+}
+
+impl RtnlEvent {
+    /// Classify a notification, None for messages of other kinds
+    ///
+    /// AF_BRIDGE link messages are skipped: they carry bridge port state only,
+    /// and RTM_DELLINK of that family means the port left its bridge,
+    /// not that the link is gone.
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Option<RtnlEvent> {
+        match packet.get_kind() {
+            RTM_NEWLINK | RTM_DELLINK => {
+                let removed = packet.get_kind() == RTM_DELLINK;
+                match Link::from_packet(packet) {
+                    Some(ref link) if link.get_family() == AddressFamily::Bridge => None,
+                    Some(link) if removed => Some(RtnlEvent::LinkRemoved(link)),
+                    link => link.map(RtnlEvent::LinkAdded),
+                }
+            },
+            RTM_NEWADDR => Addr::from_packet(packet).map(RtnlEvent::AddrAdded),
+            RTM_DELADDR => Addr::from_packet(packet).map(RtnlEvent::AddrRemoved),
+            RTM_NEWROUTE => Route::from_packet(packet).map(RtnlEvent::RouteAdded),
+            RTM_DELROUTE => Route::from_packet(packet).map(RtnlEvent::RouteRemoved),
+            RTM_NEWNEIGH => Neighbour::from_packet(packet).map(RtnlEvent::NeighChanged),
+            RTM_DELNEIGH => Neighbour::from_packet(packet).map(RtnlEvent::NeighRemoved),
+            RTM_NEWRULE => Rule::from_packet(packet).map(RtnlEvent::RuleAdded),
+            RTM_DELRULE => Rule::from_packet(packet).map(RtnlEvent::RuleRemoved),
+            _ => None,
+        }
+    }
+}
+
+/// Blocking iterator over rtnetlink notifications
+///
+/// Lost notifications (the socket buffer overflowed) are reported as `ENOBUFS`
+/// errors; iteration continues after them. Notifications still queued when
+/// the error is reported are dropped with it, so every event following the
+/// error is newer than it, and newer than a dump made after it.
+pub struct EventIterator {
+    conn: NetlinkConnection,
+    queue: VecDeque<RtnlEvent>,
+    buf: Vec<u8>,
+}
+
+impl EventIterator {
+    /// Open a connection subscribed to `events`
+    pub fn new(events: EventSet) -> io::Result<Self> {
+        let conn = NetlinkConnection::builder().build()?;
+        EventIterator::from_connection(conn, events)
+    }
+
+    /// Subscribe `conn` to `events`
+    pub fn from_connection(mut conn: NetlinkConnection, events: EventSet) -> io::Result<Self> {
+        events.subscribe(&mut conn)?;
+        Ok(EventIterator { conn: conn, queue: VecDeque::new(), buf: vec![0; 32768] })
+    }
+
+    /// Get underlying connection
+    pub fn connection(&mut self) -> &mut NetlinkConnection {
+        &mut self.conn
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let len = match self.conn.socket().recv(&mut self.buf) {
+            Err(ref e) if e.raw_os_error() == Some(::libc::ENOBUFS) => {
+                self.conn.socket().discard_queued()?;
+                return Err(io::Error::from_raw_os_error(::libc::ENOBUFS));
+            },
+            res => res?,
+        };
+        let mut offset = 0;
+        while offset < len {
+            let pkt = match NetlinkPacket::new(&self.buf[offset..len]) {
+                Some(pkt) => pkt,
+                None => break,
+            };
+            let pkt_len = pkt.get_length() as usize;
+            if pkt_len < MutableNetlinkPacket::minimum_packet_size() || offset + pkt_len > len {
+                break;
+            }
+            let owned = NetlinkPacket::owned(pkt.packet()[..pkt_len].to_owned()).unwrap();
+            if let Some(event) = RtnlEvent::from_packet(owned) {
+                self.queue.push_back(event);
+            }
+            offset += crate::util::align(pkt_len);
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for EventIterator {
+    type Item = io::Result<RtnlEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.queue.pop_front() {
+                return Some(Ok(event));
+            }
+            match self.fill() {
+                Ok(()) => {},
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+mod tests {
+    #[test]
+    fn classify_events() {
+        use crate::packet::netlink::{NetlinkMsgFlags,NetlinkRequestBuilder};
+        use crate::packet::route::AddressFamily;
+        use crate::packet::route::event::{EventSet,RtnlEvent,RTNLGRP_LINK,RTNLGRP_IPV4_IFADDR,RTNLGRP_IPV6_IFADDR};
+        use crate::packet::route::link::{IfInfoPacketBuilder,RTM_DELLINK};
+        use crate::packet::route::route::{RouteInfo,RTM_DELROUTE};
+
+        assert_eq!((EventSet::LINK | EventSet::ADDR).groups(), vec![RTNLGRP_LINK, RTNLGRP_IPV4_IFADDR, RTNLGRP_IPV6_IFADDR]);
+        let route = RouteInfo::new("10.0.0.0/8".parse().unwrap());
+        match RtnlEvent::from_packet(route.to_request(RTM_DELROUTE, NetlinkMsgFlags::empty())) {
+            Some(RtnlEvent::RouteRemoved(r)) => assert_eq!(r.get_dst_len(), 8),
+            other => panic!("unexpected {:?}", other),
+        }
+        let done = NetlinkRequestBuilder::new(crate::packet::netlink::NLMSG_DONE, NetlinkMsgFlags::empty()).build();
+        assert!(RtnlEvent::from_packet(done).is_none());
+
+        // a port leaving its bridge is not the link going away
+        let dellink = |family| NetlinkRequestBuilder::new(RTM_DELLINK, NetlinkMsgFlags::empty())
+            .append(IfInfoPacketBuilder::new().set_family(family).set_index(3).build())
+            .build();
+        assert!(RtnlEvent::from_packet(dellink(AddressFamily::Bridge)).is_none());
+        match RtnlEvent::from_packet(dellink(AddressFamily::Unspec)) {
+            Some(RtnlEvent::LinkRemoved(link)) => assert_eq!(link.get_index(), 3),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    // root permissions required
+    fn overflow_drops_stale_events() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::event::{EventIterator,EventSet,RtnlEvent};
+        use crate::packet::route::link::Links;
+
+        let conn = NetlinkConnection::builder().recv_buffer_size(16384).build().unwrap();
+        let mut events = EventIterator::from_connection(conn, EventSet::LINK).unwrap();
+        let mut conn = NetlinkConnection::new();
+        for i in 0..64 {
+            let name = format!("pnl-flood{}", i);
+            conn.new_dummy_link(&name).unwrap();
+            let link = conn.get_link_by_name(&name).unwrap().unwrap();
+            conn.delete_link(link).unwrap();
+        }
+        let err = events.next().unwrap().unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(::libc::ENOBUFS));
+        conn.new_dummy_link("pnl-flood-end").unwrap();
+        for event in &mut events {
+            let name = match event.unwrap() {
+                RtnlEvent::LinkAdded(link) | RtnlEvent::LinkRemoved(link) => link.get_name().unwrap_or_default(),
+                _ => continue,
+            };
+            if name == "pnl-flood-end" {
+                break;
+            }
+            assert!(!name.starts_with("pnl-flood"), "stale event for {}", name);
+        }
+        let link = conn.get_link_by_name("pnl-flood-end").unwrap().unwrap();
+        conn.delete_link(link).unwrap();
+    }
+
+    #[test]
+    // root permissions required
+    fn link_events() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::event::{EventIterator,EventSet,RtnlEvent};
+        use crate::packet::route::link::Links;
+
+        let mut events = EventIterator::new(EventSet::LINK).unwrap();
+        let mut conn = NetlinkConnection::new();
+        conn.new_dummy_link("pnl-event0").unwrap();
+        let link = conn.get_link_by_name("pnl-event0").unwrap().unwrap();
+        conn.delete_link(link).unwrap();
+        let mut added = false;
+        for event in &mut events {
+            match event.unwrap() {
+                RtnlEvent::LinkAdded(ref link) if link.get_name() == Some("pnl-event0".to_owned()) => added = true,
+                RtnlEvent::LinkRemoved(ref link) if link.get_name() == Some("pnl-event0".to_owned()) => break,
+                _ => {},
+            }
+        }
+        assert!(added);
+    }
+}
@@ -0,0 +1,340 @@
+//! Link flap detection and debouncing
+//!
+//! `FlapDetector` is a plain state machine: feed it link states with the
+//! time they were observed, call `poll` when `next_deadline` is reached,
+//! and it reports a link as up or down only once the new state has held
+//! for the configured delay. Bounces shorter than that are only counted.
+//! `FlapMonitor` drives it from link notifications.
+//!
+//! ```no_run
+//! use pnetlink::packet::route::flap::{FlapConfig,FlapMonitor};
+//!
+//! for event in FlapMonitor::new(FlapConfig::default()).unwrap() {
+//!     let event = event.unwrap();
+//!     println!("{:?} is {:?} after {} flaps", event.name, event.status, event.flaps);
+//! }
+//! ```
+use crate::packet::netlink::NetlinkConnection;
+use crate::packet::route::event::{EventIterator,EventSet,RtnlEvent};
+use crate::packet::route::link::{Link,LinkInfo,Links,IfFlags,OperState};
+
+use std::collections::{HashMap,HashSet,VecDeque};
+use std::io;
+use std::time::{Duration,Instant};
+
+/// Link state as seen by the detector
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum LinkStatus {
+    Up,
+    Down,
+}
+
+impl LinkStatus {
+    /// Up if the link is administratively up, has carrier and its
+    /// operating state is up or unknown
+    pub fn of(link: &Link) -> LinkStatus {
+        let info = LinkInfo::from(link);
+        let oper_up = match info.state {
+            None | Some(OperState::Unknown) | Some(OperState::Up) => true,
+            _ => false,
+        };
+        if info.flags.contains(IfFlags::UP) && info.carrier != Some(false) && oper_up {
+            LinkStatus::Up
+        } else {
+            LinkStatus::Down
+        }
+    }
+}
+
+/// Hold-down timers
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct FlapConfig {
+    /// Time a link must stay up before it is reported up
+    pub up_delay: Duration,
+    /// Time a link must stay down before it is reported down
+    pub down_delay: Duration,
+}
+
+impl Default for FlapConfig {
+    fn default() -> Self {
+        FlapConfig {
+            up_delay: Duration::from_secs(2),
+            down_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Stable state change of a link
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct FlapEvent {
+    pub index: u32,
+    pub name: Option<String>,
+    pub status: LinkStatus,
+    /// Transitions seen since the link was first observed, including
+    /// suppressed bounces
+    pub flaps: u64,
+}
+
+#[derive(Debug)]
+struct Tracked {
+    name: Option<String>,
+    raw: LinkStatus,
+    stable: LinkStatus,
+    since: Instant,
+    flaps: u64,
+    carrier_changes: Option<u32>,
+}
+
+/// Debouncing state machine for many links
+#[derive(Debug)]
+pub struct FlapDetector {
+    config: FlapConfig,
+    links: HashMap<u32, Tracked>,
+}
+
+impl FlapDetector {
+    pub fn new(config: FlapConfig) -> Self {
+        FlapDetector { config: config, links: HashMap::new() }
+    }
+
+    fn delay(&self, status: LinkStatus) -> Duration {
+        match status {
+            LinkStatus::Up => self.config.up_delay,
+            LinkStatus::Down => self.config.down_delay,
+        }
+    }
+
+    /// Record state of `link` observed at `now`
+    pub fn update(&mut self, link: &Link, now: Instant) -> Option<FlapEvent> {
+        self.observe(link.get_index(), link.get_name(), LinkStatus::of(link), link.get_carrier_changes(), now)
+    }
+
+    /// Record `status` of link `index` observed at `now`
+    ///
+    /// `carrier_changes` is the kernel counter (`IFLA_CARRIER_CHANGES`); when
+    /// given, bounces too quick to be notified separately are counted too.
+    /// The first observation of a link sets its stable state without an event.
+    pub fn observe(&mut self, index: u32, name: Option<String>, status: LinkStatus,
+                   carrier_changes: Option<u32>, now: Instant) -> Option<FlapEvent> {
+        {
+            let tracked = match self.links.get_mut(&index) {
+                Some(tracked) => tracked,
+                None => {
+                    self.links.insert(index, Tracked {
+                        name: name,
+                        raw: status,
+                        stable: status,
+                        since: now,
+                        flaps: 0,
+                        carrier_changes: carrier_changes,
+                    });
+                    return None;
+                },
+            };
+            let transitions = if status != tracked.raw { 1 } else { 0 };
+            let missed = match (tracked.carrier_changes, carrier_changes) {
+                (Some(prev), Some(cur)) => cur.wrapping_sub(prev) as u64,
+                _ => 0,
+            };
+            tracked.flaps += ::std::cmp::max(transitions, missed);
+            if carrier_changes.is_some() {
+                tracked.carrier_changes = carrier_changes;
+            }
+            if name.is_some() {
+                tracked.name = name;
+            }
+            if status != tracked.raw {
+                tracked.raw = status;
+                tracked.since = now;
+            }
+        }
+        self.settle(index, now)
+    }
+
+    /// Forget link `index`, e.g. when it was deleted
+    ///
+    /// A link last reported up is reported down right away, there is no
+    /// state left to debounce.
+    pub fn remove(&mut self, index: u32) -> Option<FlapEvent> {
+        let tracked = self.links.remove(&index)?;
+        if tracked.stable != LinkStatus::Up {
+            return None;
+        }
+        let flaps = tracked.flaps + if tracked.raw == LinkStatus::Up { 1 } else { 0 };
+        Some(FlapEvent { index: index, name: tracked.name, status: LinkStatus::Down, flaps: flaps })
+    }
+
+    fn settle(&mut self, index: u32, now: Instant) -> Option<FlapEvent> {
+        let delay = match self.links.get(&index) {
+            Some(tracked) => self.delay(tracked.raw),
+            None => return None,
+        };
+        let tracked = self.links.get_mut(&index).unwrap();
+        if tracked.raw == tracked.stable || now < tracked.since + delay {
+            return None;
+        }
+        tracked.stable = tracked.raw;
+        Some(FlapEvent { index: index, name: tracked.name.clone(), status: tracked.stable, flaps: tracked.flaps })
+    }
+
+    /// Report links whose new state held long enough by `now`
+    pub fn poll(&mut self, now: Instant) -> Vec<FlapEvent> {
+        let mut indexes: Vec<u32> = self.links.keys().cloned().collect();
+        indexes.sort();
+        indexes.into_iter().filter_map(|index| self.settle(index, now)).collect()
+    }
+
+    /// Earliest time `poll` may report something, None if nothing is pending
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.links.values()
+            .filter(|tracked| tracked.raw != tracked.stable)
+            .map(|tracked| tracked.since + self.delay(tracked.raw))
+            .min()
+    }
+
+    /// Get reported state of link `index`
+    pub fn status(&self, index: u32) -> Option<LinkStatus> {
+        self.links.get(&index).map(|tracked| tracked.stable)
+    }
+
+    /// Get number of transitions of link `index`
+    pub fn flaps(&self, index: u32) -> Option<u64> {
+        self.links.get(&index).map(|tracked| tracked.flaps)
+    }
+}
+
+/// Blocking iterator of debounced link state changes
+pub struct FlapMonitor {
+    events: EventIterator,
+    conn: NetlinkConnection,
+    detector: FlapDetector,
+    queue: VecDeque<FlapEvent>,
+}
+
+impl FlapMonitor {
+    /// Subscribe to link notifications and observe the current links
+    pub fn new(config: FlapConfig) -> io::Result<Self> {
+        let mut monitor = FlapMonitor {
+            events: EventIterator::new(EventSet::LINK)?,
+            conn: NetlinkConnection::new(),
+            detector: FlapDetector::new(config),
+            queue: VecDeque::new(),
+        };
+        monitor.observe_all()?;
+        Ok(monitor)
+    }
+
+    // also used after lost notifications, carrier counters account for missed
+    // flaps and links missing from the dump were removed meanwhile
+    fn observe_all(&mut self) -> io::Result<()> {
+        let now = Instant::now();
+        let mut seen = HashSet::new();
+        for link in self.conn.iter_links()? {
+            seen.insert(link.get_index());
+            self.queue.extend(self.detector.update(&link, now));
+        }
+        let mut gone: Vec<u32> = self.detector.links.keys().filter(|index| !seen.contains(*index)).cloned().collect();
+        gone.sort();
+        for index in gone {
+            self.queue.extend(self.detector.remove(index));
+        }
+        Ok(())
+    }
+
+    /// Get underlying detector, e.g. for flap counters
+    pub fn detector(&self) -> &FlapDetector {
+        &self.detector
+    }
+}
+
+impl Iterator for FlapMonitor {
+    type Item = io::Result<FlapEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.queue.pop_front() {
+                return Some(Ok(event));
+            }
+            let now = Instant::now();
+            self.queue.extend(self.detector.poll(now));
+            if !self.queue.is_empty() {
+                continue;
+            }
+            let timeout = self.detector.next_deadline().map(|deadline| deadline - now);
+            if let Err(e) = self.events.connection().socket_mut().set_read_timeout(timeout) {
+                return Some(Err(e));
+            }
+            match self.events.next() {
+                Some(Ok(RtnlEvent::LinkAdded(link))) => {
+                    self.queue.extend(self.detector.update(&link, Instant::now()));
+                },
+                Some(Ok(RtnlEvent::LinkRemoved(link))) => self.queue.extend(self.detector.remove(link.get_index())),
+                Some(Ok(_)) => {},
+                Some(Err(ref e)) if e.raw_os_error() == Some(::libc::ENOBUFS) => {
+                    if let Err(e) = self.observe_all() {
+                        return Some(Err(e));
+                    }
+                },
+                Some(Err(ref e)) if e.kind() == io::ErrorKind::WouldBlock => {},
+                Some(Err(e)) => return Some(Err(e)),
+                None => return None,
+            }
+        }
+    }
+}
+
+mod tests {
+    #[test]
+    fn debounce() {
+        use crate::packet::route::flap::{FlapConfig,FlapDetector,LinkStatus};
+        use std::time::{Duration,Instant};
+
+        let ms = Duration::from_millis;
+        let config = FlapConfig { up_delay: ms(1000), down_delay: ms(100) };
+        let mut detector = FlapDetector::new(config);
+        let t0 = Instant::now();
+        assert!(detector.observe(2, None, LinkStatus::Up, Some(1), t0).is_none());
+
+        // a bounce shorter than down_delay is only counted
+        assert!(detector.observe(2, None, LinkStatus::Down, Some(2), t0 + ms(10)).is_none());
+        assert_eq!(detector.next_deadline(), Some(t0 + ms(110)));
+        assert!(detector.observe(2, None, LinkStatus::Up, Some(3), t0 + ms(50)).is_none());
+        assert!(detector.next_deadline().is_none());
+        assert!(detector.poll(t0 + ms(200)).is_empty());
+        assert_eq!(detector.status(2), Some(LinkStatus::Up));
+
+        // quick bounces the kernel did not notify separately
+        assert!(detector.observe(2, None, LinkStatus::Down, Some(7), t0 + ms(300)).is_none());
+        let events = detector.poll(t0 + ms(400));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status, LinkStatus::Down);
+        assert_eq!(events[0].flaps, 6);
+
+        // up must hold for up_delay
+        detector.observe(2, None, LinkStatus::Up, Some(8), t0 + ms(500));
+        assert!(detector.poll(t0 + ms(1400)).is_empty());
+        assert_eq!(detector.poll(t0 + ms(1500))[0].status, LinkStatus::Up);
+        assert_eq!(detector.flaps(2), Some(7));
+    }
+
+    #[test]
+    fn removal() {
+        use crate::packet::route::flap::{FlapConfig,FlapDetector,LinkStatus};
+        use std::time::{Duration,Instant};
+
+        let ms = Duration::from_millis;
+        let mut detector = FlapDetector::new(FlapConfig { up_delay: ms(1000), down_delay: ms(100) });
+        let t0 = Instant::now();
+        detector.observe(2, Some("eth0".to_owned()), LinkStatus::Up, None, t0);
+        detector.observe(3, None, LinkStatus::Down, None, t0);
+
+        // reported up: a final down without waiting for down_delay
+        let event = detector.remove(2).unwrap();
+        assert_eq!((event.name.as_ref().map(|name| &name[..]), event.status, event.flaps), (Some("eth0"), LinkStatus::Down, 1));
+        assert!(detector.status(2).is_none());
+        // already reported down, or unknown
+        assert!(detector.remove(3).is_none());
+        assert!(detector.remove(4).is_none());
+        assert!(detector.next_deadline().is_none());
+    }
+}
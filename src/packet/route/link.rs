@@ -16,16 +16,24 @@
 //! ```
 
 
-use packet::route::{IfInfoPacket, MutableIfInfoPacket, RtAttrIterator, RtAttrPacket,
+use crate::packet::route::{NLA_TYPE_MASK, AddressFamily, IfInfoPacket, MutableIfInfoPacket, RtAttrIterator, RtAttrPacket,
                     RtAttrMtuPacket};
-use packet::route::route::WithPayload;
-use packet::netlink::NetlinkPacket;
-use packet::netlink::NetlinkMsgFlags;
-use packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
-use packet::netlink::NetlinkConnection;
+use crate::packet::route::route::{ToPayload,WithPayload};
+use crate::packet::route::bond::{BondInfo,BondSlave};
+use crate::packet::route::bridge::{BridgeInfo,BridgePort};
+use crate::packet::route::macvlan::{IpVlanInfo,MacVlanInfo};
+use crate::packet::route::tunnel::{TunnelInfo,TunnelKind};
+use crate::packet::route::vlan::VlanInfo;
+use crate::packet::route::vxlan::VxlanInfo;
+use crate::packet::netlink::NetlinkPacket;
+use crate::packet::netlink::NetlinkMsgFlags;
+use crate::packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder,ToIoError};
+use crate::packet::netlink::NetlinkConnection;
 use pnet::packet::Packet;
 use pnet::util::MacAddr;
+use byteorder::{ByteOrder, NativeEndian};
 use std::io::{Read,Write,self};
+use std::os::unix::io::RawFd;
 
 /* rt message types */
 pub const RTM_NEWLINK: u16 = 16;
@@ -82,23 +90,78 @@ pub const IFLA_INFO_UNSPEC: u16 = 0;
 pub const IFLA_INFO_KIND: u16 = 1;
 pub const IFLA_INFO_DATA: u16 = 2;
 pub const IFLA_INFO_XSTATS: u16 = 3;
+pub const IFLA_INFO_SLAVE_KIND: u16 = 4;
+pub const IFLA_INFO_SLAVE_DATA: u16 = 5;
 
-/// Interface type
-/// NB: Only Generic, Ether and Loopback are currently defined
-#[derive(Debug,Copy,Clone)]
+/// Interface type (ARPHRD_* values from linux/if_arp.h)
+/// NB: types not listed here are reported as `Void`
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
 #[repr(u16)]
 pub enum IfType {
-    /* todo: more types, see if_link.h */
     Generic = 0,
     Ether = 1,
+    Eether = 2,
+    Ax25 = 3,
+    Ieee802 = 6,
+    Arcnet = 7,
+    Atm = 19,
+    Ieee1394 = 24,
+    Infiniband = 32,
+    Slip = 256,
+    Can = 280,
+    Ppp = 512,
+    Hdlc = 513,
+    RawIp = 519,
+    Tunnel = 768,
+    Tunnel6 = 769,
     Loopback = 772,
+    Fddi = 774,
+    Sit = 776,
+    IpGre = 778,
+    Ieee80211 = 801,
+    Ieee80211Prism = 802,
+    Ieee80211Radiotap = 803,
+    Ieee802154 = 804,
+    Ip6Gre = 823,
+    Netlink = 824,
+    SixLowpan = 825,
+    None = 0xfffe,
+    Void = 0xffff,
 }
 
 impl IfType {
     pub fn new(val: u16) -> Self {
-        use std::mem;
-        /* XXX */
-        unsafe { mem::transmute(val) }
+        match val {
+            0 => IfType::Generic,
+            1 => IfType::Ether,
+            2 => IfType::Eether,
+            3 => IfType::Ax25,
+            6 => IfType::Ieee802,
+            7 => IfType::Arcnet,
+            19 => IfType::Atm,
+            24 => IfType::Ieee1394,
+            32 => IfType::Infiniband,
+            256 => IfType::Slip,
+            280 => IfType::Can,
+            512 => IfType::Ppp,
+            513 => IfType::Hdlc,
+            519 => IfType::RawIp,
+            768 => IfType::Tunnel,
+            769 => IfType::Tunnel6,
+            772 => IfType::Loopback,
+            774 => IfType::Fddi,
+            776 => IfType::Sit,
+            778 => IfType::IpGre,
+            801 => IfType::Ieee80211,
+            802 => IfType::Ieee80211Prism,
+            803 => IfType::Ieee80211Radiotap,
+            804 => IfType::Ieee802154,
+            823 => IfType::Ip6Gre,
+            824 => IfType::Netlink,
+            825 => IfType::SixLowpan,
+            0xfffe => IfType::None,
+            _ => IfType::Void,
+        }
     }
 }
 
@@ -114,6 +177,78 @@ pub enum LinkType {
     Bridge
 }
 
+impl LinkType {
+    /// `IFLA_INFO_KIND` value
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            LinkType::Vlan => "vlan",
+            LinkType::Veth => "veth",
+            LinkType::Vcan => "vcan",
+            LinkType::Dummy => "dummy",
+            LinkType::Ifb => "ifb",
+            LinkType::MacVlan => "macvlan",
+            LinkType::Can => "can",
+            LinkType::Bridge => "bridge",
+        }
+    }
+}
+
+/// Kind of virtual link together with its options
+///
+/// Implemented by `LinkType` for kinds created without options and by
+/// typed option structs for the others. Unset options are left out of the
+/// request and keep the kernel defaults; the matching `*Info` structs read
+/// back from a link are None where the kernel does not report an option.
+pub trait LinkKind {
+    /// `IFLA_INFO_KIND` value, e.g. "dummy"
+    fn kind(&self) -> &str;
+    /// Attributes nested in `IFLA_INFO_DATA`
+    fn info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        vec![]
+    }
+    /// Check options the kernel would reject or misread, before building the request
+    fn validate(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Append attribute `kind` to `data` if the option is set
+pub fn push_opt<P: ToPayload>(data: &mut Vec<RtAttrPacket<'static>>, kind: u16, val: Option<P>) {
+    if let Some(val) = val {
+        data.push(RtAttrPacket::create_with_payload(kind, val));
+    }
+}
+
+/// Append flag attribute `kind` to `data` if `on`, presence of a flag is its value
+pub fn push_flag(data: &mut Vec<RtAttrPacket<'static>>, kind: u16, on: bool) {
+    if on {
+        let empty: &[&dyn ToPayload] = &[];
+        data.push(RtAttrPacket::create_with_payload(kind, empty));
+    }
+}
+
+/// Attribute `kind` with `attrs` nested in its payload
+pub fn nested(kind: u16, attrs: &[RtAttrPacket]) -> RtAttrPacket<'static> {
+    let attrs: Vec<&dyn ToPayload> = attrs.iter().map(|rta| rta as &dyn ToPayload).collect();
+    RtAttrPacket::create_with_payload(kind, &attrs[..])
+}
+
+fn link_info_rta(kind: &dyn LinkKind) -> RtAttrPacket<'static> {
+    let data = kind.info_data();
+    let kind_rta = RtAttrPacket::create_with_payload(IFLA_INFO_KIND, kind.kind());
+    if data.is_empty() {
+        RtAttrPacket::create_with_payload(IFLA_LINKINFO, kind_rta)
+    } else {
+        nested(IFLA_LINKINFO, &[kind_rta, nested(IFLA_INFO_DATA, &data)])
+    }
+}
+
+impl LinkKind for LinkType {
+    fn kind(&self) -> &str {
+        LinkType::kind(self)
+    }
+}
+
 /// Interface (link) flags
 bitflags! {
     pub struct IfFlags: u32 {
@@ -165,7 +300,7 @@ impl IfFlags {
 }
 
 /// Operating state
-#[derive(Debug,PartialEq,Eq)]
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
 #[repr(u8)]
 pub enum OperState {
     Unknown = 0,
@@ -203,6 +338,21 @@ impl<R: Read> Iterator for LinksIterator<R> {
     }
 }
 
+/// Send a get request, ENODEV means there is no such link
+fn get_link<C: Read + Write>(conn: &mut C, req: NetlinkPacket<'static>) -> io::Result<Option<Link>> {
+    conn.write(req.packet())?;
+    let reader = NetlinkReader::new(conn);
+    let mut link = None;
+    for pkt in reader.into_iter() {
+        match pkt.to_io_error() {
+            Some(ref e) if e.raw_os_error() == Some(::libc::ENODEV) => return Ok(None),
+            Some(e) => return Err(e),
+            None => link = Link::from_packet(pkt).or(link),
+        }
+    }
+    Ok(link)
+}
+
 impl ::std::fmt::Debug for Link {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         write!(f, "{}: {:?}", self.get_index(), self.get_name())
@@ -213,138 +363,208 @@ impl ::std::fmt::Debug for Link {
 pub trait Links where Self: Read + Write {
     /// iterate over links
     fn iter_links(&mut self) -> io::Result<Box<LinksIterator<&mut Self>>>;
-    /// returns link by its index
+    /// returns link by its index, None if it does not exist
     fn get_link_by_index(&mut self, index: u32) -> io::Result<Option<Link>>;
-    /// returns link by its name
+    /// returns link by its name, None if it does not exist
     fn get_link_by_name(&mut self, name: &str) -> io::Result<Option<Link>>;
     /// delete link
     fn delete_link(&mut self, link: Link) -> io::Result<()>;
     /// create dummy link
     fn new_dummy_link(&mut self, name: &str) -> io::Result<()>;
+    /// create link described by `builder`
+    fn new_link(&mut self, builder: &LinkBuilder) -> io::Result<()>;
     /// set link down
     fn link_set_down(&mut self, index: u32) -> io::Result<()>;
     /// set link up
     fn link_set_up(&mut self, index: u32) -> io::Result<()>;
+    /// enslave link to `master`, or release it with None
+    fn link_set_master(&mut self, index: u32, master: Option<u32>) -> io::Result<()>;
+    /// change settings of bridge port `index`
+    fn link_set_bridge_port(&mut self, index: u32, port: &BridgePort) -> io::Result<()>;
+    /// change kind specific options of an existing link
+    fn link_set_options(&mut self, index: u32, kind: &dyn LinkKind) -> io::Result<()>;
 }
 
 impl Links for NetlinkConnection {
     fn iter_links(&mut self) -> io::Result<Box<LinksIterator<&mut Self>>> {
-        let req = NetlinkRequestBuilder::new(RTM_GETLINK, NetlinkMsgFlags::NLM_F_DUMP)
-            .append(
-                IfInfoPacketBuilder::new()
-                    .build()
-            ).build();
-        try!(self.write(req.packet()));
+        let req = Link::dump_request();
+        self.write(req.packet())?;
         let reader = NetlinkReader::new(self);
         Ok(Box::new(LinksIterator { iter: reader.into_iter() }))
     }
 
     fn get_link_by_index(&mut self, index: u32) -> io::Result<Option<Link>> {
-        let req = {
-            let buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-            NetlinkRequestBuilder::new(RTM_GETLINK, NetlinkMsgFlags::NLM_F_ACK)
-            .append(
-                IfInfoPacketBuilder::new()
-                    .set_index(index)
-                    .build()
-            ).build()
-        };
-        try!(self.write(req.packet()));
-        let reader = NetlinkReader::new(self);
-        let li = LinksIterator { iter: reader.into_iter() };
-        Ok(li.last())
+        get_link(self, Link::get_by_index_request(index))
     }
 
     fn get_link_by_name(&mut self, name: &str) -> io::Result<Option<Link>> {
-        let req = {
-            NetlinkRequestBuilder::new(RTM_GETLINK, NetlinkMsgFlags::NLM_F_ACK).append({
-                IfInfoPacketBuilder::new().append(
-                    RtAttrPacket::create_with_payload(IFLA_IFNAME, name)).build()
-            }).build()
-        };
-        try!(self.write(req.packet()));
-        let reader = NetlinkReader::new(self);
-        let li = LinksIterator { iter: reader.into_iter() };
-        Ok(li.last())
+        get_link(self, Link::get_by_name_request(name))
     }
 
     fn new_dummy_link(&mut self, name: &str) -> io::Result<()> {
-        let ifi = {
-            IfInfoPacketBuilder::new().
-                append(RtAttrPacket::create_with_payload(IFLA_IFNAME, name)).
-                append(RtAttrPacket::create_with_payload(
-                    IFLA_LINKINFO, RtAttrPacket::create_with_payload(IFLA_INFO_KIND, "dummy"))).build()
-        };
-        let req = NetlinkRequestBuilder::new(RTM_NEWLINK, NetlinkMsgFlags::NLM_F_CREATE | NetlinkMsgFlags::NLM_F_EXCL | NetlinkMsgFlags::NLM_F_ACK)
-            .append(ifi).build();
-        try!(self.write(req.packet()));
+        let req = Link::new_dummy_request(name);
+        self.write(req.packet())?;
+        let reader = NetlinkReader::new(self);
+        reader.read_to_end()
+    }
+
+    fn new_link(&mut self, builder: &LinkBuilder) -> io::Result<()> {
+        let req = builder.request()?;
+        self.write(req.packet())?;
         let reader = NetlinkReader::new(self);
         reader.read_to_end()
     }
 
     fn delete_link(&mut self, link: Link) -> io::Result<()> {
-        let index = link.get_index();
-        let req = {
-            let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-            NetlinkRequestBuilder::new(RTM_DELLINK, NetlinkMsgFlags::NLM_F_ACK)
-            .append({
-                let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                ifinfo.set_family(0 /* AF_UNSPEC */);
-                ifinfo.set_index(index);
-                ifinfo
-            }).build()
-        };
-        try!(self.write(req.packet()));
+        let req = Link::delete_request(link.get_index());
+        self.write(req.packet())?;
         let reader = NetlinkReader::new(self);
         reader.read_to_end()
     }
 
     fn link_set_down(&mut self, index: u32) -> io::Result<()> {
-        let req = {
-            let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-            NetlinkRequestBuilder::new(RTM_NEWLINK, NetlinkMsgFlags::NLM_F_ACK)
-                .append({
-                    let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                    ifinfo.set_family(0 /* AF_UNSPEC */);
-                    ifinfo.set_index(index);
-                    ifinfo.set_change(IfFlags::UP.bits);
-                    ifinfo.set_flags(IfFlags::new(0) & !IfFlags::UP);
-                    ifinfo
-                }).build()
-        };
-
-       try!(self.write(req.packet()));
-       let reader = NetlinkReader::new(self);
-       reader.read_to_end()
+        let req = Link::set_up_down_request(index, false);
+        self.write(req.packet())?;
+        let reader = NetlinkReader::new(self);
+        reader.read_to_end()
     }
 
     fn link_set_up(&mut self, index: u32) -> io::Result<()> {
-        let req = {
-            let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-            NetlinkRequestBuilder::new(RTM_NEWLINK, NetlinkMsgFlags::NLM_F_ACK)
-                .append({
-                    let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                    ifinfo.set_family(0 /* AF_UNSPEC */);
-                    ifinfo.set_index(index);
-                    ifinfo.set_change(IfFlags::UP.bits);
-                    ifinfo.set_flags(IfFlags::UP);
-                    ifinfo
-                }).build()
-        };
+        let req = Link::set_up_down_request(index, true);
+        self.write(req.packet())?;
+        let reader = NetlinkReader::new(self);
+        reader.read_to_end()
+    }
+
+    fn link_set_master(&mut self, index: u32, master: Option<u32>) -> io::Result<()> {
+        let req = Link::set_master_request(index, master);
+        self.write(req.packet())?;
+        let reader = NetlinkReader::new(self);
+        reader.read_to_end()
+    }
+
+    fn link_set_bridge_port(&mut self, index: u32, port: &BridgePort) -> io::Result<()> {
+        let req = Link::set_bridge_port_request(index, port);
+        self.write(req.packet())?;
+        let reader = NetlinkReader::new(self);
+        reader.read_to_end()
+    }
 
-       try!(self.write(req.packet()));
-       let reader = NetlinkReader::new(self);
-       reader.read_to_end()
+    fn link_set_options(&mut self, index: u32, kind: &dyn LinkKind) -> io::Result<()> {
+        let req = Link::set_options_request(index, kind)?;
+        self.write(req.packet())?;
+        let reader = NetlinkReader::new(self);
+        reader.read_to_end()
     }
 
 }
 
+/// Requests shared by the `Links` trait and async `Handle`
+impl Link {
+    /// Wrap a RTM_NEWLINK or RTM_DELLINK message
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Option<Link> {
+        match packet.get_kind() {
+            RTM_NEWLINK | RTM_DELLINK => Some(Link { packet: packet }),
+            _ => None,
+        }
+    }
+
+    /// Request dumping all links
+    pub fn dump_request() -> NetlinkPacket<'static> {
+        NetlinkRequestBuilder::new(RTM_GETLINK, NetlinkMsgFlags::NLM_F_DUMP)
+            .append(IfInfoPacketBuilder::new().build())
+            .build()
+    }
+
+    /// Request link with index `index`
+    pub fn get_by_index_request(index: u32) -> NetlinkPacket<'static> {
+        NetlinkRequestBuilder::new(RTM_GETLINK, NetlinkMsgFlags::NLM_F_ACK)
+            .append(IfInfoPacketBuilder::new().set_index(index).build())
+            .build()
+    }
+
+    /// Request link with name `name`
+    pub fn get_by_name_request(name: &str) -> NetlinkPacket<'static> {
+        NetlinkRequestBuilder::new(RTM_GETLINK, NetlinkMsgFlags::NLM_F_ACK)
+            .append(IfInfoPacketBuilder::new()
+                    .append(RtAttrPacket::create_with_payload(IFLA_IFNAME, name))
+                    .build())
+            .build()
+    }
+
+    /// Request creating dummy link `name`
+    pub fn new_dummy_request(name: &str) -> NetlinkPacket<'static> {
+        LinkBuilder::new(name).kind(LinkType::Dummy).to_request()
+    }
+
+    /// Request deleting link with index `index`
+    pub fn delete_request(index: u32) -> NetlinkPacket<'static> {
+        NetlinkRequestBuilder::new(RTM_DELLINK, NetlinkMsgFlags::NLM_F_ACK)
+            .append(IfInfoPacketBuilder::new().set_family(AddressFamily::Unspec).set_index(index).build())
+            .build()
+    }
+
+    /// Request setting link with index `index` up or down
+    pub fn set_up_down_request(index: u32, up: bool) -> NetlinkPacket<'static> {
+        let flags = if up { IfFlags::UP } else { IfFlags::new(0) };
+        NetlinkRequestBuilder::new(RTM_NEWLINK, NetlinkMsgFlags::NLM_F_ACK)
+            .append(IfInfoPacketBuilder::new()
+                    .set_family(AddressFamily::Unspec)
+                    .set_index(index)
+                    .set_flags(flags)
+                    .set_change(IfFlags::UP.bits)
+                    .build())
+            .build()
+    }
+
+    /// Request enslaving link `index` to `master`, releasing it with None
+    pub fn set_master_request(index: u32, master: Option<u32>) -> NetlinkPacket<'static> {
+        NetlinkRequestBuilder::new(RTM_SETLINK, NetlinkMsgFlags::NLM_F_ACK)
+            .append(IfInfoPacketBuilder::new()
+                    .set_family(AddressFamily::Unspec)
+                    .set_index(index)
+                    .append(RtAttrPacket::create_with_payload(IFLA_MASTER, master.unwrap_or(0)))
+                    .build())
+            .build()
+    }
+
+    /// Request changing settings of bridge port `index`
+    pub fn set_bridge_port_request(index: u32, port: &BridgePort) -> NetlinkPacket<'static> {
+        NetlinkRequestBuilder::new(RTM_SETLINK, NetlinkMsgFlags::NLM_F_ACK)
+            .append(IfInfoPacketBuilder::new()
+                    .set_family(AddressFamily::Bridge)
+                    .set_index(index)
+                    .append(port.protinfo())
+                    .build())
+            .build()
+    }
+
+    /// Request changing kind specific options of link `index`
+    ///
+    /// Fails with `InvalidInput` if the options are inconsistent.
+    pub fn set_options_request(index: u32, kind: &dyn LinkKind) -> io::Result<NetlinkPacket<'static>> {
+        kind.validate()?;
+        Ok(NetlinkRequestBuilder::new(RTM_NEWLINK, NetlinkMsgFlags::NLM_F_ACK)
+            .append(IfInfoPacketBuilder::new()
+                    .set_family(AddressFamily::Unspec)
+                    .set_index(index)
+                    .append(link_info_rta(kind))
+                    .build())
+            .build())
+    }
+}
+
 impl Link {
     /// Get link's unique index
     pub fn get_index(&self) -> u32 {
         self.with_ifinfo(|ifi| ifi.get_index())
     }
 
+    /// Get message family, `Bridge` for bridge port messages
+    pub fn get_family(&self) -> AddressFamily {
+        self.with_ifinfo(|ifi| ifi.get_family())
+    }
+
     /// Get link's type
     pub fn get_type(&self) -> IfType {
         self.with_ifinfo(|ifi| ifi.get_type_())
@@ -389,6 +609,21 @@ impl Link {
         })
     }
 
+    /// Check whether link is administratively up
+    pub fn is_up(&self) -> bool {
+        self.get_flags().contains(IfFlags::UP)
+    }
+
+    /// Get carrier state, None if not reported
+    pub fn get_carrier(&self) -> Option<bool> {
+        self.with_rta(IFLA_CARRIER, |rta| rta.payload()[0] != 0)
+    }
+
+    /// Get number of carrier changes since the link was created
+    pub fn get_carrier_changes(&self) -> Option<u32> {
+        self.with_rta(IFLA_CARRIER_CHANGES, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
     /// Get broadcast address
     pub fn get_broadcast(&self) -> Option<MacAddr> {
         self.with_rta(IFLA_BROADCAST, |rta| {
@@ -406,6 +641,165 @@ impl Link {
         })
     }
 
+    /// Get alias
+    pub fn get_alias(&self) -> Option<String> {
+        use std::ffi::CStr;
+        self.with_rta(IFLA_IFALIAS, |rta| {
+            let cstr = CStr::from_bytes_with_nul(rta.payload()).unwrap();
+            cstr.to_owned().into_string().unwrap()
+        })
+    }
+
+    /// Get transmit queue length
+    pub fn get_txqlen(&self) -> Option<u32> {
+        self.with_rta(IFLA_TXQLEN, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    /// Get index of the master device (bridge, bond, vrf)
+    pub fn get_master(&self) -> Option<u32> {
+        self.with_rta(IFLA_MASTER, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    /// Get index of the lower (parent) device
+    pub fn get_link(&self) -> Option<u32> {
+        self.with_rta(IFLA_LINK, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    /// Get id of the namespace the lower device lives in, if not this one
+    pub fn get_link_netnsid(&self) -> Option<i32> {
+        self.with_rta(IFLA_LINK_NETNSID, |rta| NativeEndian::read_i32(rta.payload()))
+    }
+
+    /// Get link kind (`IFLA_INFO_KIND`), e.g. "veth"
+    pub fn get_kind(&self) -> Option<String> {
+        use std::ffi::CStr;
+        self.with_rta(IFLA_LINKINFO, |rta| {
+            RtAttrIterator::new(rta.payload())
+                .find(|rta| rta.get_rta_type() == IFLA_INFO_KIND)
+                .map(|rta| CStr::from_bytes_with_nul(rta.payload()).unwrap().to_owned().into_string().unwrap())
+        }).and_then(|kind| kind)
+    }
+
+    /// Get kind specific attributes (`IFLA_INFO_DATA` payload)
+    pub fn get_info_data(&self) -> Option<Vec<u8>> {
+        self.with_rta(IFLA_LINKINFO, |rta| {
+            RtAttrIterator::new(rta.payload())
+                .find(|rta| rta.get_rta_type() == IFLA_INFO_DATA)
+                .map(|rta| rta.payload().to_vec())
+        }).and_then(|data| data)
+    }
+
+    fn is_kind(&self, kind: &str) -> bool {
+        self.get_kind().as_ref().map(|k| k.as_str()) == Some(kind)
+    }
+
+    /// Get vlan settings, None unless the link kind is "vlan"
+    pub fn get_vlan(&self) -> Option<VlanInfo> {
+        if !self.is_kind("vlan") {
+            return None;
+        }
+        self.get_info_data().and_then(|data| VlanInfo::from_info_data(&data))
+    }
+
+    /// Get bridge settings, None unless the link kind is "bridge"
+    pub fn get_bridge(&self) -> Option<BridgeInfo> {
+        if !self.is_kind("bridge") {
+            return None;
+        }
+        self.get_info_data().map(|data| BridgeInfo::from_info_data(&data))
+    }
+
+    /// Get macvlan or macvtap settings, None for other kinds
+    pub fn get_macvlan(&self) -> Option<MacVlanInfo> {
+        let tap = match self.get_kind() {
+            Some(ref kind) if kind == "macvlan" => false,
+            Some(ref kind) if kind == "macvtap" => true,
+            _ => return None,
+        };
+        Some(MacVlanInfo::from_info_data(tap, &self.get_info_data().unwrap_or_default()))
+    }
+
+    /// Get ipvlan settings, None unless the link kind is "ipvlan"
+    pub fn get_ipvlan(&self) -> Option<IpVlanInfo> {
+        if !self.is_kind("ipvlan") {
+            return None;
+        }
+        self.get_info_data().and_then(|data| IpVlanInfo::from_info_data(&data))
+    }
+
+    /// Get vxlan settings, None unless the link kind is "vxlan"
+    pub fn get_vxlan(&self) -> Option<VxlanInfo> {
+        if !self.is_kind("vxlan") {
+            return None;
+        }
+        self.get_info_data().and_then(|data| VxlanInfo::from_info_data(&data))
+    }
+
+    /// Get ip tunnel settings, None unless the link is a GRE, ERSPAN, IPIP, SIT or IP6TNL tunnel
+    pub fn get_tunnel(&self) -> Option<TunnelInfo> {
+        let kind = self.get_kind().and_then(|kind| TunnelKind::from_kind(&kind))?;
+        self.get_info_data().map(|data| TunnelInfo::from_info_data(kind, &data))
+    }
+
+    /// Get bridge port settings, None unless the link is a bridge port
+    pub fn get_bridge_port(&self) -> Option<BridgePort> {
+        // AF_BRIDGE messages carry them in IFLA_PROTINFO, others as slave data
+        if self.get_family() == AddressFamily::Bridge {
+            return self.with_rta_iter(|mut rti| {
+                rti.find(|rta| rta.get_rta_type() & NLA_TYPE_MASK == IFLA_PROTINFO)
+                    .map(|rta| BridgePort::from_attrs(rta.payload()))
+            });
+        }
+        self.get_slave_data("bridge").map(|data| BridgePort::from_attrs(&data))
+    }
+
+    /// Get master specific attributes (`IFLA_INFO_SLAVE_DATA` payload) if
+    /// the link is enslaved to a master of kind `slave_kind`, e.g. "bond"
+    pub fn get_slave_data(&self, slave_kind: &str) -> Option<Vec<u8>> {
+        self.with_rta(IFLA_LINKINFO, |rta| {
+            let matches = RtAttrIterator::new(rta.payload())
+                .any(|rta| rta.get_rta_type() == IFLA_INFO_SLAVE_KIND
+                     && rta.payload().split(|&b| b == 0).next() == Some(slave_kind.as_bytes()));
+            if !matches {
+                return None;
+            }
+            RtAttrIterator::new(rta.payload())
+                .find(|rta| rta.get_rta_type() == IFLA_INFO_SLAVE_DATA)
+                .map(|rta| rta.payload().to_vec())
+        }).and_then(|data| data)
+    }
+
+    /// Get bond settings, None unless the link kind is "bond"
+    pub fn get_bond(&self) -> Option<BondInfo> {
+        if !self.is_kind("bond") {
+            return None;
+        }
+        self.get_info_data().map(|data| BondInfo::from_info_data(&data))
+    }
+
+    /// Get bond slave state, None unless the link is enslaved to a bond
+    pub fn get_bond_slave(&self) -> Option<BondSlave> {
+        self.get_slave_data("bond").map(|data| BondSlave::from_slave_data(&data))
+    }
+
+    /// Get address family specific attributes (`IFLA_AF_SPEC` payload)
+    pub fn get_af_spec(&self) -> Option<Vec<u8>> {
+        self.with_rta_iter(|mut rti| {
+            rti.find(|rta| rta.get_rta_type() & NLA_TYPE_MASK == IFLA_AF_SPEC)
+                .map(|rta| rta.payload().to_vec())
+        })
+    }
+
+    /// Get link mode (0 is default, 1 is dormant)
+    pub fn get_link_mode(&self) -> Option<u8> {
+        self.with_rta(IFLA_LINKMODE, |rta| rta.payload()[0])
+    }
+
+    /// Get device group
+    pub fn get_group(&self) -> Option<u32> {
+        self.with_rta(IFLA_GROUP, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
     // helper methods
     fn with_packet<T,F>(&self, cb: F) -> T
         where F: Fn(&NetlinkPacket) -> T {
@@ -482,6 +876,323 @@ impl Link {
     }
 }
 
+/// Link statistics (IFLA_STATS64)
+#[derive(Debug,Copy,Clone,Default,PartialEq,Eq,Hash)]
+pub struct LinkStats {
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    pub multicast: u64,
+    pub collisions: u64,
+}
+
+impl LinkStats {
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 10 * 8 {
+            return None;
+        }
+        let field = |n: usize| NativeEndian::read_u64(&bytes[n * 8..]);
+        Some(LinkStats {
+            rx_packets: field(0),
+            tx_packets: field(1),
+            rx_bytes: field(2),
+            tx_bytes: field(3),
+            rx_errors: field(4),
+            tx_errors: field(5),
+            rx_dropped: field(6),
+            tx_dropped: field(7),
+            multicast: field(8),
+            collisions: field(9),
+        })
+    }
+}
+
+/// Owned link description
+///
+/// Unlike `Link`, which looks attributes up in the received packet on every
+/// call, `LinkInfo` parses the message once and can be cloned, compared and
+/// hashed freely.
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub struct LinkInfo {
+    pub index: u32,
+    pub type_: IfType,
+    pub flags: IfFlags,
+    pub name: Option<String>,
+    pub alias: Option<String>,
+    pub mtu: Option<u32>,
+    pub txqlen: Option<u32>,
+    pub mac: Option<MacAddr>,
+    pub broadcast: Option<MacAddr>,
+    pub qdisc: Option<String>,
+    pub state: Option<OperState>,
+    pub link_mode: Option<u8>,
+    pub group: Option<u32>,
+    /// `IFLA_INFO_KIND` of virtual links, e.g. "dummy" or "bridge"
+    pub kind: Option<String>,
+    pub master: Option<u32>,
+    pub link: Option<u32>,
+    pub carrier: Option<bool>,
+    pub stats: Option<LinkStats>,
+}
+
+impl<'a> From<&'a Link> for LinkInfo {
+    fn from(link: &'a Link) -> Self {
+        use std::ffi::CStr;
+        use std::mem;
+
+        // malformed strings are skipped, invalid UTF-8 is replaced
+        let string = |rta: &RtAttrPacket| {
+            CStr::from_bytes_with_nul(rta.payload()).ok().map(|s| s.to_string_lossy().into_owned())
+        };
+        let mac = |rta: &RtAttrPacket| {
+            let payload = rta.payload();
+            MacAddr::new(payload[0], payload[1], payload[2], payload[3], payload[4], payload[5])
+        };
+        link.with_ifinfo(|ifi| {
+            let mut info = LinkInfo {
+                index: ifi.get_index(),
+                type_: ifi.get_type_(),
+                flags: ifi.get_flags(),
+                name: None,
+                alias: None,
+                mtu: None,
+                txqlen: None,
+                mac: None,
+                broadcast: None,
+                qdisc: None,
+                state: None,
+                link_mode: None,
+                group: None,
+                kind: None,
+                master: None,
+                link: None,
+                carrier: None,
+                stats: None,
+            };
+            for rta in RtAttrIterator::new(ifi.payload()) {
+                match rta.get_rta_type() {
+                    IFLA_IFNAME => info.name = string(&rta),
+                    IFLA_IFALIAS => info.alias = string(&rta),
+                    IFLA_QDISC => info.qdisc = string(&rta),
+                    IFLA_MTU if rta.payload().len() >= 4 => info.mtu = Some(NativeEndian::read_u32(rta.payload())),
+                    IFLA_TXQLEN if rta.payload().len() >= 4 => info.txqlen = Some(NativeEndian::read_u32(rta.payload())),
+                    IFLA_GROUP if rta.payload().len() >= 4 => info.group = Some(NativeEndian::read_u32(rta.payload())),
+                    IFLA_MASTER if rta.payload().len() >= 4 => info.master = Some(NativeEndian::read_u32(rta.payload())),
+                    IFLA_LINK if rta.payload().len() >= 4 => info.link = Some(NativeEndian::read_u32(rta.payload())),
+                    IFLA_ADDRESS if rta.payload().len() == 6 => info.mac = Some(mac(&rta)),
+                    IFLA_BROADCAST if rta.payload().len() == 6 => info.broadcast = Some(mac(&rta)),
+                    IFLA_OPERSTATE if !rta.payload().is_empty() && rta.payload()[0] <= OperState::Up as u8 => {
+                        info.state = Some(unsafe { mem::transmute(rta.payload()[0]) })
+                    },
+                    IFLA_LINKMODE if !rta.payload().is_empty() => info.link_mode = Some(rta.payload()[0]),
+                    IFLA_CARRIER if !rta.payload().is_empty() => info.carrier = Some(rta.payload()[0] != 0),
+                    IFLA_STATS64 => info.stats = LinkStats::from_bytes(rta.payload()),
+                    IFLA_LINKINFO => {
+                        info.kind = RtAttrIterator::new(rta.payload())
+                            .find(|rta| rta.get_rta_type() == IFLA_INFO_KIND)
+                            .and_then(|rta| string(&rta));
+                    },
+                    _ => {},
+                }
+            }
+            info
+        })
+    }
+}
+
+impl From<Link> for LinkInfo {
+    fn from(link: Link) -> Self {
+        LinkInfo::from(&link)
+    }
+}
+
+impl LinkInfo {
+    /// Build a request of `kind` (e.g. RTM_NEWLINK) describing this link
+    ///
+    /// Name, MTU, queue length, hardware address, master, alias and kind are
+    /// carried as attributes; of the flags only `UP` is requested to change.
+    pub fn to_request(&self, kind: u16, flags: NetlinkMsgFlags) -> NetlinkPacket<'static> {
+        let mut ifi = IfInfoPacketBuilder::new()
+            .set_index(self.index)
+            .set_flags(self.flags & IfFlags::UP)
+            .set_change(IfFlags::UP.bits());
+        if let Some(ref name) = self.name {
+            ifi = ifi.append(RtAttrPacket::create_with_payload(IFLA_IFNAME, name.as_str()));
+        }
+        if let Some(mtu) = self.mtu {
+            ifi = ifi.append(RtAttrPacket::create_with_payload(IFLA_MTU, mtu));
+        }
+        if let Some(txqlen) = self.txqlen {
+            ifi = ifi.append(RtAttrPacket::create_with_payload(IFLA_TXQLEN, txqlen));
+        }
+        if let Some(mac) = self.mac {
+            ifi = ifi.append(RtAttrPacket::create_with_payload(IFLA_ADDRESS, mac));
+        }
+        if let Some(master) = self.master {
+            ifi = ifi.append(RtAttrPacket::create_with_payload(IFLA_MASTER, master));
+        }
+        if let Some(ref alias) = self.alias {
+            ifi = ifi.append(RtAttrPacket::create_with_payload(IFLA_IFALIAS, alias.as_str()));
+        }
+        if let Some(ref link_kind) = self.kind {
+            ifi = ifi.append(RtAttrPacket::create_with_payload(
+                IFLA_LINKINFO, RtAttrPacket::create_with_payload(IFLA_INFO_KIND, link_kind.as_str())));
+        }
+        NetlinkRequestBuilder::new(kind, flags).append(ifi.build()).build()
+    }
+}
+
+/// Network namespace to create a link in
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Netns {
+    /// Namespace of process `pid`
+    Pid(u32),
+    /// Namespace file descriptor, e.g. of an open `/var/run/netns/NAME`
+    Fd(RawFd),
+}
+
+impl Netns {
+    fn to_rta(&self) -> RtAttrPacket<'static> {
+        match *self {
+            Netns::Pid(pid) => RtAttrPacket::create_with_payload(IFLA_NET_NS_PID, pid),
+            Netns::Fd(fd) => RtAttrPacket::create_with_payload(IFLA_NET_NS_FD, fd as u32),
+        }
+    }
+}
+
+/// Builder of a single `RTM_NEWLINK` request creating a link
+///
+/// ```no_run
+/// use pnetlink::packet::netlink::NetlinkConnection;
+/// use pnetlink::packet::route::link::{LinkBuilder,LinkType,Links};
+///
+/// let mut conn = NetlinkConnection::new();
+/// conn.new_link(&LinkBuilder::new("dummy0").kind(LinkType::Dummy).mtu(9000).up(true)).unwrap();
+/// ```
+pub struct LinkBuilder {
+    name: String,
+    kind: Option<Box<dyn LinkKind + Send + Sync>>,
+    parent: Option<u32>,
+    mtu: Option<u32>,
+    mac: Option<MacAddr>,
+    txqlen: Option<u32>,
+    master: Option<u32>,
+    netns: Option<Netns>,
+    up: bool,
+}
+
+impl LinkBuilder {
+    /// Create link `name`
+    pub fn new(name: &str) -> Self {
+        LinkBuilder {
+            name: name.to_owned(),
+            kind: None,
+            parent: None,
+            mtu: None,
+            mac: None,
+            txqlen: None,
+            master: None,
+            netns: None,
+            up: false,
+        }
+    }
+
+    /// Set kind and kind specific options
+    pub fn kind<K: LinkKind + Send + Sync + 'static>(mut self, kind: K) -> Self {
+        self.kind = Some(Box::new(kind));
+        self
+    }
+
+    /// Set parent link (`IFLA_LINK`), e.g. of a VLAN or macvlan
+    pub fn parent(mut self, index: u32) -> Self {
+        self.parent = Some(index);
+        self
+    }
+
+    pub fn mtu(mut self, mtu: u32) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    pub fn mac(mut self, mac: MacAddr) -> Self {
+        self.mac = Some(mac);
+        self
+    }
+
+    pub fn txqlen(mut self, txqlen: u32) -> Self {
+        self.txqlen = Some(txqlen);
+        self
+    }
+
+    /// Enslave to link `index`, e.g. a bridge or bond
+    pub fn master(mut self, index: u32) -> Self {
+        self.master = Some(index);
+        self
+    }
+
+    /// Create the link in namespace `netns`
+    pub fn netns(mut self, netns: Netns) -> Self {
+        self.netns = Some(netns);
+        self
+    }
+
+    /// Bring the link up on creation
+    pub fn up(mut self, up: bool) -> Self {
+        self.up = up;
+        self
+    }
+
+    /// Build `IFLA_LINKINFO` attribute, None without kind
+    pub fn link_info(&self) -> Option<RtAttrPacket<'static>> {
+        self.kind.as_ref().map(|kind| link_info_rta(&**kind))
+    }
+
+    /// Build the link description, without the netlink header
+    pub fn build(&self) -> IfInfoPacket<'static> {
+        let flags = if self.up { IfFlags::UP } else { IfFlags::new(0) };
+        let mut ifi = IfInfoPacketBuilder::new()
+            .set_flags(flags)
+            .set_change(IfFlags::UP.bits())
+            .append(RtAttrPacket::create_with_payload(IFLA_IFNAME, self.name.as_str()));
+        let attrs = [(IFLA_LINK, self.parent), (IFLA_MTU, self.mtu), (IFLA_TXQLEN, self.txqlen), (IFLA_MASTER, self.master)];
+        for &(kind, val) in attrs.iter() {
+            if let Some(val) = val {
+                ifi = ifi.append(RtAttrPacket::create_with_payload(kind, val));
+            }
+        }
+        if let Some(mac) = self.mac {
+            ifi = ifi.append(RtAttrPacket::create_with_payload(IFLA_ADDRESS, mac));
+        }
+        if let Some(netns) = self.netns {
+            ifi = ifi.append(netns.to_rta());
+        }
+        if let Some(link_info) = self.link_info() {
+            ifi = ifi.append(link_info);
+        }
+        ifi.build()
+    }
+
+    /// Build the `RTM_NEWLINK` request, failing if the link exists
+    ///
+    /// Fails with `InvalidInput` if the kind options are inconsistent.
+    pub fn request(&self) -> io::Result<NetlinkPacket<'static>> {
+        if let Some(ref kind) = self.kind {
+            kind.validate()?;
+        }
+        Ok(self.to_request())
+    }
+
+    fn to_request(&self) -> NetlinkPacket<'static> {
+        NetlinkRequestBuilder::new(RTM_NEWLINK, NetlinkMsgFlags::NLM_F_CREATE | NetlinkMsgFlags::NLM_F_EXCL | NetlinkMsgFlags::NLM_F_ACK)
+            .append(self.build()).build()
+    }
+}
+
 pub struct IfInfoPacketBuilder {
     data: Vec<u8>,
 }
@@ -493,7 +1204,7 @@ impl IfInfoPacketBuilder {
         IfInfoPacketBuilder { data: data }
     }
 
-    pub fn set_family(mut self, family: u8) -> Self {
+    pub fn set_family(mut self, family: AddressFamily) -> Self {
         {
             let mut pkt = MutableIfInfoPacket::new(&mut self.data[..]).unwrap();
             pkt.set_family(family);
@@ -525,9 +1236,17 @@ impl IfInfoPacketBuilder {
         self
     }
 
+    pub fn set_change(mut self, change: u32) -> Self {
+        {
+            let mut pkt = MutableIfInfoPacket::new(&mut self.data[..]).unwrap();
+            pkt.set_change(change);
+        }
+        self
+    }
+
     pub fn append(mut self, rta: RtAttrPacket) -> Self {
         let len = rta.get_rta_len() as usize;
-        let aligned_len = ::util::align(len);
+        let aligned_len = crate::util::align(len);
         self.data.extend_from_slice(&rta.packet()[0..len]);
         // add padding for alignment
         for _ in len..aligned_len {
@@ -545,18 +1264,62 @@ impl IfInfoPacketBuilder {
 mod tests {
     #[test]
     fn dump_links() {
-        use ::packet::netlink::NetlinkConnection;
-        use ::packet::route::link::{Link,Links};
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::{Link,Links};
         let mut conn = NetlinkConnection::new();
         for link in conn.iter_links().unwrap() {
             Link::dump_link(link.packet);
         }
     }
 
+    #[test]
+    fn link_info_roundtrip() {
+        use crate::packet::netlink::{NetlinkConnection,NetlinkMsgFlags,NetlinkReader};
+        use crate::packet::route::link::{Links,LinkInfo,LinksIterator,RTM_NEWLINK};
+        use pnet::packet::Packet;
+        use std::io::Cursor;
+
+        let mut conn = NetlinkConnection::new();
+        let lo = LinkInfo::from(conn.get_link_by_name("lo").unwrap().unwrap());
+        let req = lo.to_request(RTM_NEWLINK, NetlinkMsgFlags::empty());
+        let mut links = LinksIterator { iter: NetlinkReader::new(Cursor::new(req.packet().to_vec())).into_iter() };
+        let parsed = LinkInfo::from(links.next().unwrap());
+        assert_eq!(parsed.index, lo.index);
+        assert_eq!(parsed.name, lo.name);
+        assert_eq!(parsed.mtu, lo.mtu);
+        assert_eq!(parsed.mac, lo.mac);
+    }
+
+    #[test]
+    fn link_info_malformed() {
+        use crate::packet::netlink::{NetlinkMsgFlags,NetlinkRequestBuilder};
+        use crate::packet::route::RtAttrPacket;
+        use crate::packet::route::link::{IfInfoPacketBuilder,Link,LinkInfo,RTM_NEWLINK,
+                                         IFLA_IFNAME,IFLA_IFALIAS,IFLA_MTU,IFLA_OPERSTATE};
+        use crate::packet::route::route::ToPayload;
+
+        let empty: &[&dyn ToPayload] = &[];
+        let req = NetlinkRequestBuilder::new(RTM_NEWLINK, NetlinkMsgFlags::empty())
+            .append(IfInfoPacketBuilder::new()
+                    // not NUL-terminated
+                    .append(RtAttrPacket::create_with_payload(IFLA_IFNAME, 0xffu8))
+                    // invalid UTF-8
+                    .append(RtAttrPacket::create_with_payload(IFLA_IFALIAS, 0x00ffu16.to_le()))
+                    .append(RtAttrPacket::create_with_payload(IFLA_MTU, 0x05u8))
+                    .append(RtAttrPacket::create_with_payload(IFLA_OPERSTATE, empty))
+                    .build())
+            .build();
+        let info = LinkInfo::from(Link::from_packet(req).unwrap());
+        assert_eq!(info.name, None);
+        assert_eq!(info.alias, Some("\u{fffd}".to_owned()));
+        assert_eq!(info.mtu, None);
+        assert_eq!(info.state, None);
+    }
+
     #[test]
     fn find_lo() {
-        use ::packet::netlink::NetlinkConnection;
-        use ::packet::route::link::Links;
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::Links;
 
         let mut conn = NetlinkConnection::new();
         let lo0 = conn.get_link_by_name("lo").unwrap();
@@ -567,13 +1330,16 @@ mod tests {
         assert!(lo1.is_some());
         let lo1 = lo1.unwrap();
         assert!(lo1.get_name() == lo0.get_name());
+        // same as the async Handle: no such link is None, not an error
+        assert!(conn.get_link_by_name("nonexistent0").unwrap().is_none());
+        assert!(conn.get_link_by_index(0x7fff_fff0).unwrap().is_none());
     }
 
     #[test]
     // root permissions required
     fn create_and_delete_link() {
-        use ::packet::netlink::NetlinkConnection;
-        use ::packet::route::link::Links;
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::Links;
 
         let mut conn = NetlinkConnection::new();
         conn.new_dummy_link("test1488").unwrap();
@@ -587,9 +1353,9 @@ mod tests {
     #[test]
     // CAP_NET_ADMIN needed
     fn up_and_down_link() {
-        use ::packet::netlink::NetlinkConnection;
-        use ::packet::route::link::Links;
-        use ::packet::route::link::IfFlags;
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::Links;
+        use crate::packet::route::link::IfFlags;
 
         let linkname = "test1489";
 
@@ -610,4 +1376,24 @@ mod tests {
 
         conn.delete_link(link);
     }
+
+    #[test]
+    fn create_link_with_builder() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::{Links,LinkBuilder,LinkInfo,LinkType,IfFlags};
+        use pnet::util::MacAddr;
+
+        let mut conn = NetlinkConnection::new();
+        let mac = MacAddr::new(0x02, 0, 0, 0, 0x41, 0x01);
+        conn.new_link(&LinkBuilder::new("pnl-build0").kind(LinkType::Dummy)
+                      .mtu(1400).mac(mac).txqlen(42).up(true)).unwrap();
+        let link = conn.get_link_by_name("pnl-build0").unwrap().unwrap();
+        let info = LinkInfo::from(&link);
+        assert_eq!(info.kind, Some("dummy".to_owned()));
+        assert_eq!(info.mtu, Some(1400));
+        assert_eq!(info.mac, Some(mac));
+        assert_eq!(info.txqlen, Some(42));
+        assert!(info.flags.contains(IfFlags::UP));
+        conn.delete_link(link).unwrap();
+    }
 }
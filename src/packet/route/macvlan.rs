@@ -0,0 +1,329 @@
+//! MACVLAN, MACVTAP and IPVLAN links stacked on a parent link
+//!
+//! # Example
+//! ```no_run
+//! use pnetlink::packet::netlink::NetlinkConnection;
+//! use pnetlink::packet::route::link::{Links,LinkBuilder};
+//! use pnetlink::packet::route::macvlan::{IpVlan,IpVlanFlags,IpVlanMode,MacVlan,MacVlanMode};
+//!
+//! let mut conn = NetlinkConnection::new();
+//! let eth0 = conn.get_link_by_name("eth0").unwrap().unwrap();
+//! conn.new_link(&LinkBuilder::new("mv0").parent(eth0.get_index())
+//!     .kind(MacVlan::new(MacVlanMode::Bridge))).unwrap();
+//! conn.new_link(&LinkBuilder::new("ipv0").parent(eth0.get_index())
+//!     .kind(IpVlan::new(IpVlanMode::L3S).flags(IpVlanFlags::PRIVATE))).unwrap();
+//! ```
+use crate::packet::route::{RtAttrIterator,RtAttrPacket};
+use crate::packet::route::link::{LinkKind,nested,push_opt};
+use crate::packet::route::route::WithPayload;
+use byteorder::{ByteOrder,NativeEndian};
+use pnet::packet::Packet;
+use pnet::util::MacAddr;
+
+pub const IFLA_MACVLAN_UNSPEC: u16 = 0;
+pub const IFLA_MACVLAN_MODE: u16 = 1;
+pub const IFLA_MACVLAN_FLAGS: u16 = 2;
+pub const IFLA_MACVLAN_MACADDR_MODE: u16 = 3;
+pub const IFLA_MACVLAN_MACADDR: u16 = 4;
+pub const IFLA_MACVLAN_MACADDR_DATA: u16 = 5;
+pub const IFLA_MACVLAN_MACADDR_COUNT: u16 = 6;
+
+pub const MACVLAN_MACADDR_ADD: u32 = 0;
+pub const MACVLAN_MACADDR_DEL: u32 = 1;
+pub const MACVLAN_MACADDR_FLUSH: u32 = 2;
+pub const MACVLAN_MACADDR_SET: u32 = 3;
+
+pub const IFLA_IPVLAN_UNSPEC: u16 = 0;
+pub const IFLA_IPVLAN_MODE: u16 = 1;
+pub const IFLA_IPVLAN_FLAGS: u16 = 2;
+
+/// How macvlans on the same parent reach each other
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum MacVlanMode {
+    /// No traffic between them
+    Private,
+    /// Through the external switch
+    Vepa,
+    /// Directly
+    Bridge,
+    /// Single macvlan taking over the parent
+    Passthru,
+    /// Only accept traffic from the source MAC list
+    Source,
+    Other(u32),
+}
+
+impl MacVlanMode {
+    pub fn new(val: u32) -> Self {
+        match val {
+            1 => MacVlanMode::Private,
+            2 => MacVlanMode::Vepa,
+            4 => MacVlanMode::Bridge,
+            8 => MacVlanMode::Passthru,
+            16 => MacVlanMode::Source,
+            _ => MacVlanMode::Other(val),
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        match *self {
+            MacVlanMode::Private => 1,
+            MacVlanMode::Vepa => 2,
+            MacVlanMode::Bridge => 4,
+            MacVlanMode::Passthru => 8,
+            MacVlanMode::Source => 16,
+            MacVlanMode::Other(val) => val,
+        }
+    }
+}
+
+bitflags! {
+    pub struct MacVlanFlags: u16 {
+        /// Do not put the parent in promiscuous mode (passthru)
+        const NOPROMISC = 0x1;
+        const NODST = 0x2;
+    }
+}
+
+/// Macvlan or macvtap options, the parent link is given with
+/// `LinkBuilder::parent`
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct MacVlan {
+    tap: bool,
+    mode: Option<MacVlanMode>,
+    flags: Option<MacVlanFlags>,
+    source_macs: Vec<MacAddr>,
+}
+
+impl MacVlan {
+    pub fn new(mode: MacVlanMode) -> Self {
+        MacVlan { tap: false, mode: Some(mode), flags: None, source_macs: vec![] }
+    }
+
+    /// Macvtap, i.e. with a character device, rather than macvlan
+    pub fn macvtap(mode: MacVlanMode) -> Self {
+        MacVlan { tap: true, ..MacVlan::new(mode) }
+    }
+
+    pub fn flags(mut self, flags: MacVlanFlags) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
+    /// Add `mac` to the source list, which replaces the current one
+    pub fn source_mac(mut self, mac: MacAddr) -> Self {
+        self.source_macs.push(mac);
+        self
+    }
+}
+
+impl LinkKind for MacVlan {
+    fn kind(&self) -> &str {
+        if self.tap { "macvtap" } else { "macvlan" }
+    }
+
+    fn info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        let mut data = vec![];
+        push_opt(&mut data, IFLA_MACVLAN_MODE, self.mode.map(|mode| mode.value()));
+        push_opt(&mut data, IFLA_MACVLAN_FLAGS, self.flags.map(|flags| flags.bits()));
+        if !self.source_macs.is_empty() {
+            let macs: Vec<RtAttrPacket<'static>> = self.source_macs.iter()
+                .map(|&mac| RtAttrPacket::create_with_payload(IFLA_MACVLAN_MACADDR, mac))
+                .collect();
+            data.push(RtAttrPacket::create_with_payload(IFLA_MACVLAN_MACADDR_MODE, MACVLAN_MACADDR_SET));
+            data.push(nested(IFLA_MACVLAN_MACADDR_DATA, &macs));
+        }
+        data
+    }
+}
+
+/// Macvlan or macvtap settings of an existing link
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub struct MacVlanInfo {
+    /// Macvtap rather than macvlan
+    pub tap: bool,
+    pub mode: Option<MacVlanMode>,
+    pub flags: Option<MacVlanFlags>,
+    /// Allowed source addresses in source mode
+    pub source_macs: Vec<MacAddr>,
+}
+
+impl MacVlanInfo {
+    /// Parse `IFLA_INFO_DATA` of a "macvlan" or "macvtap" link
+    pub fn from_info_data(tap: bool, data: &[u8]) -> Self {
+        let mut macvlan = MacVlanInfo { tap: tap, mode: None, flags: None, source_macs: vec![] };
+        for rta in RtAttrIterator::new(data) {
+            let payload = rta.payload();
+            match rta.get_rta_type() {
+                IFLA_MACVLAN_MODE if payload.len() >= 4 => macvlan.mode = Some(MacVlanMode::new(NativeEndian::read_u32(payload))),
+                IFLA_MACVLAN_FLAGS if payload.len() >= 2 => {
+                    macvlan.flags = Some(MacVlanFlags::from_bits_truncate(NativeEndian::read_u16(payload)));
+                },
+                IFLA_MACVLAN_MACADDR_DATA => {
+                    macvlan.source_macs = RtAttrIterator::new(payload)
+                        .filter(|rta| rta.get_rta_type() == IFLA_MACVLAN_MACADDR && rta.payload().len() >= 6)
+                        .map(|rta| {
+                            let mac = rta.payload();
+                            MacAddr::new(mac[0], mac[1], mac[2], mac[3], mac[4], mac[5])
+                        })
+                        .collect();
+                },
+                _ => {},
+            }
+        }
+        macvlan
+    }
+}
+
+/// Layer ipvlans on the same parent switch at
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum IpVlanMode {
+    L2,
+    L3,
+    /// L3 going through netfilter on the ipvlan
+    L3S,
+    Other(u16),
+}
+
+impl IpVlanMode {
+    pub fn new(val: u16) -> Self {
+        match val {
+            0 => IpVlanMode::L2,
+            1 => IpVlanMode::L3,
+            2 => IpVlanMode::L3S,
+            _ => IpVlanMode::Other(val),
+        }
+    }
+
+    pub fn value(&self) -> u16 {
+        match *self {
+            IpVlanMode::L2 => 0,
+            IpVlanMode::L3 => 1,
+            IpVlanMode::L3S => 2,
+            IpVlanMode::Other(val) => val,
+        }
+    }
+}
+
+bitflags! {
+    /// Without flags ipvlans on the same parent reach each other directly
+    pub struct IpVlanFlags: u16 {
+        /// No traffic between them
+        const PRIVATE = 0x1;
+        /// Through the external switch
+        const VEPA = 0x2;
+    }
+}
+
+/// Ipvlan options, the parent link is given with `LinkBuilder::parent`
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct IpVlan {
+    mode: IpVlanMode,
+    flags: IpVlanFlags,
+}
+
+impl IpVlan {
+    pub fn new(mode: IpVlanMode) -> Self {
+        IpVlan { mode: mode, flags: IpVlanFlags::empty() }
+    }
+
+    pub fn flags(mut self, flags: IpVlanFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+}
+
+impl LinkKind for IpVlan {
+    fn kind(&self) -> &str {
+        "ipvlan"
+    }
+
+    fn info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        vec![RtAttrPacket::create_with_payload(IFLA_IPVLAN_MODE, self.mode.value()),
+             RtAttrPacket::create_with_payload(IFLA_IPVLAN_FLAGS, self.flags.bits())]
+    }
+}
+
+/// Ipvlan settings of an existing link
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub struct IpVlanInfo {
+    pub mode: IpVlanMode,
+    pub flags: IpVlanFlags,
+}
+
+impl IpVlanInfo {
+    /// Parse `IFLA_INFO_DATA` of an "ipvlan" link, None without mode
+    pub fn from_info_data(data: &[u8]) -> Option<Self> {
+        let mut mode = None;
+        let mut flags = IpVlanFlags::empty();
+        for rta in RtAttrIterator::new(data) {
+            let payload = rta.payload();
+            match rta.get_rta_type() {
+                IFLA_IPVLAN_MODE if payload.len() >= 2 => mode = Some(IpVlanMode::new(NativeEndian::read_u16(payload))),
+                IFLA_IPVLAN_FLAGS if payload.len() >= 2 => flags = IpVlanFlags::from_bits_truncate(NativeEndian::read_u16(payload)),
+                _ => {},
+            }
+        }
+        mode.map(|mode| IpVlanInfo { mode: mode, flags: flags })
+    }
+}
+
+mod tests {
+    #[test]
+    fn macvlan_encoding() {
+        use crate::packet::route::link::{Link,LinkBuilder};
+        use crate::packet::route::macvlan::{IpVlan,IpVlanFlags,IpVlanInfo,IpVlanMode,MacVlan,MacVlanFlags,MacVlanInfo,MacVlanMode};
+        use pnet::util::MacAddr;
+
+        let macs = vec![MacAddr::new(2, 0, 0, 0, 0, 1), MacAddr::new(2, 0, 0, 0, 0, 2)];
+        let macvtap = MacVlan::macvtap(MacVlanMode::Source).flags(MacVlanFlags::NODST)
+            .source_mac(macs[0]).source_mac(macs[1]);
+        let link = Link::from_packet(LinkBuilder::new("pnl-mvtap0").parent(1).kind(macvtap).request().unwrap()).unwrap();
+        assert_eq!(link.get_kind(), Some("macvtap".to_owned()));
+        assert_eq!(link.get_macvlan(), Some(MacVlanInfo {
+            tap: true,
+            mode: Some(MacVlanMode::Source),
+            flags: Some(MacVlanFlags::NODST),
+            source_macs: macs,
+        }));
+        assert!(link.get_ipvlan().is_none());
+
+        let ipvlan = IpVlan::new(IpVlanMode::L3S).flags(IpVlanFlags::VEPA);
+        let link = Link::from_packet(LinkBuilder::new("pnl-ipvl0").parent(1).kind(ipvlan).request().unwrap()).unwrap();
+        assert_eq!(link.get_ipvlan(), Some(IpVlanInfo { mode: IpVlanMode::L3S, flags: IpVlanFlags::VEPA }));
+        assert!(link.get_macvlan().is_none());
+    }
+
+    #[test]
+    // root permissions required
+    fn create_macvlan_and_ipvlan() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::{Links,LinkBuilder,LinkType};
+        use crate::packet::route::macvlan::{IpVlan,IpVlanFlags,IpVlanInfo,IpVlanMode,MacVlan,MacVlanMode};
+        use pnet::util::MacAddr;
+
+        let mut conn = NetlinkConnection::new();
+        conn.new_link(&LinkBuilder::new("pnl-mvpar0").kind(LinkType::Dummy)).unwrap();
+        let parent = conn.get_link_by_name("pnl-mvpar0").unwrap().unwrap();
+        let mac = MacAddr::new(2, 0, 0, 0, 0x48, 1);
+        conn.new_link(&LinkBuilder::new("pnl-mv0").parent(parent.get_index())
+                      .kind(MacVlan::new(MacVlanMode::Source).source_mac(mac))).unwrap();
+        conn.new_link(&LinkBuilder::new("pnl-mv1").parent(parent.get_index())
+                      .kind(MacVlan::new(MacVlanMode::Bridge))).unwrap();
+        conn.new_link(&LinkBuilder::new("pnl-ipv0").parent(parent.get_index())
+                      .kind(IpVlan::new(IpVlanMode::L3).flags(IpVlanFlags::PRIVATE))).unwrap();
+
+        let mv0 = conn.get_link_by_name("pnl-mv0").unwrap().unwrap().get_macvlan().unwrap();
+        assert_eq!(mv0.mode, Some(MacVlanMode::Source));
+        assert_eq!(mv0.source_macs, vec![mac]);
+        let mv1 = conn.get_link_by_name("pnl-mv1").unwrap().unwrap();
+        assert_eq!(mv1.get_link(), Some(parent.get_index()));
+        assert_eq!(mv1.get_macvlan().unwrap().mode, Some(MacVlanMode::Bridge));
+        let ipv0 = conn.get_link_by_name("pnl-ipv0").unwrap().unwrap().get_ipvlan().unwrap();
+        assert_eq!(ipv0, IpVlanInfo { mode: IpVlanMode::L3, flags: IpVlanFlags::PRIVATE });
+
+        // deleting the parent takes the stacked links along
+        conn.delete_link(parent).unwrap();
+        assert!(conn.get_link_by_name("pnl-mv0").unwrap().is_none());
+    }
+}
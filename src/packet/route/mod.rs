@@ -3,19 +3,144 @@
 //! Different layer operations are implemented as traits 
 //! on NetlinkConnection
 pub mod addr;
+pub mod bond;
+pub mod bridge;
+pub mod bridge_vlan;
+pub mod event;
+pub mod flap;
 pub mod link;
+pub mod macvlan;
 pub mod neighbour;
+pub mod prefix;
+pub mod resolver;
+pub mod resync;
 pub mod route;
 pub mod rule;
+#[cfg(feature = "serde")]
+pub mod snapshot;
+pub mod state;
+pub mod tunnel;
+pub mod veth;
+pub mod vlan;
+pub mod vxlan;
+pub mod wait;
+
+use std::net::{IpAddr,Ipv4Addr,Ipv6Addr};
 
 include!(concat!(env!("OUT_DIR"), "/route/route.rs"));
 
 const RTA_ALIGNTO: usize = 4;
 
+/// Attribute type flag marking nested attributes
+pub const NLA_F_NESTED: u16 = 0x8000;
+/// Mask of the attribute type without flags
+pub const NLA_TYPE_MASK: u16 = 0x3fff;
+
 fn align(len: usize) -> usize {
     ((len)+RTA_ALIGNTO-1) & !(RTA_ALIGNTO-1)
 }
 
+/// Address family (AF_* values from linux/socket.h)
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum AddressFamily {
+    Unspec,
+    Unix,
+    Inet,
+    Bridge,
+    Inet6,
+    Packet,
+    Mpls,
+    /// Any other family
+    Other(OtherFamily),
+}
+
+/// AF_* value without a variant of its own
+///
+/// Only `AddressFamily::new` creates one, so `Other` never stands for a
+/// named family and each family compares and hashes the same way.
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub struct OtherFamily(u8);
+
+impl OtherFamily {
+    /// Returns raw AF_* value
+    pub fn to_u8(&self) -> u8 {
+        self.0
+    }
+}
+
+impl AddressFamily {
+    pub fn new(val: u8) -> Self {
+        match val {
+            0 => AddressFamily::Unspec,
+            1 => AddressFamily::Unix,
+            2 => AddressFamily::Inet,
+            7 => AddressFamily::Bridge,
+            10 => AddressFamily::Inet6,
+            17 => AddressFamily::Packet,
+            28 => AddressFamily::Mpls,
+            val => AddressFamily::Other(OtherFamily(val)),
+        }
+    }
+
+    /// Returns raw AF_* value
+    pub fn to_u8(&self) -> u8 {
+        match *self {
+            AddressFamily::Unspec => 0,
+            AddressFamily::Unix => 1,
+            AddressFamily::Inet => 2,
+            AddressFamily::Bridge => 7,
+            AddressFamily::Inet6 => 10,
+            AddressFamily::Packet => 17,
+            AddressFamily::Mpls => 28,
+            AddressFamily::Other(other) => other.to_u8(),
+        }
+    }
+
+    /// Returns true for `Inet` and `Inet6`
+    pub fn is_ip(&self) -> bool {
+        *self == AddressFamily::Inet || *self == AddressFamily::Inet6
+    }
+}
+
+impl From<u8> for AddressFamily {
+    fn from(val: u8) -> Self {
+        AddressFamily::new(val)
+    }
+}
+
+impl From<AddressFamily> for u8 {
+    fn from(family: AddressFamily) -> u8 {
+        family.to_u8()
+    }
+}
+
+impl From<IpAddr> for AddressFamily {
+    fn from(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => AddressFamily::Inet,
+            IpAddr::V6(_) => AddressFamily::Inet6,
+        }
+    }
+}
+
+impl<'a> From<&'a IpAddr> for AddressFamily {
+    fn from(addr: &'a IpAddr) -> Self {
+        AddressFamily::from(*addr)
+    }
+}
+
+impl From<Ipv4Addr> for AddressFamily {
+    fn from(_: Ipv4Addr) -> Self {
+        AddressFamily::Inet
+    }
+}
+
+impl From<Ipv6Addr> for AddressFamily {
+    fn from(_: Ipv6Addr) -> Self {
+        AddressFamily::Inet6
+    }
+}
+
 /// RTNETLINK attribute iterator
 pub struct RtAttrIterator<'a> {
     buf: &'a [u8],
@@ -46,3 +171,19 @@ impl<'a> Iterator for RtAttrIterator<'a> {
 }
 
 
+
+#[test]
+fn address_family_conversions() {
+    for val in 0..256u16 {
+        let val = val as u8;
+        assert_eq!(AddressFamily::new(val).to_u8(), val);
+    }
+    assert_eq!(AddressFamily::from(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))), AddressFamily::Inet);
+    assert_eq!(AddressFamily::from(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), AddressFamily::Inet6);
+    assert!(!AddressFamily::Bridge.is_ip());
+    assert_eq!(AddressFamily::new(2), AddressFamily::Inet);
+    match AddressFamily::new(3) {
+        AddressFamily::Other(other) => assert_eq!(other.to_u8(), 3),
+        family => panic!("unexpected {:?}", family),
+    }
+}
@@ -6,15 +6,16 @@ use std::net::IpAddr;
 
 use byteorder::{ByteOrder, NativeEndian};
 
-use packet::route::addr::Addr;
-use packet::route::{NeighbourDiscoveryPacket, MutableNeighbourDiscoveryPacket, RtAttrIterator,
+use crate::packet::route::addr::Addr;
+use crate::packet::route::{AddressFamily, NeighbourDiscoveryPacket, MutableNeighbourDiscoveryPacket, RtAttrIterator,
                     RtAttrPacket, MutableRtAttrPacket, RtAttrMtuPacket};
-use packet::route::link::Link;
-use packet::netlink::{MutableNetlinkPacket, NetlinkPacket, NetlinkErrorPacket};
-use packet::netlink::NetlinkMsgFlags;
-use packet::netlink::{NetlinkBufIterator, NetlinkReader, NetlinkRequestBuilder};
-use ::socket::{NetlinkSocket, NetlinkProtocol};
-use packet::netlink::NetlinkConnection;
+use crate::packet::route::link::Link;
+use crate::packet::route::route::WithPayload;
+use crate::packet::netlink::{MutableNetlinkPacket, NetlinkPacket, NetlinkErrorPacket};
+use crate::packet::netlink::NetlinkMsgFlags;
+use crate::packet::netlink::{NetlinkBufIterator, NetlinkReader, NetlinkRequestBuilder};
+use crate::socket::{NetlinkSocket, NetlinkProtocol};
+use crate::packet::netlink::NetlinkConnection;
 use pnet::packet::MutablePacket;
 use pnet::packet::Packet;
 use pnet::packet::PacketSize;
@@ -158,6 +159,19 @@ impl<R: Read> Iterator for NeighboursIterator<R> {
     }
 }
 
+/// Parse NDA_DST, None if it does not fit `family`
+///
+/// Bridge FDB entries carry a remote (e.g. of vxlan) of either IP family.
+fn destination_from_bytes(family: AddressFamily, bytes: &[u8]) -> Option<IpAddr> {
+    let family = match (family, bytes.len()) {
+        (AddressFamily::Inet, 4) | (AddressFamily::Inet6, 16) => family,
+        (AddressFamily::Bridge, 4) => AddressFamily::Inet,
+        (AddressFamily::Bridge, 16) => AddressFamily::Inet6,
+        _ => return None,
+    };
+    Some(Addr::ip_from_family_and_bytes(family, bytes))
+}
+
 impl ::std::fmt::Debug for Neighbour {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         let family = self.get_family();
@@ -188,8 +202,9 @@ impl ::std::fmt::Debug for Neighbour {
                         match rta.get_rta_len() {
                             // 4 for the rta header, then 4 or 16.
                             8 | 20 => {
-                                let addr = Addr::ip_from_family_and_bytes(family, rta.payload());
-                                write!(f, " {:?}", addr);
+                                if let Some(addr) = destination_from_bytes(family, rta.payload()) {
+                                    write!(f, " {:?}", addr);
+                                }
                             }
                             l => {
                                 write!(f, "unknown address length {:?}", l);
@@ -213,89 +228,61 @@ pub trait Neighbours
     fn iter_neighbours(&mut self,
                        link: Option<&Link>)
                        -> io::Result<Box<NeighboursIterator<&mut Self>>>;
-    // Not implemented yet.
-    // delete neighbour
-    // fn delete_neighbour(&mut self, neighbour: Neighbour) -> io::Result<()>;
-    // create neighbour
-    // fn create_neighbour(&mut self, name: &str) -> io::Result<()>;
-    //
+    /// add or replace neighbour
+    fn add_neighbour(&mut self, neighbour: &NeighbourInfo) -> io::Result<()>;
+    /// delete neighbour
+    fn delete_neighbour(&mut self, neighbour: &NeighbourInfo) -> io::Result<()>;
 }
 
 impl Neighbours for NetlinkConnection {
     fn iter_neighbours(&mut self,
                        link: Option<&Link>)
                        -> io::Result<Box<NeighboursIterator<&mut Self>>> {
-        // NB: This should be a IfInfoPacket because - well see rtnetlink.c in Linux - but they pun
-        // successfully.
-        //
-        let req = NetlinkRequestBuilder::new(RTM_GETNEIGH, NetlinkMsgFlags::NLM_F_DUMP)
-            .append(match link {
-                    Some(link) => {
-                        NeighbourDiscoveryPacketBuilder::new().set_ifindex(link.get_index())
-                    }
-                    _ => NeighbourDiscoveryPacketBuilder::new(),
-                }
-                .build())
-            .build();
-        try!(self.write(req.packet()));
+        let req = Neighbour::dump_request(None, link.map(|link| link.get_index()));
+        self.write(req.packet())?;
         let reader = NetlinkReader::new(self);
         Ok(Box::new(NeighboursIterator { iter: reader.into_iter() }))
     }
-    // fn get_neighbour_by_index(&mut self, index: u32) -> io::Result<Option<Neighbour>> {
-    // let mut req = {
-    // let mut buf = vec![0; MutableNeighbourDiscoveryPacket::minimum_packet_size()];
-    // NetlinkRequestBuilder::new(RTM_GETNEIGH, NLM_F_ACK)
-    // .append(NeighbourDiscoveryPacketBuilder::new()
-    // .set_ifindex(index)
-    // .build()
-    // .get_packet())
-    // .build()
-    // };
-    // try!(self.write(req.get_packet().packet()));
-    // let reader = NetlinkReader::new(self);
-    // let li = NeighboursIterator { iter: reader.into_iter() };
-    // Ok(li.last())
-    // }
-    //
-    // #[cfg(test)]
-    // fn new_dummy_neighbour(&mut self, name: &str) -> io::Result<()> {
-    // let mut neigh = {
-    // let mut buf = vec![0; 32];
-    // let name_len = name.as_bytes().len();
-    // let mut buf_name = vec![0; RtAttrPacket::minimum_packet_size() + name_len + 1];
-    // NeighbourDiscoveryPacketBuilder::new()
-    // .build()
-    // };
-    // let req = NetlinkRequestBuilder::new(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL | NLM_F_ACK)
-    // .append(neigh.get_packet())
-    // .build();
-    // try!(self.write(req.get_packet().packet()));
-    // let reader = NetlinkReader::new(self);
-    // reader.read_to_end()
-    // }
-    //
-    // fn delete_neighbour(&mut self, neighbour: Neighbour) -> io::Result<()> {
-    // let index = neighbour.get_ifindex();
-    // let mut req = {
-    // let mut buf = vec![0; MutableNeighbourDiscoveryPacket::minimum_packet_size()];
-    // NetlinkRequestBuilder::new(RTM_DELNEIGH, NLM_F_ACK)
-    // .append({
-    // let mut neighbour = MutableNeighbourDiscoveryPacket::new(&mut buf).unwrap();
-    // neighbour.set_family(0 /* AF_UNSPEC */);
-    // neighbour.set_ifindex(index);
-    // neighbour
-    // })
-    // .build()
-    // };
-    // try!(self.write(req.get_packet().packet()));
-    // let reader = NetlinkReader::new(self);
-    // reader.read_to_end()
-    // }
-    //
+
+    fn add_neighbour(&mut self, neighbour: &NeighbourInfo) -> io::Result<()> {
+        let req = neighbour.add_request();
+        self.write(req.packet())?;
+        NetlinkReader::new(self).read_to_end()
+    }
+
+    fn delete_neighbour(&mut self, neighbour: &NeighbourInfo) -> io::Result<()> {
+        let req = neighbour.delete_request();
+        self.write(req.packet())?;
+        NetlinkReader::new(self).read_to_end()
+    }
+}
+
+/// Requests shared by the `Neighbours` trait and async `Handle`
+impl Neighbour {
+    /// Wrap a RTM_NEWNEIGH or RTM_DELNEIGH message
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Option<Neighbour> {
+        match packet.get_kind() {
+            RTM_NEWNEIGH | RTM_DELNEIGH => Some(Neighbour { packet: packet }),
+            _ => None,
+        }
+    }
+
+    /// Request dumping neighbours of `family` on link `ifindex`, None for all
+    pub fn dump_request(family: Option<AddressFamily>, ifindex: Option<u32>) -> NetlinkPacket<'static> {
+        let builder = NeighbourDiscoveryPacketBuilder::new()
+            .set_family(family.unwrap_or(AddressFamily::Unspec));
+        let builder = match ifindex {
+            Some(index) => builder.set_ifindex(index),
+            None => builder,
+        };
+        NetlinkRequestBuilder::new(RTM_GETNEIGH, NetlinkMsgFlags::NLM_F_DUMP)
+            .append(builder.build())
+            .build()
+    }
 }
 
 impl Neighbour {
-    pub fn get_family(&self) -> u8 {
+    pub fn get_family(&self) -> AddressFamily {
         self.with_neighbour(|neigh| neigh.get_family())
     }
 
@@ -320,10 +307,7 @@ impl Neighbour {
         let rta_lookup = self.with_rta(NeighbourAttributes::DST, |rta| {
             match rta.get_rta_len() {
                 // 4 for the rta header, then 4 or 16.
-                8 | 20 => {
-                    let addr = Addr::ip_from_family_and_bytes(family, rta.payload());
-                    Some(addr)
-                }
+                8 | 20 => destination_from_bytes(family, rta.payload()),
                 l => {
                     // Perhaps this should return Result<> ?
                     println!("unknown address length {:?}", l);
@@ -413,6 +397,96 @@ impl Neighbour {
     }
 }
 
+/// Owned neighbour description
+///
+/// Parsed once from a `Neighbour`; can be cloned, compared and hashed.
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub struct NeighbourInfo {
+    pub family: AddressFamily,
+    pub ifindex: u32,
+    pub state: NeighbourState,
+    pub flags: NeighbourFlags,
+    pub type_: u8,
+    pub destination: Option<IpAddr>,
+    pub lladdr: Option<MacAddr>,
+    pub vlan: Option<u16>,
+    pub master: Option<u32>,
+}
+
+impl<'a> From<&'a Neighbour> for NeighbourInfo {
+    fn from(neigh: &'a Neighbour) -> Self {
+        neigh.with_neighbour(|nd| {
+            let family = nd.get_family();
+            let mut info = NeighbourInfo {
+                family: family,
+                ifindex: nd.get_ifindex(),
+                state: nd.get_state(),
+                flags: nd.get_flags(),
+                type_: nd.get_type_(),
+                destination: None,
+                lladdr: None,
+                vlan: None,
+                master: None,
+            };
+            for rta in RtAttrIterator::new(nd.payload()) {
+                let payload = rta.payload();
+                match NeighbourAttributes::from(rta.get_rta_type()) {
+                    NeighbourAttributes::DST => info.destination = destination_from_bytes(family, payload),
+                    NeighbourAttributes::LLADDR if payload.len() == 6 => {
+                        info.lladdr = Some(MacAddr::new(payload[0], payload[1], payload[2],
+                                                        payload[3], payload[4], payload[5]));
+                    }
+                    NeighbourAttributes::VLAN if payload.len() >= 2 => info.vlan = Some(NativeEndian::read_u16(payload)),
+                    NeighbourAttributes::MASTER if payload.len() >= 4 => info.master = Some(NativeEndian::read_u32(payload)),
+                    _ => {}
+                }
+            }
+            info
+        })
+    }
+}
+
+impl From<Neighbour> for NeighbourInfo {
+    fn from(neigh: Neighbour) -> Self {
+        NeighbourInfo::from(&neigh)
+    }
+}
+
+impl NeighbourInfo {
+    /// Build a request of `kind` (e.g. RTM_NEWNEIGH or RTM_DELNEIGH) for this entry
+    pub fn to_request(&self, kind: u16, flags: NetlinkMsgFlags) -> NetlinkPacket<'static> {
+        let mut nd = NeighbourDiscoveryPacketBuilder::new()
+            .set_family(self.family)
+            .set_ifindex(self.ifindex)
+            .set_state(self.state)
+            .set_flags(self.flags)
+            .set_type(self.type_);
+        if let Some(dst) = self.destination {
+            nd = nd.append(RtAttrPacket::create_with_payload(NeighbourAttributes::DST as u16, dst));
+        }
+        if let Some(lladdr) = self.lladdr {
+            nd = nd.append(RtAttrPacket::create_with_payload(NeighbourAttributes::LLADDR as u16, lladdr));
+        }
+        if let Some(vlan) = self.vlan {
+            nd = nd.append(RtAttrPacket::create_with_payload(NeighbourAttributes::VLAN as u16, vlan));
+        }
+        if let Some(master) = self.master {
+            nd = nd.append(RtAttrPacket::create_with_payload(NeighbourAttributes::MASTER as u16, master));
+        }
+        NetlinkRequestBuilder::new(kind, flags).append(nd.build()).build()
+    }
+
+    /// Request adding this neighbour, replacing an existing entry
+    pub fn add_request(&self) -> NetlinkPacket<'static> {
+        self.to_request(RTM_NEWNEIGH, NetlinkMsgFlags::NLM_F_CREATE | NetlinkMsgFlags::NLM_F_REPLACE | NetlinkMsgFlags::NLM_F_ACK)
+    }
+
+    /// Request deleting this neighbour
+    pub fn delete_request(&self) -> NetlinkPacket<'static> {
+        self.to_request(RTM_DELNEIGH, NetlinkMsgFlags::NLM_F_ACK)
+    }
+}
+
 struct NeighbourDiscoveryPacketBuilder {
     data: Vec<u8>,
 }
@@ -424,7 +498,7 @@ impl NeighbourDiscoveryPacketBuilder {
         NeighbourDiscoveryPacketBuilder { data: data }
     }
 
-    pub fn set_family(mut self, family: u8) -> Self {
+    pub fn set_family(mut self, family: AddressFamily) -> Self {
         {
             let mut pkt = MutableNeighbourDiscoveryPacket::new(&mut self.data[..]).unwrap();
             pkt.set_family(family);
@@ -464,6 +538,17 @@ impl NeighbourDiscoveryPacketBuilder {
         self
     }
 
+    pub fn append(mut self, rta: RtAttrPacket) -> Self {
+        let len = rta.get_rta_len() as usize;
+        let aligned_len = crate::util::align(len);
+        self.data.extend_from_slice(&rta.packet()[0..len]);
+        // add padding for alignment
+        for _ in len..aligned_len {
+            self.data.push(0);
+        }
+        self
+    }
+
     pub fn build(self) -> NeighbourDiscoveryPacket<'static> {
         NeighbourDiscoveryPacket::owned(self.data).unwrap()
     }
@@ -471,10 +556,39 @@ impl NeighbourDiscoveryPacketBuilder {
 
 
 mod tests {
+    #[test]
+    fn fdb_destination() {
+        use crate::packet::route::AddressFamily;
+        use crate::packet::route::neighbour::{Neighbour, NeighbourInfo, NeighbourFlags, NeighbourState, RTM_NEWNEIGH};
+        use crate::packet::netlink::NetlinkMsgFlags;
+        use pnet::util::MacAddr;
+
+        // vxlan FDB entry: bridge family, IPv4 remote
+        let fdb = NeighbourInfo {
+            family: AddressFamily::Bridge,
+            ifindex: 5,
+            state: NeighbourState::PERMANENT,
+            flags: NeighbourFlags::SELF,
+            type_: 0,
+            destination: Some("192.0.2.1".parse().unwrap()),
+            lladdr: Some(MacAddr::new(0x02, 0, 0, 0, 0, 1)),
+            vlan: None,
+            master: None,
+        };
+        let neigh = Neighbour::from_packet(fdb.to_request(RTM_NEWNEIGH, NetlinkMsgFlags::empty())).unwrap();
+        assert_eq!(neigh.get_destination(), fdb.destination);
+        assert_eq!(NeighbourInfo::from(&neigh), fdb);
+
+        let mut bad = fdb.clone();
+        bad.family = AddressFamily::Inet6;
+        let neigh = Neighbour::from_packet(bad.to_request(RTM_NEWNEIGH, NetlinkMsgFlags::empty())).unwrap();
+        assert_eq!(NeighbourInfo::from(&neigh).destination, None);
+    }
+
     #[test]
     fn dump_neighbours() {
-        use ::packet::netlink::NetlinkConnection;
-        use ::packet::route::neighbour::{Neighbour, Neighbours};
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::neighbour::{Neighbour, Neighbours};
         let mut conn = NetlinkConnection::new();
         for neighbour in conn.iter_neighbours(None).unwrap() {
             Neighbour::dump_neighbour(neighbour.packet);
@@ -483,9 +597,9 @@ mod tests {
 
     #[test]
     fn dump_lo_neighbours() {
-        use ::packet::netlink::NetlinkConnection;
-        use ::packet::route::link::{Link, Links};
-        use ::packet::route::neighbour::{Neighbour, Neighbours};
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::{Link, Links};
+        use crate::packet::route::neighbour::{Neighbour, Neighbours};
 
         let mut conn = NetlinkConnection::new();
         let lo0 = conn.get_link_by_name("lo").unwrap().unwrap();
@@ -498,8 +612,8 @@ mod tests {
     //    #[test]
     //    // root permissions required
     //    fn create_and_delete_neighbour() {
-    //        use ::packet::netlink::NetlinkConnection;
-    //        use ::packet::route::neighbour::{Neighbour, Neighbours};
+    //        use crate::packet::netlink::NetlinkConnection;
+    //        use crate::packet::route::neighbour::{Neighbour, Neighbours};
     //
     //        let mut conn = NetlinkConnection::new();
     //        conn.new_dummy_neighbour("test1488").unwrap();
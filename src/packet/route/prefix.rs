@@ -0,0 +1,192 @@
+//! IP prefix (address and prefix length)
+//!
+//! The kernel keeps host bits of interface addresses (10.0.0.5/24 is a valid
+//! address), but compares routes and rules by network, so requests built from
+//! a prefix for those use `network()`.
+use crate::packet::route::AddressFamily;
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr,Ipv4Addr,Ipv6Addr};
+use std::str::FromStr;
+
+/// IPv4 or IPv6 prefix with a length valid for its family
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash,PartialOrd,Ord)]
+pub struct IpPrefix {
+    addr: IpAddr,
+    len: u8,
+}
+
+impl IpPrefix {
+    /// Create prefix, fails if `len` is too long for the address family
+    pub fn new(addr: IpAddr, len: u8) -> io::Result<Self> {
+        if len > Self::max_len_for(&addr) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("prefix length {} is invalid for {}", len, addr)));
+        }
+        Ok(IpPrefix { addr: addr, len: len })
+    }
+
+    /// Create host prefix (/32 or /128)
+    pub fn host(addr: IpAddr) -> Self {
+        IpPrefix { addr: addr, len: Self::max_len_for(&addr) }
+    }
+
+    /// Create default route prefix (0.0.0.0/0 or ::/0)
+    pub fn default_for(family: AddressFamily) -> Option<Self> {
+        match family {
+            AddressFamily::Inet => Some(IpPrefix { addr: IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), len: 0 }),
+            AddressFamily::Inet6 => Some(IpPrefix { addr: IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)), len: 0 }),
+            _ => None,
+        }
+    }
+
+    /// Get address as given, host bits included
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Get prefix length
+    pub fn len(&self) -> u8 {
+        self.len
+    }
+
+    /// Returns true for a /0 prefix, same as `is_default`
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get address family
+    pub fn family(&self) -> AddressFamily {
+        AddressFamily::from(self.addr)
+    }
+
+    /// Get maximum prefix length for the family (32 or 128)
+    pub fn max_len(&self) -> u8 {
+        Self::max_len_for(&self.addr)
+    }
+
+    /// Returns true if this is a host prefix
+    pub fn is_host(&self) -> bool {
+        self.len == self.max_len()
+    }
+
+    /// Returns true if this is a default route prefix
+    pub fn is_default(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get network address (host bits cleared)
+    pub fn network(&self) -> IpAddr {
+        match self.addr {
+            IpAddr::V4(ip) => {
+                let bits = u32::from(ip) & Self::mask_v4(self.len);
+                IpAddr::V4(Ipv4Addr::from(bits))
+            },
+            IpAddr::V6(ip) => {
+                let mut octets = ip.octets();
+                Self::mask_octets(&mut octets, self.len);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            },
+        }
+    }
+
+    /// Get prefix with host bits cleared
+    pub fn trunc(&self) -> Self {
+        IpPrefix { addr: self.network(), len: self.len }
+    }
+
+    /// Returns true if `addr` belongs to this prefix
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => {
+                IpPrefix { addr: addr, len: self.len }.network() == self.network()
+            },
+            _ => false,
+        }
+    }
+
+    fn max_len_for(addr: &IpAddr) -> u8 {
+        match *addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        }
+    }
+
+    fn mask_v4(len: u8) -> u32 {
+        if len == 0 { 0 } else { !0u32 << (32 - len as u32) }
+    }
+
+    fn mask_octets(octets: &mut [u8], len: u8) {
+        let len = len as usize;
+        for (i, octet) in octets.iter_mut().enumerate() {
+            if len <= i * 8 {
+                *octet = 0;
+            } else if len < (i + 1) * 8 {
+                *octet &= !(0xffu8 >> (len - i * 8));
+            }
+        }
+    }
+}
+
+impl From<IpAddr> for IpPrefix {
+    fn from(addr: IpAddr) -> Self {
+        IpPrefix::host(addr)
+    }
+}
+
+impl fmt::Display for IpPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.len)
+    }
+}
+
+impl FromStr for IpPrefix {
+    type Err = io::Error;
+
+    /// Parses "addr/len", or a bare address as a host prefix
+    fn from_str(s: &str) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid prefix {:?}", s));
+        let mut parts = s.splitn(2, '/');
+        let addr = parts.next().unwrap().parse::<IpAddr>().map_err(|_| invalid())?;
+        match parts.next() {
+            Some(len) => {
+                let len = len.parse::<u8>().map_err(|_| invalid())?;
+                IpPrefix::new(addr, len)
+            },
+            None => Ok(IpPrefix::host(addr)),
+        }
+    }
+}
+
+mod tests {
+    #[test]
+    fn parse_and_validate() {
+        use crate::packet::route::prefix::IpPrefix;
+        use std::net::{IpAddr,Ipv4Addr};
+
+        let prefix = "10.1.2.3/24".parse::<IpPrefix>().unwrap();
+        assert_eq!(prefix.addr(), IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)));
+        assert_eq!(prefix.len(), 24);
+        assert_eq!(prefix.network(), IpAddr::V4(Ipv4Addr::new(10, 1, 2, 0)));
+        assert_eq!(prefix.to_string(), "10.1.2.3/24");
+        assert!("10.1.2.3/33".parse::<IpPrefix>().is_err());
+        assert!("10.1.2.3/".parse::<IpPrefix>().is_err());
+        assert!("fe80::1/129".parse::<IpPrefix>().is_err());
+        assert_eq!("fe80::1".parse::<IpPrefix>().unwrap().len(), 128);
+    }
+
+    #[test]
+    fn host_bits() {
+        use crate::packet::route::prefix::IpPrefix;
+
+        let prefix = "2001:db8:abcd:f234::1/50".parse::<IpPrefix>().unwrap();
+        assert_eq!(prefix.trunc().to_string(), "2001:db8:abcd:c000::/50");
+        assert!(prefix.contains("2001:db8:abcd:d000::7".parse().unwrap()));
+        assert!(!prefix.contains("2001:db8:abcd:4000::7".parse().unwrap()));
+        assert!(!prefix.contains("10.0.0.1".parse().unwrap()));
+        let default = "0.0.0.0/0".parse::<IpPrefix>().unwrap();
+        assert!(default.is_default() && default.is_empty());
+        assert!(default.contains("192.0.2.1".parse().unwrap()));
+    }
+}
@@ -0,0 +1,199 @@
+//! Cached interface index, name and alias lookups
+//!
+//! ```no_run
+//! use pnetlink::packet::route::resolver::LinkResolver;
+//!
+//! let resolver = LinkResolver::spawn().unwrap();
+//! let index = resolver.index("lo").unwrap();
+//! assert_eq!(resolver.name(index), Some("lo".to_owned()));
+//! ```
+use crate::packet::netlink::NetlinkConnection;
+use crate::packet::route::event::{EventIterator,EventSet,RtnlEvent};
+use crate::packet::route::link::{Link,Links};
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc,RwLock};
+use std::thread;
+
+#[derive(Debug,Default)]
+struct Names {
+    by_index: HashMap<u32, (String, Option<String>)>,
+    by_name: HashMap<String, u32>,
+    by_alias: HashMap<String, u32>,
+}
+
+impl Names {
+    fn insert(&mut self, index: u32, name: String, alias: Option<String>) {
+        self.remove(index);
+        // a name or alias now on another index was left over by a lost
+        // RTM_DELLINK or a rename
+        if let Some(old) = self.by_name.insert(name.clone(), index) {
+            self.remove(old);
+        }
+        if let Some(ref alias) = alias {
+            self.by_alias.insert(alias.clone(), index);
+        }
+        self.by_index.insert(index, (name, alias));
+    }
+
+    fn remove(&mut self, index: u32) {
+        if let Some((name, alias)) = self.by_index.remove(&index) {
+            if self.by_name.get(&name) == Some(&index) {
+                self.by_name.remove(&name);
+            }
+            if let Some(alias) = alias {
+                if self.by_alias.get(&alias) == Some(&index) {
+                    self.by_alias.remove(&alias);
+                }
+            }
+        }
+    }
+}
+
+/// Thread-safe cache of ifindex to name and alias mappings
+///
+/// Clones share the same cache. Lookups never talk to the kernel; keep
+/// the cache current with `apply`, or let `spawn` do it from a thread
+/// listening to link notifications.
+#[derive(Debug,Clone,Default)]
+pub struct LinkResolver {
+    names: Arc<RwLock<Names>>,
+}
+
+impl LinkResolver {
+    /// Create empty resolver
+    pub fn new() -> Self {
+        LinkResolver::default()
+    }
+
+    /// Create resolver from the links currently known to the kernel
+    pub fn load(conn: &mut NetlinkConnection) -> io::Result<Self> {
+        let resolver = LinkResolver::new();
+        resolver.reload(conn)?;
+        Ok(resolver)
+    }
+
+    /// Create resolver updated from link notifications by a background
+    /// thread, which exits once all clones are dropped and the next
+    /// notification arrives
+    pub fn spawn() -> io::Result<Self> {
+        // subscribe before the dump, so no change falls in between
+        let mut events = EventIterator::new(EventSet::LINK)?;
+        let mut conn = NetlinkConnection::new();
+        let resolver = LinkResolver::load(&mut conn)?;
+        let names = Arc::downgrade(&resolver.names);
+        thread::Builder::new().name("pnetlink-resolver".to_owned()).spawn(move || {
+            for event in &mut events {
+                let resolver = match names.upgrade() {
+                    Some(names) => LinkResolver { names: names },
+                    None => break,
+                };
+                match event {
+                    Ok(event) => resolver.apply(&event),
+                    Err(ref e) if e.raw_os_error() == Some(::libc::ENOBUFS) => {
+                        if resolver.reload(&mut conn).is_err() {
+                            break;
+                        }
+                    },
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                    Err(_) => break,
+                }
+            }
+        })?;
+        Ok(resolver)
+    }
+
+    /// Replace cache contents with the links dumped on `conn`
+    pub fn reload(&self, conn: &mut NetlinkConnection) -> io::Result<()> {
+        let mut fresh = Names::default();
+        for link in conn.iter_links()? {
+            if let Some(name) = link.get_name() {
+                fresh.insert(link.get_index(), name, link.get_alias());
+            }
+        }
+        *self.names.write().unwrap() = fresh;
+        Ok(())
+    }
+
+    /// Update cache from a notification, other than link ones are ignored
+    pub fn apply(&self, event: &RtnlEvent) {
+        match *event {
+            RtnlEvent::LinkAdded(ref link) => self.update(link),
+            RtnlEvent::LinkRemoved(ref link) => self.names.write().unwrap().remove(link.get_index()),
+            _ => {},
+        }
+    }
+
+    /// Add or update `link`
+    pub fn update(&self, link: &Link) {
+        let mut names = self.names.write().unwrap();
+        match link.get_name() {
+            Some(name) => names.insert(link.get_index(), name, link.get_alias()),
+            None => names.remove(link.get_index()),
+        }
+    }
+
+    /// Get link name by index
+    pub fn name(&self, index: u32) -> Option<String> {
+        self.names.read().unwrap().by_index.get(&index).map(|&(ref name, _)| name.clone())
+    }
+
+    /// Get link alias by index
+    pub fn alias(&self, index: u32) -> Option<String> {
+        self.names.read().unwrap().by_index.get(&index).and_then(|&(_, ref alias)| alias.clone())
+    }
+
+    /// Get link index by name
+    pub fn index(&self, name: &str) -> Option<u32> {
+        self.names.read().unwrap().by_name.get(name).cloned()
+    }
+
+    /// Get link index by alias
+    pub fn index_by_alias(&self, alias: &str) -> Option<u32> {
+        self.names.read().unwrap().by_alias.get(alias).cloned()
+    }
+}
+
+mod tests {
+    #[test]
+    fn rename_and_reuse() {
+        use crate::packet::netlink::{NetlinkConnection,NetlinkMsgFlags};
+        use crate::packet::route::event::RtnlEvent;
+        use crate::packet::route::link::{Link,LinkInfo,Links,RTM_NEWLINK,RTM_DELLINK};
+        use crate::packet::route::resolver::LinkResolver;
+
+        let mut conn = NetlinkConnection::new();
+        let mut info = LinkInfo::from(conn.get_link_by_name("lo").unwrap().unwrap());
+        let link = |info: &LinkInfo, kind| Link::from_packet(info.to_request(kind, NetlinkMsgFlags::empty())).unwrap();
+        let resolver = LinkResolver::new();
+        info.index = 42;
+        info.name = Some("pnl-res0".to_owned());
+        info.alias = Some("uplink".to_owned());
+        resolver.apply(&RtnlEvent::LinkAdded(link(&info, RTM_NEWLINK)));
+        assert_eq!(resolver.index_by_alias("uplink"), Some(42));
+        // renamed
+        info.name = Some("pnl-res1".to_owned());
+        resolver.clone().apply(&RtnlEvent::LinkAdded(link(&info, RTM_NEWLINK)));
+        assert_eq!(resolver.index("pnl-res0"), None);
+        assert_eq!(resolver.name(42), Some("pnl-res1".to_owned()));
+        // deletion lost, name reused by another index
+        info.index = 43;
+        info.alias = None;
+        resolver.apply(&RtnlEvent::LinkAdded(link(&info, RTM_NEWLINK)));
+        assert_eq!(resolver.index("pnl-res1"), Some(43));
+        assert_eq!(resolver.name(42), None);
+        assert_eq!(resolver.index_by_alias("uplink"), None);
+        resolver.apply(&RtnlEvent::LinkRemoved(link(&info, RTM_DELLINK)));
+        assert_eq!(resolver.index("pnl-res1"), None);
+    }
+
+    #[test]
+    fn spawn_resolver() {
+        use crate::packet::route::resolver::LinkResolver;
+
+        let resolver = LinkResolver::spawn().unwrap();
+        let index = resolver.index("lo").unwrap();
+        assert_eq!(resolver.name(index), Some("lo".to_owned()));
+    }
+}
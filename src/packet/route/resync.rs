@@ -0,0 +1,385 @@
+//! Notification streams surviving socket buffer overflows
+//!
+//! When a listener does not keep up, the kernel drops notifications and
+//! reports `ENOBUFS`. `ResyncIterator` then dumps the subscribed tables
+//! again and emits synthetic added/removed events for whatever changed
+//! in the meantime, so the sequence of events seen by the consumer stays
+//! consistent with the kernel.
+use crate::packet::netlink::{NetlinkConnection,NetlinkMsgFlags};
+use crate::packet::route::AddressFamily;
+use crate::packet::route::addr::{Addr,AddrInfo,Addresses,RTM_DELADDR};
+use crate::packet::route::event::{EventIterator,EventSet,RtnlEvent};
+use crate::packet::route::link::{Link,LinkInfo,Links,RTM_DELLINK};
+use crate::packet::route::neighbour::{Neighbour,NeighbourFlags,NeighbourInfo,Neighbours,RTM_DELNEIGH};
+use crate::packet::route::route::{Route,RouteInfo,RtmType,RTM_DELROUTE};
+use crate::packet::route::rule::{Rule,RuleInfo,RTM_DELRULE};
+
+use std::collections::{HashMap,VecDeque};
+use std::hash::Hash;
+use std::io;
+use std::net::IpAddr;
+use pnet::util::MacAddr;
+
+pub(super) type AddrKey = (u32, AddressFamily, u8, Option<IpAddr>);
+pub(super) type RouteKey = (AddressFamily, u32, u8, Option<IpAddr>, u8, Option<u32>, Option<u32>, RtmType);
+pub(super) type NeighbourKey = (u32, AddressFamily, Option<IpAddr>, Option<MacAddr>, Option<u16>, NeighbourFlags);
+
+/// Contents of the rtnetlink tables, as returned by dumps
+#[derive(Debug,Default)]
+pub struct Dump {
+    pub links: Vec<Link>,
+    pub addrs: Vec<Addr>,
+    pub routes: Vec<Route>,
+    pub neighbours: Vec<Neighbour>,
+    pub rules: Vec<Rule>,
+}
+
+impl Dump {
+    /// Dump tables covered by `events` on `conn`
+    pub fn new(conn: &mut NetlinkConnection, events: EventSet) -> io::Result<Dump> {
+        let mut dump = Dump::default();
+        if events.contains(EventSet::LINK) {
+            dump.links = conn.iter_links()?.collect();
+        }
+        if events.intersects(EventSet::ADDR) {
+            dump.addrs = conn.iter_addrs(None)?.collect();
+        }
+        if events.intersects(EventSet::ROUTE) {
+            dump.routes = Route::iter_routes(conn).collect();
+        }
+        if events.contains(EventSet::NEIGH) {
+            dump.neighbours = conn.iter_neighbours(None)?.collect();
+        }
+        if events.intersects(EventSet::RULE) {
+            dump.rules = Rule::iter_rules(conn).collect();
+        }
+        Ok(dump)
+    }
+
+    /// Turn dumped objects into added events, links first
+    pub fn into_events(self) -> Vec<RtnlEvent> {
+        self.links.into_iter().map(RtnlEvent::LinkAdded)
+            .chain(self.rules.into_iter().map(RtnlEvent::RuleAdded))
+            .chain(self.neighbours.into_iter().map(RtnlEvent::NeighChanged))
+            .chain(self.routes.into_iter().map(RtnlEvent::RouteAdded))
+            .chain(self.addrs.into_iter().map(RtnlEvent::AddrAdded))
+            .collect()
+    }
+}
+
+/// Last known state of the subscribed tables
+///
+/// Objects are compared without counters and lifetimes, which change
+/// all the time without being announced.
+#[derive(Debug)]
+pub struct EventTracker {
+    events: EventSet,
+    links: HashMap<u32, LinkInfo>,
+    addrs: HashMap<AddrKey, AddrInfo>,
+    routes: HashMap<RouteKey, RouteInfo>,
+    neighbours: HashMap<NeighbourKey, NeighbourInfo>,
+    rules: HashMap<RuleInfo, RuleInfo>,
+}
+
+pub(super) fn link_info(link: &Link) -> LinkInfo {
+    let mut info = LinkInfo::from(link);
+    info.stats = None;
+    info
+}
+
+pub(super) fn addr_info(addr: &Addr) -> AddrInfo {
+    let mut info = AddrInfo::from(addr);
+    info.valid_lft = None;
+    info.preferred_lft = None;
+    info
+}
+
+pub(super) fn addr_key(addr: &AddrInfo) -> AddrKey {
+    (addr.index, addr.family, addr.prefix_len, addr.local.or(addr.address))
+}
+
+pub(super) fn route_key(route: &RouteInfo) -> RouteKey {
+    // e.g. fe80::/64 exists once per interface
+    (route.family, route.table, route.dst_len, route.destination, route.tos, route.priority, route.oif, route.type_)
+}
+
+pub(super) fn neighbour_key(neighbour: &NeighbourInfo) -> NeighbourKey {
+    if neighbour.family != AddressFamily::Bridge {
+        return (neighbour.ifindex, neighbour.family, neighbour.destination, None, None, NeighbourFlags::empty());
+    }
+    // FDB entries of a port differ in MAC and VLAN, and exist both in the
+    // bridge (master) and in the device itself (self)
+    let owner = neighbour.flags & (NeighbourFlags::SELF | NeighbourFlags::MASTER);
+    (neighbour.ifindex, neighbour.family, neighbour.destination, neighbour.lladdr, neighbour.vlan, owner)
+}
+
+fn covers(events: EventSet, family: AddressFamily, v4: EventSet, v6: EventSet) -> bool {
+    match family {
+        AddressFamily::Inet => events.contains(v4),
+        AddressFamily::Inet6 => events.contains(v6),
+        _ => events.intersects(v4 | v6),
+    }
+}
+
+/// Replace `table` with `new`, emitting removed and added/changed objects
+fn diff<K: Hash + Eq, V: PartialEq, T>(table: &mut HashMap<K, V>, new: Vec<(K, V, T)>,
+                                       removed: &mut Vec<V>, added: &mut Vec<T>) {
+    let mut old = ::std::mem::replace(table, HashMap::new());
+    for (key, info, obj) in new {
+        match old.remove(&key) {
+            Some(ref prev) if *prev == info => {},
+            _ => added.push(obj),
+        }
+        table.insert(key, info);
+    }
+    removed.extend(old.into_iter().map(|(_, info)| info));
+}
+
+impl EventTracker {
+    /// Track tables covered by `events`, initially empty
+    pub fn new(events: EventSet) -> Self {
+        EventTracker {
+            events: events,
+            links: HashMap::new(),
+            addrs: HashMap::new(),
+            routes: HashMap::new(),
+            neighbours: HashMap::new(),
+            rules: HashMap::new(),
+        }
+    }
+
+    /// Get tracked events
+    pub fn events(&self) -> EventSet {
+        self.events
+    }
+
+    /// Check whether `event` belongs to a tracked table
+    pub fn covers(&self, event: &RtnlEvent) -> bool {
+        match *event {
+            RtnlEvent::LinkAdded(_) | RtnlEvent::LinkRemoved(_) => self.events.contains(EventSet::LINK),
+            RtnlEvent::AddrAdded(ref addr) | RtnlEvent::AddrRemoved(ref addr) =>
+                covers(self.events, addr.get_family(), EventSet::IPV4_ADDR, EventSet::IPV6_ADDR),
+            RtnlEvent::RouteAdded(ref route) | RtnlEvent::RouteRemoved(ref route) =>
+                covers(self.events, route.get_family(), EventSet::IPV4_ROUTE, EventSet::IPV6_ROUTE),
+            RtnlEvent::NeighChanged(_) | RtnlEvent::NeighRemoved(_) => self.events.contains(EventSet::NEIGH),
+            RtnlEvent::RuleAdded(ref rule) | RtnlEvent::RuleRemoved(ref rule) =>
+                covers(self.events, rule.get_family(), EventSet::IPV4_RULE, EventSet::IPV6_RULE),
+        }
+    }
+
+    /// Update state from a notification
+    pub fn apply(&mut self, event: &RtnlEvent) {
+        if !self.covers(event) {
+            return;
+        }
+        match *event {
+            RtnlEvent::LinkAdded(ref link) => { self.links.insert(link.get_index(), link_info(link)); },
+            RtnlEvent::LinkRemoved(ref link) => { self.links.remove(&link.get_index()); },
+            RtnlEvent::AddrAdded(ref addr) => {
+                let info = addr_info(addr);
+                self.addrs.insert(addr_key(&info), info);
+            },
+            RtnlEvent::AddrRemoved(ref addr) => { self.addrs.remove(&addr_key(&addr_info(addr))); },
+            RtnlEvent::RouteAdded(ref route) => {
+                let info = RouteInfo::from(route);
+                self.routes.insert(route_key(&info), info);
+            },
+            RtnlEvent::RouteRemoved(ref route) => { self.routes.remove(&route_key(&RouteInfo::from(route))); },
+            RtnlEvent::NeighChanged(ref neighbour) => {
+                let info = NeighbourInfo::from(neighbour);
+                self.neighbours.insert(neighbour_key(&info), info);
+            },
+            RtnlEvent::NeighRemoved(ref neighbour) => {
+                self.neighbours.remove(&neighbour_key(&NeighbourInfo::from(neighbour)));
+            },
+            RtnlEvent::RuleAdded(ref rule) => {
+                let info = RuleInfo::from(rule);
+                self.rules.insert(info.clone(), info);
+            },
+            RtnlEvent::RuleRemoved(ref rule) => { self.rules.remove(&RuleInfo::from(rule)); },
+        }
+    }
+
+    /// Replace state with `dump`, returning events for the differences
+    ///
+    /// Removals come first, ordered addresses, routes, neighbours, rules,
+    /// links; additions follow in the opposite order.
+    pub fn resync(&mut self, dump: Dump) -> Vec<RtnlEvent> {
+        let events = self.events;
+        let covered = |family, v4, v6| covers(events, family, v4, v6);
+        let flags = NetlinkMsgFlags::empty();
+
+        let (mut links_removed, mut links_added) = (vec![], vec![]);
+        diff(&mut self.links, dump.links.into_iter().map(|link| (link.get_index(), link_info(&link), link)).collect(),
+             &mut links_removed, &mut links_added);
+        let (mut addrs_removed, mut addrs_added) = (vec![], vec![]);
+        diff(&mut self.addrs, dump.addrs.into_iter()
+                .filter(|addr| covered(addr.get_family(), EventSet::IPV4_ADDR, EventSet::IPV6_ADDR))
+                .map(|addr| { let info = addr_info(&addr); (addr_key(&info), info, addr) }).collect(),
+             &mut addrs_removed, &mut addrs_added);
+        let (mut routes_removed, mut routes_added) = (vec![], vec![]);
+        diff(&mut self.routes, dump.routes.into_iter()
+                .filter(|route| covered(route.get_family(), EventSet::IPV4_ROUTE, EventSet::IPV6_ROUTE))
+                .map(|route| { let info = RouteInfo::from(&route); (route_key(&info), info, route) }).collect(),
+             &mut routes_removed, &mut routes_added);
+        let (mut neighbours_removed, mut neighbours_added) = (vec![], vec![]);
+        diff(&mut self.neighbours, dump.neighbours.into_iter()
+                .map(|neighbour| { let info = NeighbourInfo::from(&neighbour); (neighbour_key(&info), info, neighbour) }).collect(),
+             &mut neighbours_removed, &mut neighbours_added);
+        let (mut rules_removed, mut rules_added) = (vec![], vec![]);
+        diff(&mut self.rules, dump.rules.into_iter()
+                .filter(|rule| covered(rule.get_family(), EventSet::IPV4_RULE, EventSet::IPV6_RULE))
+                .map(|rule| { let info = RuleInfo::from(&rule); (info.clone(), info, rule) }).collect(),
+             &mut rules_removed, &mut rules_added);
+
+        let mut out = vec![];
+        out.extend(addrs_removed.into_iter().filter_map(|info| Addr::from_packet(info.to_request(RTM_DELADDR, flags)))
+                   .map(RtnlEvent::AddrRemoved));
+        out.extend(routes_removed.into_iter().filter_map(|info| Route::from_packet(info.to_request(RTM_DELROUTE, flags)))
+                   .map(RtnlEvent::RouteRemoved));
+        out.extend(neighbours_removed.into_iter().filter_map(|info| Neighbour::from_packet(info.to_request(RTM_DELNEIGH, flags)))
+                   .map(RtnlEvent::NeighRemoved));
+        out.extend(rules_removed.into_iter().filter_map(|info| Rule::from_packet(info.to_request(RTM_DELRULE, flags)))
+                   .map(RtnlEvent::RuleRemoved));
+        out.extend(links_removed.into_iter().filter_map(|info| Link::from_packet(info.to_request(RTM_DELLINK, flags)))
+                   .map(RtnlEvent::LinkRemoved));
+        out.extend(links_added.into_iter().map(RtnlEvent::LinkAdded));
+        out.extend(rules_added.into_iter().map(RtnlEvent::RuleAdded));
+        out.extend(neighbours_added.into_iter().map(RtnlEvent::NeighChanged));
+        out.extend(routes_added.into_iter().map(RtnlEvent::RouteAdded));
+        out.extend(addrs_added.into_iter().map(RtnlEvent::AddrAdded));
+        out
+    }
+}
+
+/// Blocking event iterator resynchronizing after overflows
+///
+/// It starts with added events for the current contents of the subscribed
+/// tables, then follows notifications. On `ENOBUFS` the tables are dumped
+/// again on a separate connection and the differences are emitted instead
+/// of the error.
+pub struct ResyncIterator {
+    events: EventIterator,
+    dumps: NetlinkConnection,
+    tracker: EventTracker,
+    queue: VecDeque<RtnlEvent>,
+}
+
+impl ResyncIterator {
+    /// Subscribe to `events` and dump the current state
+    pub fn new(events: EventSet) -> io::Result<Self> {
+        // subscribe before the dump, so no change falls in between
+        let iter = EventIterator::new(events)?;
+        let mut resync = ResyncIterator {
+            events: iter,
+            dumps: NetlinkConnection::new(),
+            tracker: EventTracker::new(events),
+            queue: VecDeque::new(),
+        };
+        resync.resync()?;
+        Ok(resync)
+    }
+
+    /// Get state tracker
+    pub fn tracker(&self) -> &EventTracker {
+        &self.tracker
+    }
+
+    fn resync(&mut self) -> io::Result<()> {
+        let dump = Dump::new(&mut self.dumps, self.tracker.events())?;
+        self.queue.extend(self.tracker.resync(dump));
+        Ok(())
+    }
+}
+
+impl Iterator for ResyncIterator {
+    type Item = io::Result<RtnlEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.queue.pop_front() {
+                return Some(Ok(event));
+            }
+            match self.events.next() {
+                Some(Ok(event)) => {
+                    self.tracker.apply(&event);
+                    return Some(Ok(event));
+                },
+                Some(Err(ref e)) if e.raw_os_error() == Some(::libc::ENOBUFS) => {
+                    if let Err(e) = self.resync() {
+                        return Some(Err(e));
+                    }
+                },
+                other => return other,
+            }
+        }
+    }
+}
+
+mod tests {
+    #[test]
+    fn resync_diff() {
+        use crate::packet::netlink::NetlinkMsgFlags;
+        use crate::packet::route::event::{EventSet,RtnlEvent};
+        use crate::packet::route::resync::{Dump,EventTracker};
+        use crate::packet::route::route::{Route,RouteInfo,RTM_NEWROUTE};
+
+        let route = |prefix: &str| {
+            let info = RouteInfo::new(prefix.parse().unwrap());
+            Route::from_packet(info.to_request(RTM_NEWROUTE, NetlinkMsgFlags::empty())).unwrap()
+        };
+        let mut tracker = EventTracker::new(EventSet::IPV4_ROUTE);
+        let events = tracker.resync(Dump { routes: vec![route("10.0.0.0/8"), route("fd00::/8")], ..Dump::default() });
+        assert_eq!(events.len(), 1);
+        tracker.apply(&RtnlEvent::RouteAdded(route("192.168.0.0/16")));
+        let events = tracker.resync(Dump { routes: vec![route("192.168.0.0/16"), route("172.16.0.0/12")], ..Dump::default() });
+        match &events[..] {
+            [RtnlEvent::RouteRemoved(removed), RtnlEvent::RouteAdded(added)] => {
+                assert_eq!(removed.get_destination_prefix(), Some("10.0.0.0/8".parse().unwrap()));
+                assert_eq!(added.get_destination_prefix(), Some("172.16.0.0/12".parse().unwrap()));
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn resync_keys() {
+        use crate::packet::netlink::NetlinkMsgFlags;
+        use crate::packet::route::AddressFamily;
+        use crate::packet::route::event::{EventSet,RtnlEvent};
+        use crate::packet::route::neighbour::{Neighbour,NeighbourInfo,NeighbourFlags,NeighbourState,RTM_NEWNEIGH};
+        use crate::packet::route::resync::{Dump,EventTracker};
+        use crate::packet::route::route::{Route,RouteInfo,RTM_NEWROUTE};
+        use pnet::util::MacAddr;
+
+        // fe80::/64 on two interfaces
+        let route = |oif| {
+            let mut info = RouteInfo::new("fe80::/64".parse().unwrap());
+            info.oif = Some(oif);
+            info.priority = Some(256);
+            Route::from_packet(info.to_request(RTM_NEWROUTE, NetlinkMsgFlags::empty())).unwrap()
+        };
+        // two FDB entries on one port
+        let fdb = |mac| {
+            let info = NeighbourInfo {
+                family: AddressFamily::Bridge,
+                ifindex: 3,
+                state: NeighbourState::PERMANENT,
+                flags: NeighbourFlags::MASTER,
+                type_: 0,
+                destination: None,
+                lladdr: Some(MacAddr::new(0x02, 0, 0, 0, 0, mac)),
+                vlan: Some(1),
+                master: None,
+            };
+            Neighbour::from_packet(info.to_request(RTM_NEWNEIGH, NetlinkMsgFlags::empty())).unwrap()
+        };
+        let mut tracker = EventTracker::new(EventSet::ROUTE | EventSet::NEIGH);
+        let events = tracker.resync(Dump { routes: vec![route(1), route(2)], neighbours: vec![fdb(1), fdb(2)], ..Dump::default() });
+        assert_eq!(events.len(), 4);
+        tracker.apply(&RtnlEvent::RouteRemoved(route(1)));
+        tracker.apply(&RtnlEvent::NeighRemoved(fdb(1)));
+        let events = tracker.resync(Dump { routes: vec![route(2)], neighbours: vec![fdb(2)], ..Dump::default() });
+        assert!(events.is_empty());
+    }
+}
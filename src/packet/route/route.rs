@@ -1,17 +1,20 @@
 //! Route operations
-use packet::route::{RouteCacheInfoPacket, RtMsgPacket, MutableIfInfoPacket, IfInfoPacket,
+use crate::packet::route::{AddressFamily, RouteCacheInfoPacket, RtMsgPacket, MutableRtMsgPacket, MutableIfInfoPacket, IfInfoPacket,
                     RtAttrIterator, RtAttrPacket, MutableRtAttrPacket};
-use packet::netlink::NetlinkPacket;
-use packet::netlink::NetlinkMsgFlags;
-use packet::netlink::{NetlinkBufIterator, NetlinkReader, NetlinkRequestBuilder};
-use packet::netlink::NetlinkConnection;
+use crate::packet::route::addr::{Addr,Scope};
+use crate::packet::route::prefix::IpPrefix;
+use crate::packet::netlink::NetlinkPacket;
+use crate::packet::netlink::NetlinkMsgFlags;
+use crate::packet::netlink::{NetlinkBufIterator, NetlinkReader, NetlinkRequestBuilder};
+use crate::packet::netlink::NetlinkConnection;
 use pnet::packet::MutablePacket;
 use pnet::packet::Packet;
 use pnet::packet::PacketSize;
-use util;
+use pnet::util::MacAddr;
+use crate::util;
 
 use std::net::{Ipv4Addr, IpAddr};
-use std::io::{Read, Cursor};
+use std::io::{self, Read, Write, Cursor};
 use byteorder::{LittleEndian, BigEndian, ReadBytesExt, NativeEndian, ByteOrder};
 
 pub const RTM_NEWROUTE: u16 = 24;
@@ -26,8 +29,10 @@ pub const RT_TABLE_DEFAULT: u32 = 253;
 pub const RT_TABLE_MAIN: u32 = 254;
 pub const RT_TABLE_LOCAL: u32 = 255;
 
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
 #[repr(u8)]
-enum RtmType {
+pub enum RtmType {
+    UNSPEC,
     UNICAST, // Gateway or direct route
     LOCAL, // Accept locally
     BROADCAST, /* Accept locally as broadcast,
@@ -43,6 +48,16 @@ enum RtmType {
     XRESOLVE, // Use external resolver
 }
 
+impl RtmType {
+    pub fn new(val: u8) -> Self {
+        use std::mem;
+        if val > RtmType::XRESOLVE as u8 {
+            return RtmType::UNSPEC;
+        }
+        unsafe { mem::transmute(val) }
+    }
+}
+
 bitflags! {
     pub struct RtmFlags: u32 {
         const NOTIFY = 0x100;
@@ -58,6 +73,33 @@ impl RtmFlags {
     }
 }
 
+/// Next hop flags, reported in the low bits of `rtm_flags`
+bitflags! {
+    pub struct RtnhFlags: u32 {
+        const DEAD = 0x1;
+        const PERVASIVE = 0x2;
+        const ONLINK = 0x4;
+        const OFFLOAD = 0x8;
+        const LINKDOWN = 0x10;
+        const UNRESOLVED = 0x20;
+    }
+}
+
+impl RtnhFlags {
+    pub fn new(val: u32) -> Self {
+          RtnhFlags::from_bits_truncate(val)
+    }
+}
+
+/* rtm_protocol values */
+pub const RTPROT_UNSPEC: u8 = 0;
+pub const RTPROT_REDIRECT: u8 = 1;
+pub const RTPROT_KERNEL: u8 = 2;
+pub const RTPROT_BOOT: u8 = 3;
+pub const RTPROT_STATIC: u8 = 4;
+pub const RTPROT_RA: u8 = 9;
+pub const RTPROT_DHCP: u8 = 16;
+
 /// Each netlink family has a set of 32 multicast groups. The ROUTE family defines the
 /// following values, which can be passed to `NetlinkSocket::bind`, eventually
 /// ending up in the `sockaddr_nl` struct's `nl_groups` field. From `linux/rtnetlink.h`.
@@ -119,18 +161,176 @@ pub struct Route {
 impl Route {
     /// Iterate over routes
     pub fn iter_routes(conn: &mut NetlinkConnection) -> RoutesIterator<&mut NetlinkConnection> {
-        let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-        let req = NetlinkRequestBuilder::new(RTM_GETROUTE, NetlinkMsgFlags::NLM_F_DUMP)
-            .append({
-                let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                ifinfo.set_family(0 /* AF_UNSPEC */);
-                ifinfo
-            })
-            .build();
-        let mut reply = conn.send(req);
+        let mut reply = conn.send(Route::dump_request(None));
         RoutesIterator { iter: reply.into_iter() }
     }
 
+    /// Wrap a RTM_NEWROUTE or RTM_DELROUTE message
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Option<Route> {
+        match packet.get_kind() {
+            RTM_NEWROUTE | RTM_DELROUTE => Some(Route { packet: packet }),
+            _ => None,
+        }
+    }
+
+    /// Request dumping routes of `family`, or all if None
+    pub fn dump_request(family: Option<AddressFamily>) -> NetlinkPacket<'static> {
+        let rtm = RtMsgRequestBuilder::new().with_rtm(|mut rtm| {
+            rtm.set_rtm_family(family.unwrap_or(AddressFamily::Unspec));
+        }).build();
+        NetlinkRequestBuilder::new(RTM_GETROUTE, NetlinkMsgFlags::NLM_F_DUMP).append(rtm).build()
+    }
+
+    /// Get address family
+    pub fn get_family(&self) -> AddressFamily {
+        self.with_rtmsg(|rtm| rtm.get_rtm_family())
+    }
+
+    /// Get destination prefix length
+    pub fn get_dst_len(&self) -> u8 {
+        self.with_rtmsg(|rtm| rtm.get_rtm_dst_len())
+    }
+
+    /// Get source prefix length
+    pub fn get_src_len(&self) -> u8 {
+        self.with_rtmsg(|rtm| rtm.get_rtm_src_len())
+    }
+
+    /// Get TOS filter
+    pub fn get_tos(&self) -> u8 {
+        self.with_rtmsg(|rtm| rtm.get_rtm_tos())
+    }
+
+    /// Get routing table id
+    ///
+    /// The 32-bit RTA_TABLE attribute is preferred over the 8-bit header field.
+    pub fn get_table(&self) -> u32 {
+        match self.with_rta(RTA_TABLE, |rta| NativeEndian::read_u32(rta.payload())) {
+            Some(table) => table,
+            None => self.with_rtmsg(|rtm| rtm.get_rtm_table() as u32),
+        }
+    }
+
+    /// Get routing protocol (RTPROT_*)
+    pub fn get_protocol(&self) -> u8 {
+        self.with_rtmsg(|rtm| rtm.get_rtm_protocol())
+    }
+
+    /// Get scope
+    pub fn get_scope(&self) -> Scope {
+        self.with_rtmsg(|rtm| rtm.get_rtm_scope())
+    }
+
+    /// Get route type
+    pub fn get_type(&self) -> RtmType {
+        self.with_rtmsg(|rtm| RtmType::new(rtm.get_rtm_type()))
+    }
+
+    /// Get route flags
+    pub fn get_flags(&self) -> RtmFlags {
+        self.with_rtmsg(|rtm| RtmFlags::new(rtm.get_rtm_flags()))
+    }
+
+    /// Get next hop flags
+    pub fn get_nh_flags(&self) -> RtnhFlags {
+        self.with_rtmsg(|rtm| RtnhFlags::new(rtm.get_rtm_flags()))
+    }
+
+    /// Get destination address
+    pub fn get_destination(&self) -> Option<IpAddr> {
+        self.get_ip(RTA_DST)
+    }
+
+    /// Get source address
+    pub fn get_source(&self) -> Option<IpAddr> {
+        self.get_ip(RTA_SRC)
+    }
+
+    /// Get destination prefix
+    ///
+    /// Routes without RTA_DST and zero length match everything (default route).
+    pub fn get_destination_prefix(&self) -> Option<IpPrefix> {
+        Self::prefix(self.get_family(), self.get_destination(), self.get_dst_len())
+    }
+
+    /// Get source prefix
+    pub fn get_source_prefix(&self) -> Option<IpPrefix> {
+        Self::prefix(self.get_family(), self.get_source(), self.get_src_len())
+    }
+
+    /// Get gateway address
+    pub fn get_gateway(&self) -> Option<IpAddr> {
+        self.get_ip(RTA_GATEWAY)
+    }
+
+    /// Get preferred source address
+    pub fn get_prefsrc(&self) -> Option<IpAddr> {
+        self.get_ip(RTA_PREFSRC)
+    }
+
+    /// Get input interface index
+    pub fn get_input_interface(&self) -> Option<u32> {
+        self.with_rta(RTA_IIF, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    /// Get output interface index
+    pub fn get_output_interface(&self) -> Option<u32> {
+        self.with_rta(RTA_OIF, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    /// Get priority (metric)
+    pub fn get_priority(&self) -> Option<u32> {
+        self.with_rta(RTA_PRIORITY, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    /// Get firewall mark
+    pub fn get_mark(&self) -> Option<u32> {
+        self.with_rta(RTA_MARK, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    // helper methods
+    fn prefix(family: AddressFamily, ip: Option<IpAddr>, len: u8) -> Option<IpPrefix> {
+        match ip {
+            Some(ip) => IpPrefix::new(ip, len).ok(),
+            None if len == 0 => IpPrefix::default_for(family),
+            None => None,
+        }
+    }
+
+    fn get_ip(&self, rta_type: u16) -> Option<IpAddr> {
+        let family = self.get_family();
+        if !family.is_ip() {
+            return None;
+        }
+        self.with_rta(rta_type, |rta| Addr::ip_from_family_and_bytes(family, rta.payload()))
+    }
+
+    fn with_packet<T,F>(&self, cb: F) -> T
+        where F: Fn(&NetlinkPacket) -> T {
+        cb(&self.packet)
+    }
+
+    fn with_rtmsg<T,F>(&self, cb: F) -> T
+        where F: Fn(RtMsgPacket) -> T {
+        self.with_packet(|pkt|
+            cb(RtMsgPacket::new(pkt.payload()).unwrap())
+        )
+    }
+
+    fn with_rta_iter<T,F>(&self, cb: F) -> T
+        where F: Fn(RtAttrIterator) -> T {
+            self.with_rtmsg(|rtm| {
+                cb(RtAttrIterator::new(rtm.payload()))
+            })
+    }
+
+    fn with_rta<T,F>(&self, rta_type: u16, cb: F) -> Option<T>
+        where F: Fn(RtAttrPacket) -> T {
+        self.with_rta_iter(|mut rti| {
+            rti.find(|rta| rta.get_rta_type() == rta_type).map(|rta| cb(rta))
+        })
+    }
+
     fn dump_route(msg: NetlinkPacket) {
         use std::ffi::CStr;
         if msg.get_kind() != RTM_NEWROUTE {
@@ -185,6 +385,249 @@ impl Route {
     }
 }
 
+/// Owned route description
+///
+/// Parsed once from a `Route`; can be cloned, compared and hashed.
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub struct RouteInfo {
+    pub family: AddressFamily,
+    pub dst_len: u8,
+    pub src_len: u8,
+    pub tos: u8,
+    pub table: u32,
+    pub protocol: u8,
+    pub scope: Scope,
+    pub type_: RtmType,
+    /// Raw `rtm_flags`, see `RtmFlags` and `RtnhFlags`
+    pub flags: u32,
+    pub destination: Option<IpAddr>,
+    pub source: Option<IpAddr>,
+    pub gateway: Option<IpAddr>,
+    pub prefsrc: Option<IpAddr>,
+    pub iif: Option<u32>,
+    pub oif: Option<u32>,
+    pub priority: Option<u32>,
+    pub mark: Option<u32>,
+}
+
+impl<'a> From<&'a Route> for RouteInfo {
+    fn from(route: &'a Route) -> Self {
+        route.with_rtmsg(|rtm| {
+            let family = rtm.get_rtm_family();
+            let ip = |rta: &RtAttrPacket| if family.is_ip() {
+                Some(Addr::ip_from_family_and_bytes(family, rta.payload()))
+            } else {
+                None
+            };
+            let mut info = RouteInfo {
+                family: family,
+                dst_len: rtm.get_rtm_dst_len(),
+                src_len: rtm.get_rtm_src_len(),
+                tos: rtm.get_rtm_tos(),
+                table: rtm.get_rtm_table() as u32,
+                protocol: rtm.get_rtm_protocol(),
+                scope: rtm.get_rtm_scope(),
+                type_: RtmType::new(rtm.get_rtm_type()),
+                flags: rtm.get_rtm_flags(),
+                destination: None,
+                source: None,
+                gateway: None,
+                prefsrc: None,
+                iif: None,
+                oif: None,
+                priority: None,
+                mark: None,
+            };
+            for rta in RtAttrIterator::new(rtm.payload()) {
+                match rta.get_rta_type() {
+                    RTA_DST => info.destination = ip(&rta),
+                    RTA_SRC => info.source = ip(&rta),
+                    RTA_GATEWAY => info.gateway = ip(&rta),
+                    RTA_PREFSRC => info.prefsrc = ip(&rta),
+                    RTA_IIF => info.iif = Some(NativeEndian::read_u32(rta.payload())),
+                    RTA_OIF => info.oif = Some(NativeEndian::read_u32(rta.payload())),
+                    RTA_PRIORITY => info.priority = Some(NativeEndian::read_u32(rta.payload())),
+                    RTA_MARK => info.mark = Some(NativeEndian::read_u32(rta.payload())),
+                    RTA_TABLE => info.table = NativeEndian::read_u32(rta.payload()),
+                    _ => {},
+                }
+            }
+            info
+        })
+    }
+}
+
+impl From<Route> for RouteInfo {
+    fn from(route: Route) -> Self {
+        RouteInfo::from(&route)
+    }
+}
+
+impl RouteInfo {
+    /// Create unicast route to `destination` in the main table
+    ///
+    /// Host bits of `destination` are cleared, the kernel rejects them for routes.
+    pub fn new(destination: IpPrefix) -> Self {
+        let mut info = RouteInfo {
+            family: destination.family(),
+            dst_len: 0,
+            src_len: 0,
+            tos: 0,
+            table: RT_TABLE_MAIN,
+            protocol: RTPROT_BOOT,
+            scope: Scope::Universe,
+            type_: RtmType::UNICAST,
+            flags: 0,
+            destination: None,
+            source: None,
+            gateway: None,
+            prefsrc: None,
+            iif: None,
+            oif: None,
+            priority: None,
+            mark: None,
+        };
+        info.set_destination(destination);
+        info
+    }
+
+    /// Get destination prefix, see `Route::get_destination_prefix`
+    pub fn destination_prefix(&self) -> Option<IpPrefix> {
+        Route::prefix(self.family, self.destination, self.dst_len)
+    }
+
+    /// Get source prefix
+    pub fn source_prefix(&self) -> Option<IpPrefix> {
+        Route::prefix(self.family, self.source, self.src_len)
+    }
+
+    /// Set destination prefix, host bits are cleared
+    ///
+    /// Default prefixes (/0) are sent without RTA_DST.
+    pub fn set_destination(&mut self, prefix: IpPrefix) {
+        self.family = prefix.family();
+        self.dst_len = prefix.len();
+        self.destination = if prefix.is_default() { None } else { Some(prefix.network()) };
+    }
+
+    /// Set source prefix, host bits are cleared
+    pub fn set_source(&mut self, prefix: IpPrefix) {
+        self.src_len = prefix.len();
+        self.source = if prefix.is_default() { None } else { Some(prefix.network()) };
+    }
+
+    /// Request adding this route, fails if addresses are not of the route family
+    pub fn add_request(&self) -> io::Result<NetlinkPacket<'static>> {
+        self.validate()?;
+        Ok(self.to_request(RTM_NEWROUTE, NetlinkMsgFlags::NLM_F_CREATE | NetlinkMsgFlags::NLM_F_EXCL | NetlinkMsgFlags::NLM_F_ACK))
+    }
+
+    /// Request deleting this route
+    pub fn delete_request(&self) -> io::Result<NetlinkPacket<'static>> {
+        self.validate()?;
+        Ok(self.to_request(RTM_DELROUTE, NetlinkMsgFlags::NLM_F_ACK))
+    }
+
+    /// Check that all addresses are of the route family
+    fn validate(&self) -> io::Result<()> {
+        let ips = [self.destination, self.source, self.gateway, self.prefsrc];
+        for ip in ips.iter().filter_map(|ip| *ip) {
+            if AddressFamily::from(ip) != self.family {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("{} does not match route family {:?}", ip, self.family)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Build a request of `kind` (e.g. RTM_NEWROUTE or RTM_DELROUTE) for this route
+    pub fn to_request(&self, kind: u16, flags: NetlinkMsgFlags) -> NetlinkPacket<'static> {
+        let mut rtm = RtMsgRequestBuilder::new().with_rtm(|mut rtm| {
+            rtm.set_rtm_family(self.family);
+            rtm.set_rtm_dst_len(self.dst_len);
+            rtm.set_rtm_src_len(self.src_len);
+            rtm.set_rtm_tos(self.tos);
+            rtm.set_rtm_table(if self.table < 256 { self.table as u8 } else { RT_TABLE_UNSPEC as u8 });
+            rtm.set_rtm_protocol(self.protocol);
+            rtm.set_rtm_scope(self.scope);
+            rtm.set_rtm_type(self.type_ as u8);
+            rtm.set_rtm_flags(self.flags);
+        });
+        let ips = [(RTA_DST, self.destination), (RTA_SRC, self.source),
+                   (RTA_GATEWAY, self.gateway), (RTA_PREFSRC, self.prefsrc)];
+        for &(rta_type, ip) in ips.iter() {
+            if let Some(ip) = ip {
+                rtm = rtm.append(RtAttrPacket::create_with_payload(rta_type, ip));
+            }
+        }
+        let ints = [(RTA_IIF, self.iif), (RTA_OIF, self.oif),
+                    (RTA_PRIORITY, self.priority), (RTA_MARK, self.mark), (RTA_TABLE, Some(self.table))];
+        for &(rta_type, val) in ints.iter() {
+            if let Some(val) = val {
+                rtm = rtm.append(RtAttrPacket::create_with_payload(rta_type, val));
+            }
+        }
+        NetlinkRequestBuilder::new(kind, flags).append(rtm.build()).build()
+    }
+}
+
+/// Route operations trait
+pub trait Routes where Self: Read + Write {
+    fn add_route(&mut self, route: &RouteInfo) -> io::Result<()>;
+    fn delete_route(&mut self, route: &RouteInfo) -> io::Result<()>;
+}
+
+impl Routes for NetlinkConnection {
+    /// Add route, fails if it already exists
+    fn add_route(&mut self, route: &RouteInfo) -> io::Result<()> {
+        let req = route.add_request()?;
+        self.write(req.packet())?;
+        NetlinkReader::new(self).read_to_end()
+    }
+
+    /// Delete route
+    fn delete_route(&mut self, route: &RouteInfo) -> io::Result<()> {
+        let req = route.delete_request()?;
+        self.write(req.packet())?;
+        NetlinkReader::new(self).read_to_end()
+    }
+}
+
+struct RtMsgRequestBuilder {
+    data: Vec<u8>,
+}
+
+impl RtMsgRequestBuilder {
+    pub fn new() -> Self {
+        let data = vec![0; MutableRtMsgPacket::minimum_packet_size()];
+        RtMsgRequestBuilder { data: data }
+    }
+
+    pub fn with_rtm<F>(mut self, f: F) -> Self
+        where F: Fn(MutableRtMsgPacket) -> () {
+        {
+            let pkt = MutableRtMsgPacket::new(&mut self.data[..]).unwrap();
+            f(pkt);
+        }
+        self
+    }
+
+    pub fn append(mut self, rta: RtAttrPacket) -> Self {
+        let len = rta.get_rta_len() as usize;
+        let aligned_len = crate::util::align(len);
+        self.data.extend_from_slice(&rta.packet()[0..len]);
+        // add padding for alignment
+        for _ in len..aligned_len {
+            self.data.push(0);
+        }
+        self
+    }
+
+    pub fn build(self) -> RtMsgPacket<'static> {
+        RtMsgPacket::owned(self.data).unwrap()
+    }
+}
+
 pub struct RoutesIterator<R: Read> {
     iter: NetlinkBufIterator<R>,
 }
@@ -214,6 +657,35 @@ fn dump_routes() {
     }
 }
 
+#[test]
+fn route_info_roundtrip() {
+    use std::io::Cursor;
+
+    let info = RouteInfo {
+        family: AddressFamily::Inet,
+        dst_len: 24,
+        src_len: 0,
+        tos: 0,
+        table: 1000,
+        protocol: RTPROT_STATIC,
+        scope: Scope::Universe,
+        type_: RtmType::UNICAST,
+        flags: 0,
+        destination: Some(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 0))),
+        source: None,
+        gateway: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+        prefsrc: None,
+        iif: None,
+        oif: Some(3),
+        priority: Some(100),
+        mark: None,
+    };
+    let req = info.to_request(RTM_NEWROUTE, NetlinkMsgFlags::empty());
+    let reader = NetlinkReader::new(Cursor::new(req.packet().to_vec()));
+    let mut routes = RoutesIterator { iter: reader.into_iter() };
+    assert_eq!(RouteInfo::from(routes.next().unwrap()), info);
+}
+
 /// A trait for converting data into Payload for a `RtAttrPacket`.
 pub trait ToPayload {
     /// Add this data to the given u8 data slice.
@@ -244,6 +716,16 @@ impl ToPayload for IpAddr {
     }
 }
 
+impl ToPayload for MacAddr {
+    fn payload_add(&self, payload: &mut [u8]) {
+        payload[..6].copy_from_slice(&[self.0, self.1, self.2, self.3, self.4, self.5])
+    }
+
+    fn payload_size(&self) -> usize {
+        6
+    }
+}
+
 impl<'a> ToPayload for &'a str {
     fn payload_add(&self, payload: &mut [u8]) {
         payload[..self.as_bytes().len()].copy_from_slice(self.as_bytes())
@@ -254,7 +736,7 @@ impl<'a> ToPayload for &'a str {
     }
 }
 
-impl<'a> ToPayload for &'a [&'a ToPayload] {
+impl<'a> ToPayload for &'a [&'a dyn ToPayload] {
     fn payload_add(&self, payload: &mut [u8]) {
         self.iter().fold(0, |pos, pkg| {
             pkg.payload_add(&mut payload[pos..]);
@@ -350,3 +832,15 @@ impl<'a> WithPayload for RtAttrPacket<'a> {
         RtAttrPacket::owned(result.packet().to_vec()).unwrap()
     }
 }
+
+#[test]
+fn route_info_prefix() {
+    let mut info = RouteInfo::new("10.1.2.3/24".parse().unwrap());
+    assert_eq!(info.destination, Some(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 0))));
+    assert_eq!(info.destination_prefix(), Some("10.1.2.0/24".parse().unwrap()));
+    info.set_destination("0.0.0.0/0".parse().unwrap());
+    assert_eq!(info.destination, None);
+    assert!(info.destination_prefix().unwrap().is_default());
+    info.gateway = Some("fe80::1".parse().unwrap());
+    assert!(info.validate().is_err());
+}
@@ -1,20 +1,24 @@
 //! Rules operations
-use packet::route::{FibRulePacket,MutableRtMsgPacket,MutableIfInfoPacket,RtAttrIterator,RtAttrPacket,MutableRtAttrPacket};
-use packet::route::link::Link;
-use packet::netlink::{MutableNetlinkPacket,NetlinkPacket,NetlinkErrorPacket};
-use packet::netlink::NetlinkMsgFlags;
-use packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
-use socket::{NetlinkSocket,NetlinkProtocol};
-use packet::netlink::NetlinkConnection;
+use crate::packet::route::{AddressFamily,FibRulePacket,MutableFibRulePacket,MutableRtMsgPacket,MutableIfInfoPacket,RtAttrIterator,RtAttrPacket,MutableRtAttrPacket};
+use crate::packet::route::link::Link;
+use crate::packet::netlink::{MutableNetlinkPacket,NetlinkPacket,NetlinkErrorPacket};
+use crate::packet::netlink::NetlinkMsgFlags;
+use crate::packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
+use crate::socket::{NetlinkSocket,NetlinkProtocol};
+use crate::packet::netlink::NetlinkConnection;
 use pnet::packet::MutablePacket;
 use pnet::packet::Packet;
 use pnet::packet::PacketSize;
 use pnet::util::MacAddr;
 use libc;
 
-use std::net::Ipv4Addr;
-use std::io::{Read,Cursor,self};
-use byteorder::{LittleEndian, BigEndian, ReadBytesExt};
+use crate::packet::route::addr::Addr;
+use crate::packet::route::prefix::IpPrefix;
+use crate::packet::route::route::WithPayload;
+
+use std::net::{Ipv4Addr,IpAddr};
+use std::io::{Read,Write,Cursor,self};
+use byteorder::{LittleEndian, BigEndian, ReadBytesExt, NativeEndian, ByteOrder};
 
 pub const RTM_NEWRULE: u16 = 32;
 pub const RTM_DELRULE: u16 = 33;
@@ -39,6 +43,19 @@ pub const FRA_TABLE: u16 = 15;      /* Extended table id */
 pub const FRA_FWMASK: u16 = 16;     /* mask for netfilter mark */
 pub const FRA_OIFNAME: u16 = 17;
 
+/* fib rule actions */
+pub const FR_ACT_UNSPEC: u8 = 0;
+pub const FR_ACT_TO_TBL: u8 = 1;        /* Pass to fixed table */
+pub const FR_ACT_GOTO: u8 = 2;          /* Jump to another rule */
+pub const FR_ACT_NOP: u8 = 3;           /* No operation */
+pub const FR_ACT_BLACKHOLE: u8 = 6;     /* Drop without notification */
+pub const FR_ACT_UNREACHABLE: u8 = 7;   /* Drop with ENETUNREACH */
+pub const FR_ACT_PROHIBIT: u8 = 8;      /* Drop with EACCES */
+
+/* fib rule flags */
+pub const FIB_RULE_PERMANENT: u32 = 0x1;
+pub const FIB_RULE_INVERT: u32 = 0x2;
+
 #[derive(Debug)]
 pub struct Rule {
     packet: NetlinkPacket<'static>,
@@ -47,17 +64,160 @@ pub struct Rule {
 impl Rule {
     /// iterate over rules
     pub fn iter_rules(conn: &mut NetlinkConnection) -> RulesIterator<&mut NetlinkConnection> {
-        let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-        let req = NetlinkRequestBuilder::new(RTM_GETRULE, NetlinkMsgFlags::NLM_F_DUMP)
-            .append({
-                let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                ifinfo.set_family(0 /* AF_UNSPEC */);
-                ifinfo
-            }).build();
-        let mut reply = conn.send(req);
+        let mut reply = conn.send(Rule::dump_request(None));
         RulesIterator { iter: reply.into_iter() }
     }
 
+    /// Wrap a RTM_NEWRULE or RTM_DELRULE message
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Option<Rule> {
+        match packet.get_kind() {
+            RTM_NEWRULE | RTM_DELRULE => Some(Rule { packet: packet }),
+            _ => None,
+        }
+    }
+
+    /// Request dumping rules of `family`, or all if None
+    pub fn dump_request(family: Option<AddressFamily>) -> NetlinkPacket<'static> {
+        let mut buf = vec![0; MutableFibRulePacket::minimum_packet_size()];
+        MutableFibRulePacket::new(&mut buf).unwrap().set_family(family.unwrap_or(AddressFamily::Unspec));
+        NetlinkRequestBuilder::new(RTM_GETRULE, NetlinkMsgFlags::NLM_F_DUMP)
+            .append(FibRulePacket::owned(buf).unwrap())
+            .build()
+    }
+
+    /// Get address family
+    pub fn get_family(&self) -> AddressFamily {
+        self.with_rule(|rule| rule.get_family())
+    }
+
+    /// Get destination prefix length
+    pub fn get_dst_len(&self) -> u8 {
+        self.with_rule(|rule| rule.get_dst_len())
+    }
+
+    /// Get source prefix length
+    pub fn get_src_len(&self) -> u8 {
+        self.with_rule(|rule| rule.get_src_len())
+    }
+
+    /// Get TOS selector
+    pub fn get_tos(&self) -> u8 {
+        self.with_rule(|rule| rule.get_tos())
+    }
+
+    /// Get rule action (FR_ACT_*)
+    pub fn get_action(&self) -> u8 {
+        self.with_rule(|rule| rule.get_action())
+    }
+
+    /// Get rule flags (FIB_RULE_*)
+    pub fn get_flags(&self) -> u32 {
+        self.with_rule(|rule| rule.get_flags())
+    }
+
+    /// Get routing table id
+    ///
+    /// The 32-bit FRA_TABLE attribute is preferred over the 8-bit header field.
+    pub fn get_table(&self) -> u32 {
+        match self.with_rta(FRA_TABLE, |rta| NativeEndian::read_u32(rta.payload())) {
+            Some(table) => table,
+            None => self.with_rule(|rule| rule.get_table() as u32),
+        }
+    }
+
+    /// Get priority (preference)
+    pub fn get_priority(&self) -> Option<u32> {
+        self.with_rta(FRA_PRIORITY, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    /// Get source address selector
+    pub fn get_source(&self) -> Option<IpAddr> {
+        self.get_ip(FRA_SRC)
+    }
+
+    /// Get destination address selector
+    pub fn get_destination(&self) -> Option<IpAddr> {
+        self.get_ip(FRA_DST)
+    }
+
+    /// Get source prefix selector, None matches any source
+    pub fn get_source_prefix(&self) -> Option<IpPrefix> {
+        self.get_source().and_then(|ip| IpPrefix::new(ip, self.get_src_len()).ok())
+    }
+
+    /// Get destination prefix selector, None matches any destination
+    pub fn get_destination_prefix(&self) -> Option<IpPrefix> {
+        self.get_destination().and_then(|ip| IpPrefix::new(ip, self.get_dst_len()).ok())
+    }
+
+    /// Get firewall mark selector
+    pub fn get_fwmark(&self) -> Option<u32> {
+        self.with_rta(FRA_FWMARK, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    /// Get firewall mark mask
+    pub fn get_fwmask(&self) -> Option<u32> {
+        self.with_rta(FRA_FWMASK, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    /// Get rule priority to jump to (FR_ACT_GOTO)
+    pub fn get_goto(&self) -> Option<u32> {
+        self.with_rta(FRA_GOTO, |rta| NativeEndian::read_u32(rta.payload()))
+    }
+
+    /// Get input interface name selector
+    pub fn get_iifname(&self) -> Option<String> {
+        self.get_string(FRA_IFNAME)
+    }
+
+    /// Get output interface name selector
+    pub fn get_oifname(&self) -> Option<String> {
+        self.get_string(FRA_OIFNAME)
+    }
+
+    // helper methods
+    fn get_ip(&self, rta_type: u16) -> Option<IpAddr> {
+        let family = self.get_family();
+        if !family.is_ip() {
+            return None;
+        }
+        self.with_rta(rta_type, |rta| Addr::ip_from_family_and_bytes(family, rta.payload()))
+    }
+
+    fn get_string(&self, rta_type: u16) -> Option<String> {
+        use std::ffi::CStr;
+        self.with_rta(rta_type, |rta| {
+            let cstr = CStr::from_bytes_with_nul(rta.payload()).unwrap();
+            cstr.to_owned().into_string().unwrap()
+        })
+    }
+
+    fn with_packet<T,F>(&self, cb: F) -> T
+        where F: Fn(&NetlinkPacket) -> T {
+        cb(&self.packet)
+    }
+
+    fn with_rule<T,F>(&self, cb: F) -> T
+        where F: Fn(FibRulePacket) -> T {
+        self.with_packet(|pkt|
+            cb(FibRulePacket::new(pkt.payload()).unwrap())
+        )
+    }
+
+    fn with_rta_iter<T,F>(&self, cb: F) -> T
+        where F: Fn(RtAttrIterator) -> T {
+            self.with_rule(|rule| {
+                cb(RtAttrIterator::new(rule.payload()))
+            })
+    }
+
+    fn with_rta<T,F>(&self, rta_type: u16, cb: F) -> Option<T>
+        where F: Fn(RtAttrPacket) -> T {
+        self.with_rta_iter(|mut rti| {
+            rti.find(|rta| rta.get_rta_type() == rta_type).map(|rta| cb(rta))
+        })
+    }
+
     fn dump_rule(msg: NetlinkPacket) {
         use std::ffi::CStr;
         if msg.get_kind() != RTM_NEWRULE {
@@ -90,6 +250,225 @@ impl Rule {
     }
 }
 
+/// Owned rule description
+///
+/// Parsed once from a `Rule`; can be cloned, compared and hashed.
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub struct RuleInfo {
+    pub family: AddressFamily,
+    pub dst_len: u8,
+    pub src_len: u8,
+    pub tos: u8,
+    pub table: u32,
+    /// FR_ACT_*
+    pub action: u8,
+    /// FIB_RULE_*
+    pub flags: u32,
+    pub priority: Option<u32>,
+    pub source: Option<IpAddr>,
+    pub destination: Option<IpAddr>,
+    pub fwmark: Option<u32>,
+    pub fwmask: Option<u32>,
+    pub goto: Option<u32>,
+    pub iifname: Option<String>,
+    pub oifname: Option<String>,
+}
+
+impl<'a> From<&'a Rule> for RuleInfo {
+    fn from(rule: &'a Rule) -> Self {
+        use std::ffi::CStr;
+
+        rule.with_rule(|fib| {
+            let family = fib.get_family();
+            let ip = |rta: &RtAttrPacket| match (family, rta.payload().len()) {
+                (AddressFamily::Inet, 4) | (AddressFamily::Inet6, 16) => {
+                    Some(Addr::ip_from_family_and_bytes(family, rta.payload()))
+                },
+                _ => None,
+            };
+            // malformed names are skipped, invalid UTF-8 is replaced
+            let string = |rta: &RtAttrPacket| {
+                CStr::from_bytes_with_nul(rta.payload()).ok().map(|cstr| cstr.to_string_lossy().into_owned())
+            };
+            let mut info = RuleInfo {
+                family: family,
+                dst_len: fib.get_dst_len(),
+                src_len: fib.get_src_len(),
+                tos: fib.get_tos(),
+                table: fib.get_table() as u32,
+                action: fib.get_action(),
+                flags: fib.get_flags(),
+                priority: None,
+                source: None,
+                destination: None,
+                fwmark: None,
+                fwmask: None,
+                goto: None,
+                iifname: None,
+                oifname: None,
+            };
+            for rta in RtAttrIterator::new(fib.payload()) {
+                match rta.get_rta_type() {
+                    FRA_SRC => info.source = ip(&rta),
+                    FRA_DST => info.destination = ip(&rta),
+                    FRA_PRIORITY if rta.payload().len() >= 4 => info.priority = Some(NativeEndian::read_u32(rta.payload())),
+                    FRA_FWMARK if rta.payload().len() >= 4 => info.fwmark = Some(NativeEndian::read_u32(rta.payload())),
+                    FRA_FWMASK if rta.payload().len() >= 4 => info.fwmask = Some(NativeEndian::read_u32(rta.payload())),
+                    FRA_GOTO if rta.payload().len() >= 4 => info.goto = Some(NativeEndian::read_u32(rta.payload())),
+                    FRA_TABLE if rta.payload().len() >= 4 => info.table = NativeEndian::read_u32(rta.payload()),
+                    FRA_IFNAME => info.iifname = string(&rta),
+                    FRA_OIFNAME => info.oifname = string(&rta),
+                    _ => {},
+                }
+            }
+            info
+        })
+    }
+}
+
+impl From<Rule> for RuleInfo {
+    fn from(rule: Rule) -> Self {
+        RuleInfo::from(&rule)
+    }
+}
+
+impl RuleInfo {
+    /// Create rule of `family` passing everything to `table`
+    pub fn new(family: AddressFamily, table: u32) -> Self {
+        RuleInfo {
+            family: family,
+            dst_len: 0,
+            src_len: 0,
+            tos: 0,
+            table: table,
+            action: FR_ACT_TO_TBL,
+            flags: 0,
+            priority: None,
+            source: None,
+            destination: None,
+            fwmark: None,
+            fwmask: None,
+            goto: None,
+            iifname: None,
+            oifname: None,
+        }
+    }
+
+    /// Get source prefix selector
+    pub fn source_prefix(&self) -> Option<IpPrefix> {
+        self.source.and_then(|ip| IpPrefix::new(ip, self.src_len).ok())
+    }
+
+    /// Get destination prefix selector
+    pub fn destination_prefix(&self) -> Option<IpPrefix> {
+        self.destination.and_then(|ip| IpPrefix::new(ip, self.dst_len).ok())
+    }
+
+    /// Set source prefix selector, host bits are cleared
+    pub fn set_source(&mut self, prefix: IpPrefix) {
+        self.family = prefix.family();
+        self.src_len = prefix.len();
+        self.source = if prefix.is_default() { None } else { Some(prefix.network()) };
+    }
+
+    /// Set destination prefix selector, host bits are cleared
+    pub fn set_destination(&mut self, prefix: IpPrefix) {
+        self.family = prefix.family();
+        self.dst_len = prefix.len();
+        self.destination = if prefix.is_default() { None } else { Some(prefix.network()) };
+    }
+
+    /// Build a request of `kind` (e.g. RTM_NEWRULE or RTM_DELRULE) for this rule
+    pub fn to_request(&self, kind: u16, flags: NetlinkMsgFlags) -> NetlinkPacket<'static> {
+        let mut buf = vec![0; MutableFibRulePacket::minimum_packet_size()];
+        {
+            let mut fib = MutableFibRulePacket::new(&mut buf).unwrap();
+            fib.set_family(self.family);
+            fib.set_dst_len(self.dst_len);
+            fib.set_src_len(self.src_len);
+            fib.set_tos(self.tos);
+            fib.set_table(if self.table < 256 { self.table as u8 } else { 0 });
+            fib.set_action(self.action);
+            fib.set_flags(self.flags);
+        }
+        let ips = [(FRA_SRC, self.source), (FRA_DST, self.destination)];
+        for &(rta_type, ip) in ips.iter() {
+            if let Some(ip) = ip {
+                Self::push_rta(&mut buf, RtAttrPacket::create_with_payload(rta_type, ip));
+            }
+        }
+        let ints = [(FRA_PRIORITY, self.priority), (FRA_FWMARK, self.fwmark), (FRA_FWMASK, self.fwmask),
+                    (FRA_GOTO, self.goto), (FRA_TABLE, Some(self.table))];
+        for &(rta_type, val) in ints.iter() {
+            if let Some(val) = val {
+                Self::push_rta(&mut buf, RtAttrPacket::create_with_payload(rta_type, val));
+            }
+        }
+        let names = [(FRA_IFNAME, &self.iifname), (FRA_OIFNAME, &self.oifname)];
+        for &(rta_type, name) in names.iter() {
+            if let Some(ref name) = *name {
+                Self::push_rta(&mut buf, RtAttrPacket::create_with_payload(rta_type, name.as_str()));
+            }
+        }
+        NetlinkRequestBuilder::new(kind, flags).append(FibRulePacket::owned(buf).unwrap()).build()
+    }
+
+    fn push_rta(buf: &mut Vec<u8>, rta: RtAttrPacket) {
+        let len = rta.get_rta_len() as usize;
+        buf.extend_from_slice(&rta.packet()[0..len]);
+        // add padding for alignment
+        for _ in len..crate::util::align(len) {
+            buf.push(0);
+        }
+    }
+
+    /// Request adding this rule, fails if addresses are not of the rule family
+    pub fn add_request(&self) -> io::Result<NetlinkPacket<'static>> {
+        self.validate()?;
+        Ok(self.to_request(RTM_NEWRULE, NetlinkMsgFlags::NLM_F_CREATE | NetlinkMsgFlags::NLM_F_EXCL | NetlinkMsgFlags::NLM_F_ACK))
+    }
+
+    /// Request deleting first rule matching this one
+    pub fn delete_request(&self) -> io::Result<NetlinkPacket<'static>> {
+        self.validate()?;
+        Ok(self.to_request(RTM_DELRULE, NetlinkMsgFlags::NLM_F_ACK))
+    }
+
+    /// Check that all addresses are of the rule family
+    fn validate(&self) -> io::Result<()> {
+        let ips = [self.source, self.destination];
+        for ip in ips.iter().filter_map(|ip| *ip) {
+            if AddressFamily::from(ip) != self.family {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("{} does not match rule family {:?}", ip, self.family)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rule operations trait
+pub trait Rules where Self: Read + Write {
+    fn add_rule(&mut self, rule: &RuleInfo) -> io::Result<()>;
+    fn delete_rule(&mut self, rule: &RuleInfo) -> io::Result<()>;
+}
+
+impl Rules for NetlinkConnection {
+    /// Add rule, fails if an identical rule exists
+    fn add_rule(&mut self, rule: &RuleInfo) -> io::Result<()> {
+        let req = rule.add_request()?;
+        self.write(req.packet())?;
+        NetlinkReader::new(self).read_to_end()
+    }
+
+    /// Delete first rule matching `rule`
+    fn delete_rule(&mut self, rule: &RuleInfo) -> io::Result<()> {
+        let req = rule.delete_request()?;
+        self.write(req.packet())?;
+        NetlinkReader::new(self).read_to_end()
+    }
+}
+
 pub struct RulesIterator<R: Read> {
     iter: NetlinkBufIterator<R>,
 }
@@ -118,3 +497,16 @@ fn dump_rules() {
         Rule::dump_rule(rule.packet);
     }
 }
+
+#[test]
+fn rule_info_roundtrip() {
+    let mut info = RuleInfo::new(AddressFamily::Inet, 1000);
+    info.set_source("10.1.2.3/24".parse().unwrap());
+    info.priority = Some(100);
+    info.iifname = Some("eth0".to_owned());
+    assert_eq!(info.source_prefix(), Some("10.1.2.0/24".parse().unwrap()));
+    let req = info.to_request(RTM_NEWRULE, NetlinkMsgFlags::empty());
+    let reader = NetlinkReader::new(Cursor::new(req.packet().to_vec()));
+    let mut rules = RulesIterator { iter: reader.into_iter() };
+    assert_eq!(RuleInfo::from(rules.next().unwrap()), info);
+}
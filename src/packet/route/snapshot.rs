@@ -0,0 +1,731 @@
+//! Serialisable snapshots of rtnetlink objects
+//!
+//! Snapshots are owned copies of `Link`, `Addr`, `Route`, `Rule` and `Neighbour`
+//! laid out like `ip -j link/addr/route/rule/neigh` output, so a document
+//! produced here can be compared with iproute2's one field by field.
+//!
+//! Interface names are not part of most rtnetlink messages, so constructors take
+//! a resolver closure mapping an interface index to its name.
+//!
+//! # Example
+//! ```
+//! extern crate pnetlink;
+//! extern crate serde_json;
+//!
+//! use std::collections::HashMap;
+//! use pnetlink::packet::netlink::NetlinkConnection;
+//! use pnetlink::packet::route::link::Links;
+//! use pnetlink::packet::route::snapshot::LinkSnapshot;
+//!
+//! let mut conn = NetlinkConnection::new();
+//! let links = conn.iter_links().unwrap().collect::<Vec<_>>();
+//! let names = links.iter().map(|l| (l.get_index(), l.get_name().unwrap())).collect::<HashMap<_,_>>();
+//! let snapshots = links.iter().map(|l| LinkSnapshot::new(l, |idx| names.get(&idx).cloned())).collect::<Vec<_>>();
+//! println!("{}", serde_json::to_string(&snapshots).unwrap());
+//! ```
+use crate::packet::route::AddressFamily;
+use crate::packet::route::link::{Link,IfFlags,IfType,OperState};
+use crate::packet::route::addr::{Addr,IfAddrFlags,Scope};
+use crate::packet::route::route::{Route,RtmType,RtmFlags,RtnhFlags,RT_TABLE_MAIN,RTPROT_BOOT};
+use crate::packet::route::rule::{self,Rule};
+use crate::packet::route::neighbour::{Neighbour,NeighbourFlags,NeighbourState};
+use serde::{Serialize,Deserialize};
+
+use std::net::IpAddr;
+
+/// `ip -j link` entry
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+#[serde(default)]
+pub struct LinkSnapshot {
+    pub ifindex: u32,
+    pub ifname: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_index: Option<u32>,
+    pub flags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qdisc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub master: Option<String>,
+    pub operstate: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linkmode: Option<String>,
+    pub group: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txqlen: Option<u32>,
+    pub link_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broadcast: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ifalias: Option<String>,
+}
+
+impl LinkSnapshot {
+    /// Snapshot `link`, resolving master and lower device names with `names`
+    pub fn new<F>(link: &Link, names: F) -> Self
+        where F: Fn(u32) -> Option<String> {
+        let index = link.get_index();
+        let (lower, lower_index) = match link.get_link() {
+            Some(idx) if idx != index => match names(idx) {
+                Some(name) => (Some(name), None),
+                None => (None, Some(idx)),
+            },
+            _ => (None, None),
+        };
+        LinkSnapshot {
+            ifindex: index,
+            ifname: link.get_name().unwrap_or_default(),
+            link: lower,
+            link_index: lower_index,
+            flags: link_flags(link.get_flags()),
+            mtu: link.get_mtu(),
+            qdisc: link.get_qdisc(),
+            master: link.get_master().and_then(|idx| names(idx)),
+            operstate: operstate_name(link.get_state()).to_owned(),
+            linkmode: link.get_link_mode().map(|mode| match mode {
+                0 => "DEFAULT".to_owned(),
+                1 => "DORMANT".to_owned(),
+                mode => mode.to_string(),
+            }),
+            group: match link.get_group() {
+                Some(0) | None => "default".to_owned(),
+                Some(group) => group.to_string(),
+            },
+            txqlen: link.get_txqlen(),
+            link_type: link_type_name(link.get_type()).to_owned(),
+            address: link.get_hw_addr().map(|mac| mac.to_string()),
+            broadcast: link.get_broadcast().map(|mac| mac.to_string()),
+            ifalias: link.get_alias(),
+        }
+    }
+}
+
+/// `ip -j addr` entry: link description followed by its addresses
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+#[serde(default)]
+pub struct AddrSnapshot {
+    #[serde(flatten)]
+    pub link: LinkSnapshot,
+    pub addr_info: Vec<AddrInfoSnapshot>,
+}
+
+impl AddrSnapshot {
+    /// Snapshot `link` together with those of `addrs` which belong to it
+    pub fn new<'a,I,F>(link: &Link, addrs: I, names: F) -> Self
+        where I: IntoIterator<Item = &'a Addr>, F: Fn(u32) -> Option<String> {
+        let index = link.get_index();
+        let mut link = LinkSnapshot::new(link, names);
+        /* ip addr does not report link mode */
+        link.linkmode = None;
+        AddrSnapshot {
+            link: link,
+            addr_info: addrs.into_iter()
+                .filter(|addr| addr.get_link_index() == index)
+                .map(AddrInfoSnapshot::new)
+                .collect(),
+        }
+    }
+}
+
+/// Single address of `ip -j addr` entry
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+#[serde(default)]
+pub struct AddrInfoSnapshot {
+    pub family: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local: Option<String>,
+    /// Peer address of point-to-point links
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    pub prefixlen: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broadcast: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anycast: Option<String>,
+    pub scope: String,
+    #[serde(skip_serializing_if = "is_false")]
+    pub secondary: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub temporary: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub tentative: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub deprecated: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub home: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub nodad: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub optimistic: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub dadfailed: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub dynamic: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub valid_life_time: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_life_time: Option<u32>,
+}
+
+impl AddrInfoSnapshot {
+    pub fn new(addr: &Addr) -> Self {
+        let family = addr.get_family();
+        let flags = addr.get_flags();
+        let address = addr.get_ip();
+        let local = addr.get_local_ip().or(address);
+        let peer = match (local, address) {
+            (Some(local), Some(address)) if local != address => Some(address),
+            _ => None,
+        };
+        let cache_info = addr.get_cache_info();
+        AddrInfoSnapshot {
+            family: family_name(family),
+            local: local.map(|ip| ip.to_string()),
+            address: peer.map(|ip| ip.to_string()),
+            prefixlen: addr.get_prefix_len(),
+            broadcast: addr.get_broadcast_ip().map(|ip| ip.to_string()),
+            anycast: addr.get_anycast_ip().map(|ip| ip.to_string()),
+            scope: scope_name(addr.get_scope()),
+            /* IFA_F_SECONDARY is called temporary for IPv6 */
+            secondary: flags.contains(IfAddrFlags::SECONDARY) && family != AddressFamily::Inet6,
+            temporary: flags.contains(IfAddrFlags::TEMPORARY) && family == AddressFamily::Inet6,
+            tentative: flags.contains(IfAddrFlags::TENTATIVE),
+            deprecated: flags.contains(IfAddrFlags::DEPRECATED),
+            home: flags.contains(IfAddrFlags::HOMEADDRESS),
+            nodad: flags.contains(IfAddrFlags::NODAD),
+            optimistic: flags.contains(IfAddrFlags::OPTIMISTIC),
+            dadfailed: flags.contains(IfAddrFlags::DADFAILED),
+            dynamic: !flags.contains(IfAddrFlags::PERMANENT),
+            label: addr.get_label(),
+            valid_life_time: cache_info.as_ref().map(|ci| ci.get_ifa_valid()),
+            preferred_life_time: cache_info.as_ref().map(|ci| ci.get_ifa_prefered()),
+        }
+    }
+}
+
+/// `ip -j route` entry
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+#[serde(default)]
+pub struct RouteSnapshot {
+    /// Route type, omitted for unicast routes
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    pub dst: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub src: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev: Option<String>,
+    /// Routing table, omitted for the main table
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+    /// Routing protocol, omitted for `boot`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    /// Scope, omitted for `global`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefsrc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metric: Option<u32>,
+    pub flags: Vec<String>,
+}
+
+impl RouteSnapshot {
+    /// Snapshot `route`, resolving the output device name with `names`
+    pub fn new<F>(route: &Route, names: F) -> Self
+        where F: Fn(u32) -> Option<String> {
+        let family = route.get_family();
+        let rtm_type = route.get_type();
+        let table = route.get_table();
+        let protocol = route.get_protocol();
+        let scope = route.get_scope();
+        let cloned = route.get_flags().contains(RtmFlags::CLONED);
+        RouteSnapshot {
+            type_: if rtm_type != RtmType::UNICAST { Some(route_type_name(rtm_type).to_owned()) } else { None },
+            dst: match prefix(family, route.get_destination(), route.get_dst_len()) {
+                Some(dst) => dst,
+                None => "default".to_owned(),
+            },
+            src: prefix(family, route.get_source(), route.get_src_len()),
+            gateway: route.get_gateway().map(|ip| ip.to_string()),
+            dev: route.get_output_interface().map(|idx| names(idx).unwrap_or_else(|| idx.to_string())),
+            table: if table != RT_TABLE_MAIN { Some(table_name(table)) } else { None },
+            protocol: if protocol != RTPROT_BOOT && !cloned { Some(protocol_name(protocol)) } else { None },
+            scope: if scope != Scope::Universe && !cloned { Some(scope_name(scope)) } else { None },
+            prefsrc: route.get_prefsrc().map(|ip| ip.to_string()),
+            metric: route.get_priority(),
+            flags: route_flags(route.get_nh_flags(), route.get_flags()),
+        }
+    }
+}
+
+/// `ip -j rule` entry
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+#[serde(default)]
+pub struct RuleSnapshot {
+    pub priority: u32,
+    /// Inverted rule, serialised as `null`
+    #[serde(with = "null_flag", skip_serializing_if = "is_false")]
+    pub not: bool,
+    pub src: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub srclen: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dst: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dstlen: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fwmark: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fwmask: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iif: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oif: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goto: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+}
+
+impl RuleSnapshot {
+    pub fn new(r: &Rule) -> Self {
+        let family = r.get_family();
+        let host_len = host_len(family);
+        let (src, srclen) = match (r.get_source(), r.get_src_len()) {
+            (Some(ip), len) => (ip.to_string(), if len != host_len { Some(len) } else { None }),
+            (None, 0) => ("all".to_owned(), None),
+            (None, len) => ("0".to_owned(), Some(len)),
+        };
+        let (dst, dstlen) = match (r.get_destination(), r.get_dst_len()) {
+            (Some(ip), len) => (Some(ip.to_string()), if len != host_len { Some(len) } else { None }),
+            (None, 0) => (None, None),
+            (None, len) => (Some("0".to_owned()), Some(len)),
+        };
+        let (fwmark, fwmask) = match (r.get_fwmark(), r.get_fwmask()) {
+            (None, None) => (None, None),
+            (mark, Some(mask)) if mask != 0xffffffff => {
+                (Some(format!("{:#x}", mark.unwrap_or(0))), Some(format!("{:#x}", mask)))
+            },
+            (mark, _) => (Some(format!("{:#x}", mark.unwrap_or(0))), None),
+        };
+        let action = r.get_action();
+        RuleSnapshot {
+            priority: r.get_priority().unwrap_or(0),
+            not: r.get_flags() & rule::FIB_RULE_INVERT != 0,
+            src: src,
+            srclen: srclen,
+            dst: dst,
+            dstlen: dstlen,
+            fwmark: fwmark,
+            fwmask: fwmask,
+            iif: r.get_iifname(),
+            oif: r.get_oifname(),
+            table: if action == rule::FR_ACT_TO_TBL { Some(table_name(r.get_table())) } else { None },
+            goto: if action == rule::FR_ACT_GOTO { r.get_goto() } else { None },
+            action: match action {
+                rule::FR_ACT_TO_TBL | rule::FR_ACT_GOTO => None,
+                rule::FR_ACT_NOP => Some("nop".to_owned()),
+                rule::FR_ACT_BLACKHOLE => Some("blackhole".to_owned()),
+                rule::FR_ACT_UNREACHABLE => Some("unreachable".to_owned()),
+                rule::FR_ACT_PROHIBIT => Some("prohibit".to_owned()),
+                action => Some(action.to_string()),
+            },
+        }
+    }
+}
+
+/// `ip -j neigh` entry
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+#[serde(default)]
+pub struct NeighbourSnapshot {
+    pub dst: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lladdr: Option<String>,
+    #[serde(with = "null_flag", skip_serializing_if = "is_false")]
+    pub router: bool,
+    #[serde(with = "null_flag", skip_serializing_if = "is_false")]
+    pub proxy: bool,
+    #[serde(with = "null_flag", skip_serializing_if = "is_false")]
+    pub extern_learn: bool,
+    pub state: Vec<String>,
+}
+
+impl NeighbourSnapshot {
+    /// Snapshot `neigh`, resolving the device name with `names`
+    pub fn new<F>(neigh: &Neighbour, names: F) -> Self
+        where F: Fn(u32) -> Option<String> {
+        let flags = neigh.get_flags();
+        let idx = neigh.get_ifindex();
+        NeighbourSnapshot {
+            dst: neigh.get_destination().map(|ip| ip.to_string()).unwrap_or_default(),
+            dev: Some(names(idx).unwrap_or_else(|| idx.to_string())),
+            lladdr: neigh.get_ll_addr().map(|mac| mac.to_string()),
+            router: flags.contains(NeighbourFlags::ROUTER),
+            proxy: flags.contains(NeighbourFlags::PROXY),
+            extern_learn: flags.contains(NeighbourFlags::EXT_LEARNED),
+            state: neighbour_state(neigh.get_state()),
+        }
+    }
+}
+
+fn is_false(val: &bool) -> bool {
+    !*val
+}
+
+/// Flags that iproute2 prints as a key with a `null` value (`"not":null`)
+///
+/// A set flag is written as `null`; any value read back, `null` included,
+/// means set, and a missing key means unset (with `skip_serializing_if =
+/// "is_false"` and `#[serde(default)]`).
+mod null_flag {
+    use serde::{Deserialize,Deserializer,Serializer};
+    use serde::de::IgnoredAny;
+
+    pub fn serialize<S: Serializer>(_: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        IgnoredAny::deserialize(deserializer).map(|_| true)
+    }
+}
+
+fn host_len(family: AddressFamily) -> u8 {
+    match family {
+        AddressFamily::Inet => 32,
+        AddressFamily::Inet6 => 128,
+        _ => 0,
+    }
+}
+
+/// Formats a prefix the way iproute2 does: host prefixes have no length
+fn prefix(family: AddressFamily, addr: Option<IpAddr>, len: u8) -> Option<String> {
+    match addr {
+        Some(addr) if len == host_len(family) => Some(addr.to_string()),
+        Some(addr) => Some(format!("{}/{}", addr, len)),
+        None if len != 0 => Some(format!("0/{}", len)),
+        None => None,
+    }
+}
+
+fn family_name(family: AddressFamily) -> String {
+    match family {
+        AddressFamily::Unspec => "unspec".to_owned(),
+        AddressFamily::Unix => "unix".to_owned(),
+        AddressFamily::Inet => "inet".to_owned(),
+        AddressFamily::Bridge => "bridge".to_owned(),
+        AddressFamily::Inet6 => "inet6".to_owned(),
+        AddressFamily::Packet => "link".to_owned(),
+        AddressFamily::Mpls => "mpls".to_owned(),
+        AddressFamily::Other(family) => family.to_u8().to_string(),
+    }
+}
+
+fn scope_name(scope: Scope) -> String {
+    match scope {
+        Scope::Universe => "global",
+        Scope::Site => "site",
+        Scope::Link => "link",
+        Scope::Host => "host",
+        Scope::Nowhere => "nowhere",
+    }.to_owned()
+}
+
+fn table_name(table: u32) -> String {
+    match table {
+        0 => "unspec".to_owned(),
+        253 => "default".to_owned(),
+        254 => "main".to_owned(),
+        255 => "local".to_owned(),
+        table => table.to_string(),
+    }
+}
+
+fn protocol_name(protocol: u8) -> String {
+    match protocol {
+        0 => "unspec",
+        1 => "redirect",
+        2 => "kernel",
+        3 => "boot",
+        4 => "static",
+        8 => "gated",
+        9 => "ra",
+        10 => "mrt",
+        11 => "zebra",
+        12 => "bird",
+        13 => "dnrouted",
+        14 => "xorp",
+        15 => "ntk",
+        16 => "dhcp",
+        17 => "mrouted",
+        18 => "keepalived",
+        42 => "babel",
+        186 => "bgp",
+        187 => "isis",
+        188 => "ospf",
+        189 => "rip",
+        192 => "eigrp",
+        protocol => return protocol.to_string(),
+    }.to_owned()
+}
+
+fn route_type_name(rtm_type: RtmType) -> &'static str {
+    match rtm_type {
+        RtmType::UNSPEC => "none",
+        RtmType::UNICAST => "unicast",
+        RtmType::LOCAL => "local",
+        RtmType::BROADCAST => "broadcast",
+        RtmType::ANYCAST => "anycast",
+        RtmType::MULTICAST => "multicast",
+        RtmType::BLACKHOLE => "blackhole",
+        RtmType::UNREACHABLE => "unreachable",
+        RtmType::PROHIBIT => "prohibit",
+        RtmType::THROW => "throw",
+        RtmType::NAT => "nat",
+        RtmType::XRESOLVE => "xresolve",
+    }
+}
+
+fn route_flags(nh_flags: RtnhFlags, flags: RtmFlags) -> Vec<String> {
+    let names = [
+        (nh_flags.contains(RtnhFlags::DEAD), "dead"),
+        (nh_flags.contains(RtnhFlags::ONLINK), "onlink"),
+        (nh_flags.contains(RtnhFlags::PERVASIVE), "pervasive"),
+        (nh_flags.contains(RtnhFlags::OFFLOAD), "offload"),
+        (flags.contains(RtmFlags::NOTIFY), "notify"),
+        (nh_flags.contains(RtnhFlags::LINKDOWN), "linkdown"),
+        (nh_flags.contains(RtnhFlags::UNRESOLVED), "unresolved"),
+    ];
+    names.iter().filter(|&&(set, _)| set).map(|&(_, name)| name.to_owned()).collect()
+}
+
+fn link_flags(flags: IfFlags) -> Vec<String> {
+    let mut names = vec![];
+    if flags.contains(IfFlags::UP) && !flags.contains(IfFlags::RUNNING) {
+        names.push("NO-CARRIER");
+    }
+    let known = [
+        (IfFlags::LOOPBACK, "LOOPBACK"),
+        (IfFlags::BROADCAST, "BROADCAST"),
+        (IfFlags::POINTOPOINT, "POINTOPOINT"),
+        (IfFlags::MULTICAST, "MULTICAST"),
+        (IfFlags::NOARP, "NOARP"),
+        (IfFlags::ALLMULTI, "ALLMULTI"),
+        (IfFlags::PROMISC, "PROMISC"),
+        (IfFlags::NOTRAILERS, "NOTRAILERS"),
+        (IfFlags::DEBUG, "DEBUG"),
+        (IfFlags::DYNAMIC, "DYNAMIC"),
+        (IfFlags::AUTOMEDIA, "AUTOMEDIA"),
+        (IfFlags::PORTSEL, "PORTSEL"),
+        (IfFlags::MASTER, "MASTER"),
+        (IfFlags::SLAVE, "SLAVE"),
+        (IfFlags::UP, "UP"),
+        (IfFlags::LOWER_UP, "LOWER_UP"),
+        (IfFlags::DORMANT, "DORMANT"),
+        (IfFlags::ECHO, "ECHO"),
+    ];
+    for &(flag, name) in known.iter() {
+        if flags.contains(flag) {
+            names.push(name);
+        }
+    }
+    names.into_iter().map(|name| name.to_owned()).collect()
+}
+
+fn neighbour_state(state: NeighbourState) -> Vec<String> {
+    let known = [
+        (NeighbourState::INCOMPLETE, "INCOMPLETE"),
+        (NeighbourState::REACHABLE, "REACHABLE"),
+        (NeighbourState::STALE, "STALE"),
+        (NeighbourState::DELAY, "DELAY"),
+        (NeighbourState::PROBE, "PROBE"),
+        (NeighbourState::FAILED, "FAILED"),
+        (NeighbourState::NOARP, "NOARP"),
+        (NeighbourState::PERMANENT, "PERMANENT"),
+    ];
+    known.iter().filter(|&&(flag, _)| state.contains(flag)).map(|&(_, name)| name.to_owned()).collect()
+}
+
+fn operstate_name(state: OperState) -> &'static str {
+    match state {
+        OperState::Unknown => "UNKNOWN",
+        OperState::NotPresent => "NOTPRESENT",
+        OperState::Down => "DOWN",
+        OperState::LowerLayerDown => "LOWERLAYERDOWN",
+        OperState::Testing => "TESTING",
+        OperState::Dormant => "DORMANT",
+        OperState::Up => "UP",
+    }
+}
+
+fn link_type_name(type_: IfType) -> &'static str {
+    match type_ {
+        IfType::Generic => "netrom",
+        IfType::Ether => "ether",
+        IfType::Eether => "eether",
+        IfType::Ax25 => "ax25",
+        IfType::Ieee802 => "ieee802",
+        IfType::Arcnet => "arcnet",
+        IfType::Atm => "atm",
+        IfType::Ieee1394 => "ieee1394",
+        IfType::Infiniband => "infiniband",
+        IfType::Slip => "slip",
+        IfType::Can => "can",
+        IfType::Ppp => "ppp",
+        IfType::Hdlc => "hdlc",
+        IfType::RawIp => "rawip",
+        IfType::Tunnel => "ipip",
+        IfType::Tunnel6 => "tunnel6",
+        IfType::Loopback => "loopback",
+        IfType::Fddi => "fddi",
+        IfType::Sit => "sit",
+        IfType::IpGre => "gre",
+        IfType::Ieee80211 => "ieee802.11",
+        IfType::Ieee80211Prism => "ieee802.11/prism",
+        IfType::Ieee80211Radiotap => "ieee802.11/radiotap",
+        IfType::Ieee802154 => "ieee802.15.4",
+        IfType::Ip6Gre => "gre6",
+        IfType::Netlink => "netlink",
+        IfType::SixLowpan => "6lowpan",
+        IfType::None => "none",
+        IfType::Void => "void",
+    }
+}
+
+mod tests {
+    #[test]
+    fn link_snapshot_layout() {
+        use serde_json;
+        use crate::packet::route::snapshot::LinkSnapshot;
+
+        let lo = LinkSnapshot {
+            ifindex: 1,
+            ifname: "lo".to_owned(),
+            flags: vec!["LOOPBACK".to_owned(), "UP".to_owned(), "LOWER_UP".to_owned()],
+            mtu: Some(65536),
+            qdisc: Some("noqueue".to_owned()),
+            operstate: "UNKNOWN".to_owned(),
+            linkmode: Some("DEFAULT".to_owned()),
+            group: "default".to_owned(),
+            txqlen: Some(1000),
+            link_type: "loopback".to_owned(),
+            address: Some("00:00:00:00:00:00".to_owned()),
+            broadcast: Some("00:00:00:00:00:00".to_owned()),
+            ..Default::default()
+        };
+        let json = r#"{"ifindex":1,"ifname":"lo","flags":["LOOPBACK","UP","LOWER_UP"],"mtu":65536,"qdisc":"noqueue","operstate":"UNKNOWN","linkmode":"DEFAULT","group":"default","txqlen":1000,"link_type":"loopback","address":"00:00:00:00:00:00","broadcast":"00:00:00:00:00:00"}"#;
+        assert_eq!(serde_json::to_string(&lo).unwrap(), json);
+        assert_eq!(serde_json::from_str::<LinkSnapshot>(json).unwrap(), lo);
+    }
+
+    #[test]
+    fn route_snapshot_layout() {
+        use serde_json;
+        use crate::packet::route::snapshot::RouteSnapshot;
+
+        let route = RouteSnapshot {
+            type_: Some("local".to_owned()),
+            dst: "127.0.0.1".to_owned(),
+            dev: Some("lo".to_owned()),
+            table: Some("local".to_owned()),
+            protocol: Some("kernel".to_owned()),
+            scope: Some("host".to_owned()),
+            prefsrc: Some("127.0.0.1".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(serde_json::to_string(&route).unwrap(),
+                   r#"{"type":"local","dst":"127.0.0.1","dev":"lo","table":"local","protocol":"kernel","scope":"host","prefsrc":"127.0.0.1","flags":[]}"#);
+    }
+
+    #[test]
+    fn null_flags() {
+        use serde_json;
+        use crate::packet::route::snapshot::{NeighbourSnapshot,RuleSnapshot};
+
+        let neigh = NeighbourSnapshot {
+            dst: "192.0.2.1".to_owned(),
+            dev: Some("eth0".to_owned()),
+            router: true,
+            state: vec!["REACHABLE".to_owned()],
+            ..Default::default()
+        };
+        let json = r#"{"dst":"192.0.2.1","dev":"eth0","router":null,"state":["REACHABLE"]}"#;
+        assert_eq!(serde_json::to_string(&neigh).unwrap(), json);
+        assert_eq!(serde_json::from_str::<NeighbourSnapshot>(json).unwrap(), neigh);
+
+        let rule = RuleSnapshot { priority: 100, not: true, src: "all".to_owned(), ..Default::default() };
+        let json = r#"{"priority":100,"not":null,"src":"all"}"#;
+        assert_eq!(serde_json::to_string(&rule).unwrap(), json);
+        assert_eq!(serde_json::from_str::<RuleSnapshot>(json).unwrap(), rule);
+        let rule = serde_json::from_str::<RuleSnapshot>(r#"{"priority":100,"src":"all"}"#).unwrap();
+        assert!(!rule.not);
+    }
+
+    #[test]
+    fn prefix_format() {
+        use std::net::{IpAddr,Ipv4Addr};
+        use crate::packet::route::AddressFamily;
+        use crate::packet::route::snapshot::prefix;
+
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0));
+        assert_eq!(prefix(AddressFamily::Inet, Some(ip), 8), Some("10.0.0.0/8".to_owned()));
+        assert_eq!(prefix(AddressFamily::Inet, Some(ip), 32), Some("10.0.0.0".to_owned()));
+        assert_eq!(prefix(AddressFamily::Inet, None, 0), None);
+    }
+
+    #[test]
+    fn dump_snapshots() {
+        use serde_json;
+        use std::collections::HashMap;
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::Links;
+        use crate::packet::route::addr::Addresses;
+        use crate::packet::route::neighbour::Neighbours;
+        use crate::packet::route::route::Route;
+        use crate::packet::route::rule::Rule;
+        use crate::packet::route::snapshot::{AddrSnapshot,NeighbourSnapshot,RouteSnapshot,RuleSnapshot};
+
+        fn round_trip<T>(snapshot: T) where T: ::serde::Serialize + ::serde::de::DeserializeOwned + PartialEq + ::std::fmt::Debug {
+            let json = serde_json::to_string(&snapshot).unwrap();
+            assert_eq!(serde_json::from_str::<T>(&json).unwrap(), snapshot, "{}", json);
+        }
+
+        let mut conn = NetlinkConnection::new();
+        let links = conn.iter_links().unwrap().collect::<Vec<_>>();
+        let names = links.iter().map(|link| (link.get_index(), link.get_name().unwrap())).collect::<HashMap<_,_>>();
+        let addrs = conn.iter_addrs(None).unwrap().collect::<Vec<_>>();
+        let lo = links.iter().find(|link| link.get_name().as_ref().map(|name| &name[..]) == Some("lo")).unwrap();
+        let snapshot = AddrSnapshot::new(lo, &addrs, |idx| names.get(&idx).cloned());
+        assert!(snapshot.addr_info.iter().any(|addr| addr.local.as_ref().map(|ip| &ip[..]) == Some("127.0.0.1")));
+        for link in links.iter() {
+            round_trip(AddrSnapshot::new(link, &addrs, |idx| names.get(&idx).cloned()));
+        }
+        for route in Route::iter_routes(&mut conn) {
+            round_trip(RouteSnapshot::new(&route, |idx| names.get(&idx).cloned()));
+        }
+        let rules = Rule::iter_rules(&mut conn).map(|rule| RuleSnapshot::new(&rule)).collect::<Vec<_>>();
+        assert!(rules.iter().any(|rule| rule.table.as_ref().map(|table| &table[..]) == Some("main")));
+        for rule in rules {
+            round_trip(rule);
+        }
+        let neighbours = conn.iter_neighbours(None).unwrap().collect::<Vec<_>>();
+        for neigh in neighbours.iter() {
+            round_trip(NeighbourSnapshot::new(neigh, |idx| names.get(&idx).cloned()));
+        }
+    }
+}
@@ -0,0 +1,472 @@
+//! In-memory mirror of the kernel network configuration
+//!
+//! `NetworkState` holds owned copies of links, addresses, routes, neighbours
+//! and rules and keeps them current by applying `RtnlEvent`s. Feeding it from
+//! a `ResyncIterator` (or the async `EventStream::resync`) gives the initial
+//! dump, incremental updates and recovery from overflows in one go:
+//!
+//! ```no_run
+//! use pnetlink::packet::route::event::EventSet;
+//! use pnetlink::packet::route::resync::ResyncIterator;
+//! use pnetlink::packet::route::state::{NetworkState,StateChange};
+//!
+//! let mut state = NetworkState::new();
+//! state.on_change(|change| if let StateChange::Link(ref change) = *change {
+//!     println!("{:?}", change);
+//! });
+//! for event in ResyncIterator::new(EventSet::all()).unwrap() {
+//!     state.apply(&event.unwrap());
+//!     println!("eth0: {:?}", state.link_by_name("eth0").map(|link| link.index));
+//! }
+//! ```
+use crate::packet::netlink::NetlinkConnection;
+use crate::packet::route::event::{EventSet,RtnlEvent};
+use crate::packet::route::addr::AddrInfo;
+use crate::packet::route::link::LinkInfo;
+use crate::packet::route::neighbour::NeighbourInfo;
+use crate::packet::route::prefix::IpPrefix;
+use crate::packet::route::resync::{self,AddrKey,Dump,NeighbourKey,RouteKey};
+use crate::packet::route::route::RouteInfo;
+use crate::packet::route::rule::RuleInfo;
+
+use std::collections::{HashMap,HashSet};
+use std::hash::Hash;
+use std::io;
+use std::net::IpAddr;
+
+/// Change of a single object
+#[derive(Debug,Clone,PartialEq)]
+pub enum Change<T> {
+    Added(T),
+    /// Old and new value
+    Changed(T, T),
+    Removed(T),
+}
+
+/// Change reported to `NetworkState::on_change` callbacks
+#[derive(Debug,Clone,PartialEq)]
+pub enum StateChange {
+    Link(Change<LinkInfo>),
+    Addr(Change<AddrInfo>),
+    Route(Change<RouteInfo>),
+    Neighbour(Change<NeighbourInfo>),
+    Rule(Change<RuleInfo>),
+}
+
+type Callback = Box<dyn FnMut(&StateChange) + Send>;
+
+/// Secondary index: keys of the objects sharing a link, table, ...
+type Index<I, K> = HashMap<I, HashSet<K>>;
+
+/// Mirror of links, addresses, routes, neighbours and rules
+#[derive(Default)]
+pub struct NetworkState {
+    links: HashMap<u32, LinkInfo>,
+    names: HashMap<String, u32>,
+    addrs: HashMap<AddrKey, AddrInfo>,
+    addrs_by_link: Index<u32, AddrKey>,
+    routes: HashMap<RouteKey, RouteInfo>,
+    routes_by_table: Index<u32, RouteKey>,
+    /// by output link
+    routes_by_link: Index<u32, RouteKey>,
+    /// by table and destination, host bits cleared
+    routes_by_prefix: Index<(u32, IpPrefix), RouteKey>,
+    neighbours: HashMap<NeighbourKey, NeighbourInfo>,
+    neighbours_by_link: Index<u32, NeighbourKey>,
+    rules: HashMap<RuleInfo, RuleInfo>,
+    callbacks: Vec<Callback>,
+}
+
+fn upsert<K: Hash + Eq, T: Clone + PartialEq>(table: &mut HashMap<K, T>, key: K, new: T) -> Option<Change<T>> {
+    match table.insert(key, new.clone()) {
+        None => Some(Change::Added(new)),
+        Some(ref old) if *old == new => None,
+        Some(old) => Some(Change::Changed(old, new)),
+    }
+}
+
+fn remove<K: Hash + Eq, T>(table: &mut HashMap<K, T>, key: &K) -> Option<Change<T>> {
+    table.remove(key).map(Change::Removed)
+}
+
+fn index_add<I: Hash + Eq, K: Hash + Eq>(index: &mut Index<I, K>, id: I, key: K) {
+    index.entry(id).or_insert_with(HashSet::new).insert(key);
+}
+
+fn index_remove<I: Hash + Eq, K: Hash + Eq>(index: &mut Index<I, K>, id: &I, key: &K) {
+    let empty = match index.get_mut(id) {
+        Some(keys) => {
+            keys.remove(key);
+            keys.is_empty()
+        },
+        None => false,
+    };
+    if empty {
+        index.remove(id);
+    }
+}
+
+/// Objects of `table` listed in `index` under `id`
+fn indexed<'a, I: Hash + Eq, K: Hash + Eq, T>(table: &'a HashMap<K, T>, index: &'a Index<I, K>, id: &I) -> Vec<&'a T> {
+    index.get(id).map_or(vec![], |keys| keys.iter().filter_map(|key| table.get(key)).collect())
+}
+
+fn route_prefix(route: &RouteInfo) -> Option<IpPrefix> {
+    route.destination_prefix().map(|prefix| prefix.trunc())
+}
+
+impl NetworkState {
+    /// Create empty state
+    pub fn new() -> Self {
+        NetworkState::default()
+    }
+
+    /// Create state from a dump of all tables on `conn`
+    pub fn load(conn: &mut NetlinkConnection) -> io::Result<Self> {
+        let mut state = NetworkState::new();
+        state.sync(Dump::new(conn, EventSet::all())?);
+        Ok(state)
+    }
+
+    /// Call `callback` for every change applied from now on
+    pub fn on_change<F>(&mut self, callback: F) where F: FnMut(&StateChange) + Send + 'static {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Apply notification, returning the resulting change if any
+    pub fn apply(&mut self, event: &RtnlEvent) -> Option<StateChange> {
+        let change = match *event {
+            RtnlEvent::LinkAdded(ref link) => self.upsert_link(resync::link_info(link)).map(StateChange::Link),
+            RtnlEvent::LinkRemoved(ref link) => {
+                // dependent objects are reported through the callbacks only
+                let changes = self.apply_link_removal(link.get_index());
+                return changes.into_iter().find(|change| match *change {
+                    StateChange::Link(_) => true,
+                    _ => false,
+                });
+            },
+            RtnlEvent::AddrAdded(ref addr) => self.upsert_addr(resync::addr_info(addr)).map(StateChange::Addr),
+            RtnlEvent::AddrRemoved(ref addr) => {
+                self.remove_addr(&resync::addr_key(&resync::addr_info(addr))).map(StateChange::Addr)
+            },
+            RtnlEvent::RouteAdded(ref route) => self.upsert_route(RouteInfo::from(route)).map(StateChange::Route),
+            RtnlEvent::RouteRemoved(ref route) => {
+                self.remove_route(&resync::route_key(&RouteInfo::from(route))).map(StateChange::Route)
+            },
+            RtnlEvent::NeighChanged(ref neighbour) => {
+                self.upsert_neighbour(NeighbourInfo::from(neighbour)).map(StateChange::Neighbour)
+            },
+            RtnlEvent::NeighRemoved(ref neighbour) => {
+                self.remove_neighbour(&resync::neighbour_key(&NeighbourInfo::from(neighbour))).map(StateChange::Neighbour)
+            },
+            RtnlEvent::RuleAdded(ref rule) => {
+                let info = RuleInfo::from(rule);
+                upsert(&mut self.rules, info.clone(), info).map(StateChange::Rule)
+            },
+            RtnlEvent::RuleRemoved(ref rule) => remove(&mut self.rules, &RuleInfo::from(rule)).map(StateChange::Rule),
+        };
+        self.notify_opt(change)
+    }
+
+    /// Replace state with `dump`, returning the differences as changes
+    pub fn sync(&mut self, dump: Dump) -> Vec<StateChange> {
+        let mut changes = vec![];
+        let links: HashSet<u32> = dump.links.iter().map(|link| link.get_index()).collect();
+        let addrs: HashSet<AddrKey> = dump.addrs.iter().map(|addr| resync::addr_key(&resync::addr_info(addr))).collect();
+        let routes: HashSet<RouteKey> = dump.routes.iter().map(|route| resync::route_key(&RouteInfo::from(route))).collect();
+        let neighbours: HashSet<NeighbourKey> = dump.neighbours.iter()
+            .map(|neighbour| resync::neighbour_key(&NeighbourInfo::from(neighbour))).collect();
+        let rules: HashSet<RuleInfo> = dump.rules.iter().map(RuleInfo::from).collect();
+
+        // removals first, so that a reused index does not merge two objects
+        let stale: Vec<_> = self.addrs.keys().filter(|key| !addrs.contains(*key)).cloned().collect();
+        for key in stale {
+            let change = self.remove_addr(&key).map(StateChange::Addr);
+            changes.extend(self.notify_opt(change));
+        }
+        let stale: Vec<_> = self.routes.keys().filter(|key| !routes.contains(*key)).cloned().collect();
+        for key in stale {
+            let change = self.remove_route(&key).map(StateChange::Route);
+            changes.extend(self.notify_opt(change));
+        }
+        let stale: Vec<_> = self.neighbours.keys().filter(|key| !neighbours.contains(*key)).cloned().collect();
+        for key in stale {
+            let change = self.remove_neighbour(&key).map(StateChange::Neighbour);
+            changes.extend(self.notify_opt(change));
+        }
+        let stale: Vec<_> = self.rules.keys().filter(|key| !rules.contains(*key)).cloned().collect();
+        for key in stale {
+            let change = remove(&mut self.rules, &key).map(StateChange::Rule);
+            changes.extend(self.notify_opt(change));
+        }
+        let stale: Vec<_> = self.links.keys().filter(|index| !links.contains(*index)).cloned().collect();
+        for index in stale {
+            changes.extend(self.apply_link_removal(index));
+        }
+
+        for event in dump.into_events() {
+            changes.extend(self.apply(&event));
+        }
+        changes
+    }
+
+    fn notify_opt(&mut self, change: Option<StateChange>) -> Option<StateChange> {
+        if let Some(ref change) = change {
+            self.notify(change);
+        }
+        change
+    }
+
+    /// Remove link and everything referring to it, the kernel does not
+    /// always announce the routes going away with it
+    fn apply_link_removal(&mut self, index: u32) -> Vec<StateChange> {
+        let mut changes = vec![];
+        let addrs: Vec<_> = self.addrs_by_link.get(&index).into_iter().flatten().cloned().collect();
+        for key in addrs {
+            let change = self.remove_addr(&key).map(StateChange::Addr);
+            changes.extend(self.notify_opt(change));
+        }
+        let routes: Vec<_> = self.routes_by_link.get(&index).into_iter().flatten().cloned().collect();
+        for key in routes {
+            let change = self.remove_route(&key).map(StateChange::Route);
+            changes.extend(self.notify_opt(change));
+        }
+        let neighbours: Vec<_> = self.neighbours_by_link.get(&index).into_iter().flatten().cloned().collect();
+        for key in neighbours {
+            let change = self.remove_neighbour(&key).map(StateChange::Neighbour);
+            changes.extend(self.notify_opt(change));
+        }
+        let change = self.remove_link(index).map(StateChange::Link);
+        changes.extend(self.notify_opt(change));
+        changes
+    }
+
+    fn notify(&mut self, change: &StateChange) {
+        for callback in self.callbacks.iter_mut() {
+            callback(change);
+        }
+    }
+
+    fn upsert_link(&mut self, info: LinkInfo) -> Option<Change<LinkInfo>> {
+        if let Some(old) = self.links.get(&info.index) {
+            // renamed
+            if old.name != info.name {
+                if let Some(ref name) = old.name {
+                    self.names.remove(name);
+                }
+            }
+        }
+        if let Some(ref name) = info.name {
+            self.names.insert(name.clone(), info.index);
+        }
+        let index = info.index;
+        upsert(&mut self.links, index, info)
+    }
+
+    // keys hold the indexed fields, so a changed object stays in its index entries
+
+    fn upsert_addr(&mut self, info: AddrInfo) -> Option<Change<AddrInfo>> {
+        let key = resync::addr_key(&info);
+        index_add(&mut self.addrs_by_link, info.index, key.clone());
+        upsert(&mut self.addrs, key, info)
+    }
+
+    fn remove_addr(&mut self, key: &AddrKey) -> Option<Change<AddrInfo>> {
+        index_remove(&mut self.addrs_by_link, &key.0, key);
+        remove(&mut self.addrs, key)
+    }
+
+    fn upsert_route(&mut self, info: RouteInfo) -> Option<Change<RouteInfo>> {
+        let key = resync::route_key(&info);
+        index_add(&mut self.routes_by_table, info.table, key.clone());
+        if let Some(oif) = info.oif {
+            index_add(&mut self.routes_by_link, oif, key.clone());
+        }
+        if let Some(prefix) = route_prefix(&info) {
+            index_add(&mut self.routes_by_prefix, (info.table, prefix), key.clone());
+        }
+        upsert(&mut self.routes, key, info)
+    }
+
+    fn remove_route(&mut self, key: &RouteKey) -> Option<Change<RouteInfo>> {
+        let change = remove(&mut self.routes, key);
+        if let Some(Change::Removed(ref route)) = change {
+            index_remove(&mut self.routes_by_table, &route.table, key);
+            if let Some(oif) = route.oif {
+                index_remove(&mut self.routes_by_link, &oif, key);
+            }
+            if let Some(prefix) = route_prefix(route) {
+                index_remove(&mut self.routes_by_prefix, &(route.table, prefix), key);
+            }
+        }
+        change
+    }
+
+    fn upsert_neighbour(&mut self, info: NeighbourInfo) -> Option<Change<NeighbourInfo>> {
+        let key = resync::neighbour_key(&info);
+        index_add(&mut self.neighbours_by_link, info.ifindex, key.clone());
+        upsert(&mut self.neighbours, key, info)
+    }
+
+    fn remove_neighbour(&mut self, key: &NeighbourKey) -> Option<Change<NeighbourInfo>> {
+        index_remove(&mut self.neighbours_by_link, &key.0, key);
+        remove(&mut self.neighbours, key)
+    }
+
+    fn remove_link(&mut self, index: u32) -> Option<Change<LinkInfo>> {
+        let change = remove(&mut self.links, &index);
+        if let Some(Change::Removed(ref info)) = change {
+            if let Some(ref name) = info.name {
+                if self.names.get(name) == Some(&index) {
+                    self.names.remove(name);
+                }
+            }
+        }
+        change
+    }
+
+    /// Get link by index
+    pub fn link(&self, index: u32) -> Option<&LinkInfo> {
+        self.links.get(&index)
+    }
+
+    /// Get link by name
+    pub fn link_by_name(&self, name: &str) -> Option<&LinkInfo> {
+        self.names.get(name).and_then(|index| self.links.get(index))
+    }
+
+    /// Iterate over links
+    pub fn links(&self) -> impl Iterator<Item = &LinkInfo> {
+        self.links.values()
+    }
+
+    /// Iterate over addresses
+    pub fn addrs(&self) -> impl Iterator<Item = &AddrInfo> {
+        self.addrs.values()
+    }
+
+    /// Addresses of link with index `index`
+    pub fn addrs_of(&self, index: u32) -> Vec<&AddrInfo> {
+        indexed(&self.addrs, &self.addrs_by_link, &index)
+    }
+
+    /// Iterate over routes
+    pub fn routes(&self) -> impl Iterator<Item = &RouteInfo> {
+        self.routes.values()
+    }
+
+    /// Routes in `table`
+    pub fn routes_in(&self, table: u32) -> Vec<&RouteInfo> {
+        indexed(&self.routes, &self.routes_by_table, &table)
+    }
+
+    /// Routes via link with index `index`
+    pub fn routes_via(&self, index: u32) -> Vec<&RouteInfo> {
+        indexed(&self.routes, &self.routes_by_link, &index)
+    }
+
+    /// Routes in `table` with destination `prefix`, host bits ignored
+    ///
+    /// There may be several, e.g. with different metrics or output links.
+    pub fn routes_to(&self, table: u32, prefix: IpPrefix) -> Vec<&RouteInfo> {
+        indexed(&self.routes, &self.routes_by_prefix, &(table, prefix.trunc()))
+    }
+
+    /// Longest prefix match for `addr` in `table`, lowest metric first
+    pub fn lookup(&self, table: u32, addr: IpAddr) -> Option<&RouteInfo> {
+        let host = IpPrefix::host(addr);
+        (0..=host.len()).rev()
+            .filter_map(|len| IpPrefix::new(addr, len).ok())
+            .filter_map(|prefix| self.routes_by_prefix.get(&(table, prefix.trunc())))
+            .filter_map(|keys| {
+                keys.iter().filter_map(|key| self.routes.get(key)).min_by_key(|route| route.priority.unwrap_or(0))
+            })
+            .next()
+    }
+
+    /// Iterate over neighbours
+    pub fn neighbours(&self) -> impl Iterator<Item = &NeighbourInfo> {
+        self.neighbours.values()
+    }
+
+    /// Neighbours on link with index `index`
+    pub fn neighbours_of(&self, index: u32) -> Vec<&NeighbourInfo> {
+        indexed(&self.neighbours, &self.neighbours_by_link, &index)
+    }
+
+    /// Iterate over rules
+    pub fn rules(&self) -> impl Iterator<Item = &RuleInfo> {
+        self.rules.values()
+    }
+}
+
+mod tests {
+    #[test]
+    fn apply_and_query() {
+        use crate::packet::netlink::{NetlinkConnection,NetlinkMsgFlags};
+        use crate::packet::route::event::RtnlEvent;
+        use crate::packet::route::link::{Link,LinkInfo,Links,RTM_NEWLINK,RTM_DELLINK};
+        use crate::packet::route::route::{Route,RouteInfo,RTM_NEWROUTE,RT_TABLE_MAIN};
+        use crate::packet::route::state::{Change,NetworkState,StateChange};
+        use std::sync::{Arc,Mutex};
+
+        let link = |info: &LinkInfo, kind| Link::from_packet(info.to_request(kind, NetlinkMsgFlags::empty())).unwrap();
+        let route = |prefix: &str, oif| {
+            let mut info = RouteInfo::new(prefix.parse().unwrap());
+            info.oif = oif;
+            Route::from_packet(info.to_request(RTM_NEWROUTE, NetlinkMsgFlags::empty())).unwrap()
+        };
+        let mut state = NetworkState::new();
+        let changes = Arc::new(Mutex::new(vec![]));
+        let seen = changes.clone();
+        state.on_change(move |change| seen.lock().unwrap().push(change.clone()));
+
+        let mut conn = NetlinkConnection::new();
+        let mut info = LinkInfo::from(conn.get_link_by_name("lo").unwrap().unwrap());
+        info.index = 42;
+        info.name = Some("pnl-state0".to_owned());
+        state.apply(&RtnlEvent::LinkAdded(link(&info, RTM_NEWLINK)));
+        assert_eq!(state.link_by_name("pnl-state0").map(|link| link.index), Some(42));
+        assert!(state.apply(&RtnlEvent::LinkAdded(link(&info, RTM_NEWLINK))).is_none());
+        info.name = Some("pnl-state1".to_owned());
+        state.apply(&RtnlEvent::LinkAdded(link(&info, RTM_NEWLINK)));
+        assert!(state.link_by_name("pnl-state0").is_none());
+        assert_eq!(state.link(42).and_then(|link| link.name.clone()), Some("pnl-state1".to_owned()));
+
+        state.apply(&RtnlEvent::RouteAdded(route("10.0.0.0/8", None)));
+        state.apply(&RtnlEvent::RouteAdded(route("10.1.0.0/16", None)));
+        let best = state.lookup(RT_TABLE_MAIN, "10.1.2.3".parse().unwrap()).unwrap();
+        assert_eq!(best.dst_len, 16);
+        assert_eq!(state.lookup(RT_TABLE_MAIN, "10.2.0.1".parse().unwrap()).unwrap().dst_len, 8);
+        assert!(state.lookup(RT_TABLE_MAIN, "192.0.2.1".parse().unwrap()).is_none());
+        assert_eq!(state.routes_to(RT_TABLE_MAIN, "10.0.0.0/8".parse().unwrap()).len(), 1);
+        assert_eq!(state.routes_in(RT_TABLE_MAIN).len(), 2);
+        // same prefix and metric on two links
+        state.apply(&RtnlEvent::RouteAdded(route("fe80::/64", Some(42))));
+        state.apply(&RtnlEvent::RouteAdded(route("fe80::/64", Some(43))));
+        assert_eq!(state.routes_to(RT_TABLE_MAIN, "fe80::/64".parse().unwrap()).len(), 2);
+        assert_eq!(state.routes_via(42).len(), 1);
+
+        state.apply(&RtnlEvent::LinkRemoved(link(&info, RTM_DELLINK)));
+        assert!(state.link(42).is_none());
+        assert!(state.routes_via(42).is_empty());
+        assert_eq!(state.routes_to(RT_TABLE_MAIN, "fe80::/64".parse().unwrap()).len(), 1);
+        let changes = changes.lock().unwrap();
+        assert_eq!(changes.len(), 8);
+        match changes[1] {
+            StateChange::Link(Change::Changed(ref old, ref new)) => assert!(old.name != new.name),
+            ref other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn load() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::state::NetworkState;
+
+        let mut conn = NetlinkConnection::new();
+        let state = NetworkState::load(&mut conn).unwrap();
+        let lo = state.link_by_name("lo").unwrap();
+        assert!(state.addrs_of(lo.index).len() > 0);
+    }
+}
@@ -0,0 +1,511 @@
+//! IP tunnel links: GRE, GRETAP, ERSPAN, IPIP, SIT, IP6TNL and their IPv6 variants
+//!
+//! GRE based kinds are configured with `IFLA_GRE_*` attributes, the others
+//! with `IFLA_IPTUN_*`; `Tunnel` covers both.
+//!
+//! # Example
+//! ```no_run
+//! use pnetlink::packet::netlink::NetlinkConnection;
+//! use pnetlink::packet::route::link::{Links,LinkBuilder};
+//! use pnetlink::packet::route::tunnel::{Tunnel,TunnelKind};
+//!
+//! let mut conn = NetlinkConnection::new();
+//! conn.new_link(&LinkBuilder::new("gre-site2").kind(Tunnel::new(TunnelKind::Gre)
+//!     .local("192.0.2.1".parse().unwrap()).remote("198.51.100.1".parse().unwrap())
+//!     .key(42).ttl(64)).up(true)).unwrap();
+//! ```
+use crate::packet::route::{AddressFamily,RtAttrIterator,RtAttrPacket};
+use crate::packet::route::addr::Addr;
+use crate::packet::route::link::{LinkKind,push_flag,push_opt};
+use byteorder::{ByteOrder,BigEndian,NativeEndian};
+use pnet::packet::Packet;
+use std::io;
+use std::net::IpAddr;
+
+pub const IFLA_GRE_UNSPEC: u16 = 0;
+pub const IFLA_GRE_LINK: u16 = 1;
+pub const IFLA_GRE_IFLAGS: u16 = 2;
+pub const IFLA_GRE_OFLAGS: u16 = 3;
+pub const IFLA_GRE_IKEY: u16 = 4;
+pub const IFLA_GRE_OKEY: u16 = 5;
+pub const IFLA_GRE_LOCAL: u16 = 6;
+pub const IFLA_GRE_REMOTE: u16 = 7;
+pub const IFLA_GRE_TTL: u16 = 8;
+pub const IFLA_GRE_TOS: u16 = 9;
+pub const IFLA_GRE_PMTUDISC: u16 = 10;
+pub const IFLA_GRE_ENCAP_LIMIT: u16 = 11;
+pub const IFLA_GRE_FLOWINFO: u16 = 12;
+pub const IFLA_GRE_FLAGS: u16 = 13;
+pub const IFLA_GRE_COLLECT_METADATA: u16 = 18;
+pub const IFLA_GRE_ERSPAN_INDEX: u16 = 21;
+pub const IFLA_GRE_ERSPAN_VER: u16 = 22;
+
+pub const IFLA_IPTUN_UNSPEC: u16 = 0;
+pub const IFLA_IPTUN_LINK: u16 = 1;
+pub const IFLA_IPTUN_LOCAL: u16 = 2;
+pub const IFLA_IPTUN_REMOTE: u16 = 3;
+pub const IFLA_IPTUN_TTL: u16 = 4;
+pub const IFLA_IPTUN_TOS: u16 = 5;
+pub const IFLA_IPTUN_ENCAP_LIMIT: u16 = 6;
+pub const IFLA_IPTUN_FLOWINFO: u16 = 7;
+pub const IFLA_IPTUN_FLAGS: u16 = 8;
+pub const IFLA_IPTUN_PROTO: u16 = 9;
+pub const IFLA_IPTUN_PMTUDISC: u16 = 10;
+pub const IFLA_IPTUN_COLLECT_METADATA: u16 = 19;
+
+/// GRE header flags, network byte order in `IFLA_GRE_[IO]FLAGS`
+pub const GRE_CSUM: u16 = 0x8000;
+pub const GRE_KEY: u16 = 0x2000;
+pub const GRE_SEQ: u16 = 0x1000;
+
+const IPV6_FLOWLABEL_MASK: u32 = 0x000f_ffff;
+
+/// Tunnel kind
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum TunnelKind {
+    Gre,
+    /// GRE carrying ethernet frames
+    GreTap,
+    Erspan,
+    IpIp,
+    /// IPv6 over IPv4
+    Sit,
+    /// IPv4 or IPv6 over IPv6
+    Ip6Tnl,
+    Ip6Gre,
+    Ip6GreTap,
+    Ip6Erspan,
+}
+
+impl TunnelKind {
+    /// `IFLA_INFO_KIND` value
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            TunnelKind::Gre => "gre",
+            TunnelKind::GreTap => "gretap",
+            TunnelKind::Erspan => "erspan",
+            TunnelKind::IpIp => "ipip",
+            TunnelKind::Sit => "sit",
+            TunnelKind::Ip6Tnl => "ip6tnl",
+            TunnelKind::Ip6Gre => "ip6gre",
+            TunnelKind::Ip6GreTap => "ip6gretap",
+            TunnelKind::Ip6Erspan => "ip6erspan",
+        }
+    }
+
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        Some(match kind {
+            "gre" => TunnelKind::Gre,
+            "gretap" => TunnelKind::GreTap,
+            "erspan" => TunnelKind::Erspan,
+            "ipip" => TunnelKind::IpIp,
+            "sit" => TunnelKind::Sit,
+            "ip6tnl" => TunnelKind::Ip6Tnl,
+            "ip6gre" => TunnelKind::Ip6Gre,
+            "ip6gretap" => TunnelKind::Ip6GreTap,
+            "ip6erspan" => TunnelKind::Ip6Erspan,
+            _ => return None,
+        })
+    }
+
+    /// Configured with `IFLA_GRE_*` rather than `IFLA_IPTUN_*`
+    pub fn is_gre(&self) -> bool {
+        match *self {
+            TunnelKind::IpIp | TunnelKind::Sit | TunnelKind::Ip6Tnl => false,
+            _ => true,
+        }
+    }
+
+    pub fn is_erspan(&self) -> bool {
+        *self == TunnelKind::Erspan || *self == TunnelKind::Ip6Erspan
+    }
+
+    /// Outer header is IPv6
+    pub fn is_ipv6(&self) -> bool {
+        match *self {
+            TunnelKind::Ip6Tnl | TunnelKind::Ip6Gre | TunnelKind::Ip6GreTap | TunnelKind::Ip6Erspan => true,
+            _ => false,
+        }
+    }
+}
+
+/// Tunnel options, unset ones keep the kernel defaults
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Tunnel {
+    kind: TunnelKind,
+    local: Option<IpAddr>,
+    remote: Option<IpAddr>,
+    link: Option<u32>,
+    ikey: Option<u32>,
+    okey: Option<u32>,
+    ttl: Option<u8>,
+    tos: Option<u8>,
+    pmtudisc: Option<bool>,
+    encap_limit: Option<u8>,
+    flowlabel: Option<u32>,
+    proto: Option<u8>,
+    external: bool,
+    erspan_version: Option<u8>,
+    erspan_index: Option<u32>,
+}
+
+impl Tunnel {
+    pub fn new(kind: TunnelKind) -> Self {
+        Tunnel {
+            kind: kind,
+            local: None,
+            remote: None,
+            link: None,
+            ikey: None,
+            okey: None,
+            ttl: None,
+            tos: None,
+            pmtudisc: None,
+            encap_limit: None,
+            flowlabel: None,
+            proto: None,
+            external: false,
+            erspan_version: None,
+            erspan_index: None,
+        }
+    }
+
+    pub fn local(mut self, local: IpAddr) -> Self {
+        self.local = Some(local);
+        self
+    }
+
+    pub fn remote(mut self, remote: IpAddr) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    /// Bind the encapsulated packets to link `index`
+    pub fn link(mut self, index: u32) -> Self {
+        self.link = Some(index);
+        self
+    }
+
+    /// Same GRE key in both directions
+    pub fn key(self, key: u32) -> Self {
+        self.ikey(key).okey(key)
+    }
+
+    /// GRE key expected on received packets
+    pub fn ikey(mut self, key: u32) -> Self {
+        self.ikey = Some(key);
+        self
+    }
+
+    /// GRE key put on sent packets, the ERSPAN session id
+    pub fn okey(mut self, key: u32) -> Self {
+        self.okey = Some(key);
+        self
+    }
+
+    /// 0 inherits from the inner packet
+    pub fn ttl(mut self, ttl: u8) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// 1 inherits from the inner packet
+    pub fn tos(mut self, tos: u8) -> Self {
+        self.tos = Some(tos);
+        self
+    }
+
+    /// Path MTU discovery, i.e. set DF on IPv4
+    pub fn pmtudisc(mut self, on: bool) -> Self {
+        self.pmtudisc = Some(on);
+        self
+    }
+
+    /// IPv6 tunnel encapsulation limit
+    pub fn encap_limit(mut self, limit: u8) -> Self {
+        self.encap_limit = Some(limit);
+        self
+    }
+
+    /// IPv6 flow label
+    pub fn flowlabel(mut self, label: u32) -> Self {
+        self.flowlabel = Some(label & IPV6_FLOWLABEL_MASK);
+        self
+    }
+
+    /// Inner protocol of ip6tnl and sit, 0 for any
+    pub fn proto(mut self, proto: u8) -> Self {
+        self.proto = Some(proto);
+        self
+    }
+
+    /// Collect metadata, i.e. endpoints are set per packet
+    pub fn external(mut self, on: bool) -> Self {
+        self.external = on;
+        self
+    }
+
+    /// ERSPAN version 1 with `index`
+    pub fn erspan_v1(mut self, index: u32) -> Self {
+        self.erspan_version = Some(1);
+        self.erspan_index = Some(index);
+        self
+    }
+
+    /// ERSPAN version 2
+    pub fn erspan_v2(mut self) -> Self {
+        self.erspan_version = Some(2);
+        self.erspan_index = None;
+        self
+    }
+
+    fn gre_info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        let mut data = vec![];
+        // ERSPAN always carries sequence numbers and the session id as key
+        let base = if self.kind.is_erspan() && !self.external { GRE_SEQ | GRE_KEY } else { 0 };
+        let iflags = base | if self.ikey.is_some() { GRE_KEY } else { 0 };
+        let oflags = base | if self.okey.is_some() { GRE_KEY } else { 0 };
+        push_opt(&mut data, IFLA_GRE_IFLAGS, Some(iflags.to_be()).filter(|_| iflags != 0));
+        push_opt(&mut data, IFLA_GRE_OFLAGS, Some(oflags.to_be()).filter(|_| oflags != 0));
+        push_opt(&mut data, IFLA_GRE_IKEY, self.ikey.map(|key| key.to_be()));
+        push_opt(&mut data, IFLA_GRE_OKEY, self.okey.map(|key| key.to_be()));
+        push_opt(&mut data, IFLA_GRE_ERSPAN_VER, self.erspan_version);
+        push_opt(&mut data, IFLA_GRE_ERSPAN_INDEX, self.erspan_index);
+        data
+    }
+
+    fn iptun_info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        let mut data = vec![];
+        push_opt(&mut data, IFLA_IPTUN_PROTO, self.proto);
+        data
+    }
+}
+
+impl LinkKind for Tunnel {
+    fn kind(&self) -> &str {
+        self.kind.kind()
+    }
+
+    /// Check that the endpoints are of the outer header family
+    fn validate(&self) -> io::Result<()> {
+        for ip in [self.local, self.remote].iter().filter_map(|ip| *ip) {
+            if ip.is_ipv6() != self.kind.is_ipv6() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("{} does not match tunnel kind {}", ip, self.kind.kind())));
+            }
+        }
+        Ok(())
+    }
+
+    fn info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        // the attributes both families have only differ in their numbers
+        let (mut data, attrs) = if self.kind.is_gre() {
+            (self.gre_info_data(), [IFLA_GRE_LINK, IFLA_GRE_LOCAL, IFLA_GRE_REMOTE, IFLA_GRE_TTL, IFLA_GRE_TOS,
+                                    IFLA_GRE_PMTUDISC, IFLA_GRE_ENCAP_LIMIT, IFLA_GRE_FLOWINFO, IFLA_GRE_COLLECT_METADATA])
+        } else {
+            (self.iptun_info_data(), [IFLA_IPTUN_LINK, IFLA_IPTUN_LOCAL, IFLA_IPTUN_REMOTE, IFLA_IPTUN_TTL, IFLA_IPTUN_TOS,
+                                      IFLA_IPTUN_PMTUDISC, IFLA_IPTUN_ENCAP_LIMIT, IFLA_IPTUN_FLOWINFO, IFLA_IPTUN_COLLECT_METADATA])
+        };
+        let [link, local, remote, ttl, tos, pmtudisc, encap_limit, flowinfo, collect_metadata] = attrs;
+        push_opt(&mut data, link, self.link);
+        push_opt(&mut data, local, self.local);
+        push_opt(&mut data, remote, self.remote);
+        push_opt(&mut data, ttl, self.ttl);
+        push_opt(&mut data, tos, self.tos);
+        push_opt(&mut data, pmtudisc, self.pmtudisc.map(|on| on as u8));
+        push_opt(&mut data, encap_limit, self.encap_limit);
+        push_opt(&mut data, flowinfo, self.flowlabel.map(|label| label.to_be()));
+        push_flag(&mut data, collect_metadata, self.external);
+        data
+    }
+}
+
+/// Tunnel settings of an existing link
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub struct TunnelInfo {
+    pub kind: TunnelKind,
+    pub local: Option<IpAddr>,
+    pub remote: Option<IpAddr>,
+    /// Link the encapsulated packets are bound to
+    pub link: Option<u32>,
+    /// GRE key expected on received packets
+    pub ikey: Option<u32>,
+    /// GRE key put on sent packets, the ERSPAN session id
+    pub okey: Option<u32>,
+    pub ttl: Option<u8>,
+    pub tos: Option<u8>,
+    /// Path MTU discovery, i.e. set DF on IPv4
+    pub pmtudisc: Option<bool>,
+    /// IPv6 tunnel encapsulation limit
+    pub encap_limit: Option<u8>,
+    /// IPv6 flow label
+    pub flowlabel: Option<u32>,
+    /// Inner protocol of ip6tnl and sit, 0 for any
+    pub proto: Option<u8>,
+    /// Collect metadata, i.e. endpoints are set per packet
+    pub external: bool,
+    pub erspan_version: Option<u8>,
+    /// ERSPAN version 1 index
+    pub erspan_index: Option<u32>,
+}
+
+impl TunnelInfo {
+    /// Parse `IFLA_INFO_DATA` of a tunnel of `kind`
+    pub fn from_info_data(kind: TunnelKind, data: &[u8]) -> Self {
+        let mut tunnel = TunnelInfo {
+            kind: kind,
+            local: None,
+            remote: None,
+            link: None,
+            ikey: None,
+            okey: None,
+            ttl: None,
+            tos: None,
+            pmtudisc: None,
+            encap_limit: None,
+            flowlabel: None,
+            proto: None,
+            external: false,
+            erspan_version: None,
+            erspan_index: None,
+        };
+        let family = if kind.is_ipv6() { AddressFamily::Inet6 } else { AddressFamily::Inet };
+        let addr_len = if kind.is_ipv6() { 16 } else { 4 };
+        let (mut iflags, mut oflags) = (0, 0);
+        let (mut ikey, mut okey) = (None, None);
+        for rta in RtAttrIterator::new(data) {
+            let payload = rta.payload();
+            let ip = if payload.len() >= addr_len {
+                Some(Addr::ip_from_family_and_bytes(family, &payload[..addr_len]))
+            } else {
+                None
+            };
+            let u32_val = if payload.len() >= 4 { Some(NativeEndian::read_u32(payload)) } else { None };
+            let be32_val = if payload.len() >= 4 { Some(BigEndian::read_u32(payload)) } else { None };
+            let u8_val = payload.first().cloned();
+            match (kind.is_gre(), rta.get_rta_type()) {
+                (true, IFLA_GRE_LINK) | (false, IFLA_IPTUN_LINK) => tunnel.link = u32_val,
+                (true, IFLA_GRE_LOCAL) | (false, IFLA_IPTUN_LOCAL) => tunnel.local = ip,
+                (true, IFLA_GRE_REMOTE) | (false, IFLA_IPTUN_REMOTE) => tunnel.remote = ip,
+                (true, IFLA_GRE_TTL) | (false, IFLA_IPTUN_TTL) => tunnel.ttl = u8_val,
+                (true, IFLA_GRE_TOS) | (false, IFLA_IPTUN_TOS) => tunnel.tos = u8_val,
+                (true, IFLA_GRE_PMTUDISC) | (false, IFLA_IPTUN_PMTUDISC) => tunnel.pmtudisc = u8_val.map(|val| val != 0),
+                (true, IFLA_GRE_ENCAP_LIMIT) | (false, IFLA_IPTUN_ENCAP_LIMIT) => tunnel.encap_limit = u8_val,
+                (true, IFLA_GRE_FLOWINFO) | (false, IFLA_IPTUN_FLOWINFO) => {
+                    tunnel.flowlabel = be32_val.map(|val| val & IPV6_FLOWLABEL_MASK);
+                },
+                (true, IFLA_GRE_COLLECT_METADATA) | (false, IFLA_IPTUN_COLLECT_METADATA) => tunnel.external = true,
+                (false, IFLA_IPTUN_PROTO) => tunnel.proto = u8_val,
+                (true, IFLA_GRE_IFLAGS) if payload.len() >= 2 => iflags = BigEndian::read_u16(payload),
+                (true, IFLA_GRE_OFLAGS) if payload.len() >= 2 => oflags = BigEndian::read_u16(payload),
+                (true, IFLA_GRE_IKEY) => ikey = be32_val,
+                (true, IFLA_GRE_OKEY) => okey = be32_val,
+                (true, IFLA_GRE_ERSPAN_VER) => tunnel.erspan_version = u8_val,
+                (true, IFLA_GRE_ERSPAN_INDEX) => tunnel.erspan_index = u32_val,
+                _ => {},
+            }
+        }
+        // keys are reported as 0 when not in use
+        if iflags & GRE_KEY != 0 {
+            tunnel.ikey = ikey;
+        }
+        if oflags & GRE_KEY != 0 {
+            tunnel.okey = okey;
+        }
+        tunnel
+    }
+}
+
+mod tests {
+    #[test]
+    fn tunnel_encoding() {
+        use crate::packet::route::link::{Link,LinkBuilder};
+        use crate::packet::route::tunnel::{Tunnel,TunnelInfo,TunnelKind};
+
+        let info = |kind| TunnelInfo {
+            kind: kind,
+            local: None,
+            remote: None,
+            link: None,
+            ikey: None,
+            okey: None,
+            ttl: None,
+            tos: None,
+            pmtudisc: None,
+            encap_limit: None,
+            flowlabel: None,
+            proto: None,
+            external: false,
+            erspan_version: None,
+            erspan_index: None,
+        };
+        let (v4_local, v4_remote) = ("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap());
+        let (v6_local, v6_remote) = ("2001:db8::1".parse().unwrap(), "2001:db8::2".parse().unwrap());
+        let tunnels = vec![
+            (Tunnel::new(TunnelKind::Gre).local(v4_local).remote(v4_remote).ikey(1).okey(2).ttl(64).tos(1).pmtudisc(true).link(3),
+             TunnelInfo { local: Some(v4_local), remote: Some(v4_remote), ikey: Some(1), okey: Some(2), ttl: Some(64),
+                          tos: Some(1), pmtudisc: Some(true), link: Some(3), ..info(TunnelKind::Gre) }),
+            (Tunnel::new(TunnelKind::Ip6Gre).local(v6_local).remote(v6_remote).encap_limit(4).flowlabel(0xabcde),
+             TunnelInfo { local: Some(v6_local), remote: Some(v6_remote), encap_limit: Some(4), flowlabel: Some(0xabcde),
+                          ..info(TunnelKind::Ip6Gre) }),
+            (Tunnel::new(TunnelKind::Erspan).local(v4_local).remote(v4_remote).key(10).erspan_v1(7),
+             TunnelInfo { local: Some(v4_local), remote: Some(v4_remote), ikey: Some(10), okey: Some(10),
+                          erspan_version: Some(1), erspan_index: Some(7), ..info(TunnelKind::Erspan) }),
+            (Tunnel::new(TunnelKind::Ip6Tnl).external(true).proto(0),
+             TunnelInfo { external: true, proto: Some(0), ..info(TunnelKind::Ip6Tnl) }),
+            (Tunnel::new(TunnelKind::Sit).remote("192.0.2.9".parse().unwrap()).ttl(0),
+             TunnelInfo { remote: Some("192.0.2.9".parse().unwrap()), ttl: Some(0), ..info(TunnelKind::Sit) }),
+        ];
+        for (tunnel, expected) in tunnels {
+            let link = Link::from_packet(LinkBuilder::new("pnl-tun0").kind(tunnel).request().unwrap()).unwrap();
+            assert_eq!(link.get_kind(), Some(expected.kind.kind().to_owned()));
+            assert_eq!(link.get_tunnel(), Some(expected));
+        }
+        // endpoints must match the outer header family
+        assert!(LinkBuilder::new("pnl-tun1").kind(Tunnel::new(TunnelKind::Gre).remote(v6_remote)).request().is_err());
+        assert!(LinkBuilder::new("pnl-tun1").kind(Tunnel::new(TunnelKind::Ip6Tnl).local(v4_local)).request().is_err());
+        assert!(LinkBuilder::new("pnl-tun1").kind(Tunnel::new(TunnelKind::Sit).local(v4_local)).request().is_ok());
+    }
+
+    #[test]
+    // root permissions required
+    fn create_tunnels() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::{Links,LinkBuilder};
+        use crate::packet::route::tunnel::{Tunnel,TunnelKind};
+
+        let mut conn = NetlinkConnection::new();
+        let lo4 = "127.0.0.1".parse().unwrap();
+        let gre = Tunnel::new(TunnelKind::Gre).local(lo4).remote("127.0.0.2".parse().unwrap()).key(42).ttl(64);
+        conn.new_link(&LinkBuilder::new("pnl-gre0").kind(gre)).unwrap();
+        let ipip = Tunnel::new(TunnelKind::IpIp).local(lo4).remote("127.0.0.3".parse().unwrap());
+        conn.new_link(&LinkBuilder::new("pnl-ipip0").kind(ipip)).unwrap();
+        let ip6tnl = Tunnel::new(TunnelKind::Ip6Tnl).local("::1".parse().unwrap()).remote("fd00::2".parse().unwrap())
+            .encap_limit(2);
+        conn.new_link(&LinkBuilder::new("pnl-ip6tnl0").kind(ip6tnl)).unwrap();
+        let erspan = Tunnel::new(TunnelKind::Erspan).local(lo4).remote("127.0.0.4".parse().unwrap()).key(7).erspan_v1(3);
+        conn.new_link(&LinkBuilder::new("pnl-erspan0").kind(erspan)).unwrap();
+
+        let gre = conn.get_link_by_name("pnl-gre0").unwrap().unwrap();
+        let info = gre.get_tunnel().unwrap();
+        assert_eq!(info.kind, TunnelKind::Gre);
+        assert_eq!((info.local, info.remote), (Some(lo4), Some("127.0.0.2".parse().unwrap())));
+        assert_eq!((info.ikey, info.okey, info.ttl), (Some(42), Some(42), Some(64)));
+        let ipip = conn.get_link_by_name("pnl-ipip0").unwrap().unwrap();
+        let info = ipip.get_tunnel().unwrap();
+        assert_eq!(info.kind, TunnelKind::IpIp);
+        assert_eq!(info.remote, Some("127.0.0.3".parse().unwrap()));
+        assert_eq!((info.ikey, info.okey), (None, None));
+        let ip6tnl = conn.get_link_by_name("pnl-ip6tnl0").unwrap().unwrap();
+        let info = ip6tnl.get_tunnel().unwrap();
+        assert_eq!(info.kind, TunnelKind::Ip6Tnl);
+        assert_eq!(info.remote, Some("fd00::2".parse().unwrap()));
+        assert_eq!(info.encap_limit, Some(2));
+        let erspan = conn.get_link_by_name("pnl-erspan0").unwrap().unwrap();
+        let info = erspan.get_tunnel().unwrap();
+        assert_eq!((info.okey, info.erspan_index), (Some(7), Some(3)));
+        for link in vec![gre, ipip, ip6tnl, erspan] {
+            conn.delete_link(link).unwrap();
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Virtual ethernet pairs
+//!
+//! # Example
+//! ```no_run
+//! use std::fs::File;
+//! use std::os::unix::io::AsRawFd;
+//! use pnetlink::packet::netlink::NetlinkConnection;
+//! use pnetlink::packet::route::link::{Links,LinkBuilder,Netns};
+//! use pnetlink::packet::route::veth::Veth;
+//!
+//! let ns = File::open("/var/run/netns/container0").unwrap();
+//! let mut conn = NetlinkConnection::new();
+//! conn.new_link(&LinkBuilder::new("veth-host")
+//!     .kind(Veth::new("eth0").peer_mtu(1450).peer_netns(Netns::Fd(ns.as_raw_fd())))
+//!     .up(true)).unwrap();
+//! ```
+use crate::packet::route::RtAttrPacket;
+use crate::packet::route::link::{Link,LinkBuilder,LinkKind,Netns};
+use crate::packet::route::route::WithPayload;
+use pnet::util::MacAddr;
+
+pub const VETH_INFO_UNSPEC: u16 = 0;
+pub const VETH_INFO_PEER: u16 = 1;
+
+/// Veth options: the peer end of the pair
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Veth {
+    peer_name: String,
+    peer_mac: Option<MacAddr>,
+    peer_mtu: Option<u32>,
+    peer_netns: Option<Netns>,
+}
+
+impl Veth {
+    /// Pair with peer named `peer_name`
+    pub fn new(peer_name: &str) -> Self {
+        Veth {
+            peer_name: peer_name.to_owned(),
+            peer_mac: None,
+            peer_mtu: None,
+            peer_netns: None,
+        }
+    }
+
+    pub fn peer_mac(mut self, mac: MacAddr) -> Self {
+        self.peer_mac = Some(mac);
+        self
+    }
+
+    pub fn peer_mtu(mut self, mtu: u32) -> Self {
+        self.peer_mtu = Some(mtu);
+        self
+    }
+
+    /// Create the peer directly in namespace `netns`
+    pub fn peer_netns(mut self, netns: Netns) -> Self {
+        self.peer_netns = Some(netns);
+        self
+    }
+}
+
+impl LinkKind for Veth {
+    fn kind(&self) -> &str {
+        "veth"
+    }
+
+    fn info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        let mut peer = LinkBuilder::new(&self.peer_name);
+        if let Some(mac) = self.peer_mac {
+            peer = peer.mac(mac);
+        }
+        if let Some(mtu) = self.peer_mtu {
+            peer = peer.mtu(mtu);
+        }
+        if let Some(netns) = self.peer_netns {
+            peer = peer.netns(netns);
+        }
+        // the peer is described by a nested ifinfomsg with its attributes
+        vec![RtAttrPacket::create_with_payload(VETH_INFO_PEER, peer.build())]
+    }
+}
+
+/// Peer end of a veth as seen from the other end
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct VethPeer {
+    /// Interface index, in the namespace given by `netnsid`
+    pub index: u32,
+    /// Id of the peer's namespace, None if it is in the same one
+    pub netnsid: Option<i32>,
+}
+
+/// Get peer of veth `link`, None if it is not a veth
+pub fn peer(link: &Link) -> Option<VethPeer> {
+    if link.get_kind().as_ref().map(|kind| kind.as_str()) != Some("veth") {
+        return None;
+    }
+    link.get_link().map(|index| VethPeer { index: index, netnsid: link.get_link_netnsid() })
+}
+
+mod tests {
+    #[test]
+    fn veth_peer_encoding() {
+        use crate::packet::route::{IfInfoPacket,RtAttrIterator};
+        use crate::packet::route::link::{LinkKind,IFLA_IFNAME,IFLA_MTU};
+        use crate::packet::route::veth::{Veth,VETH_INFO_PEER};
+        use pnet::packet::Packet;
+
+        let data = Veth::new("peer0").peer_mtu(1450).info_data();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].get_rta_type(), VETH_INFO_PEER);
+        let ifi = IfInfoPacket::new(data[0].payload()).unwrap();
+        let attrs: Vec<u16> = RtAttrIterator::new(ifi.payload()).map(|rta| rta.get_rta_type()).collect();
+        assert!(attrs.contains(&IFLA_IFNAME));
+        assert!(attrs.contains(&IFLA_MTU));
+    }
+
+    #[test]
+    // root permissions required
+    fn create_veth_pair() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::{Links,LinkBuilder};
+        use crate::packet::route::veth::{self,Veth};
+
+        let mut conn = NetlinkConnection::new();
+        conn.new_link(&LinkBuilder::new("pnl-veth0").kind(Veth::new("pnl-veth1").peer_mtu(1450))).unwrap();
+        let veth0 = conn.get_link_by_name("pnl-veth0").unwrap().unwrap();
+        let veth1 = conn.get_link_by_name("pnl-veth1").unwrap().unwrap();
+        assert_eq!(veth1.get_mtu(), Some(1450));
+        assert_eq!(veth::peer(&veth0).map(|peer| peer.index), Some(veth1.get_index()));
+        assert_eq!(veth::peer(&veth1).map(|peer| peer.index), Some(veth0.get_index()));
+        conn.delete_link(veth0).unwrap();
+        assert!(conn.get_link_by_name("pnl-veth1").unwrap().is_none());
+    }
+}
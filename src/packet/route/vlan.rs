@@ -0,0 +1,270 @@
+//! 802.1Q and 802.1ad (QinQ) VLAN links
+//!
+//! # Example
+//! ```no_run
+//! use pnetlink::packet::netlink::NetlinkConnection;
+//! use pnetlink::packet::route::link::{Links,LinkBuilder};
+//! use pnetlink::packet::route::vlan::{Vlan,VlanProtocol};
+//!
+//! let mut conn = NetlinkConnection::new();
+//! let eth0 = conn.get_link_by_name("eth0").unwrap().unwrap();
+//! // outer service tag, then customer tag stacked on it
+//! conn.new_link(&LinkBuilder::new("eth0.100").parent(eth0.get_index())
+//!     .kind(Vlan::new(100).protocol(VlanProtocol::Ieee8021AD))).unwrap();
+//! let svlan = conn.get_link_by_name("eth0.100").unwrap().unwrap();
+//! conn.new_link(&LinkBuilder::new("eth0.100.200").parent(svlan.get_index())
+//!     .kind(Vlan::new(200).egress_map(0, 3))).unwrap();
+//! ```
+use crate::packet::route::{RtAttrIterator,RtAttrPacket};
+use crate::packet::route::link::{LinkKind,nested};
+use crate::packet::route::route::{ToPayload,WithPayload};
+use byteorder::{ByteOrder,BigEndian,NativeEndian};
+use pnet::packet::Packet;
+
+pub const IFLA_VLAN_UNSPEC: u16 = 0;
+pub const IFLA_VLAN_ID: u16 = 1;
+pub const IFLA_VLAN_FLAGS: u16 = 2;
+pub const IFLA_VLAN_EGRESS_QOS: u16 = 3;
+pub const IFLA_VLAN_INGRESS_QOS: u16 = 4;
+pub const IFLA_VLAN_PROTOCOL: u16 = 5;
+
+pub const IFLA_VLAN_QOS_UNSPEC: u16 = 0;
+pub const IFLA_VLAN_QOS_MAPPING: u16 = 1;
+
+pub const ETH_P_8021Q: u16 = 0x8100;
+pub const ETH_P_8021AD: u16 = 0x88a8;
+
+bitflags! {
+    pub struct VlanFlags: u32 {
+        /// Reorder the header so the link looks like plain ethernet
+        const REORDER_HDR = 0x1;
+        /// Register with GARP VLAN Registration Protocol
+        const GVRP = 0x2;
+        /// Operational state does not follow the parent's
+        const LOOSE_BINDING = 0x4;
+        /// Register with Multiple VLAN Registration Protocol
+        const MVRP = 0x8;
+        const BRIDGE_BINDING = 0x10;
+    }
+}
+
+/// Tag protocol
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum VlanProtocol {
+    /// 802.1Q customer tag
+    Ieee8021Q,
+    /// 802.1ad service tag, the outer tag of QinQ
+    Ieee8021AD,
+    Other(u16),
+}
+
+impl VlanProtocol {
+    /// Ethertype of the tag
+    pub fn ethertype(&self) -> u16 {
+        match *self {
+            VlanProtocol::Ieee8021Q => ETH_P_8021Q,
+            VlanProtocol::Ieee8021AD => ETH_P_8021AD,
+            VlanProtocol::Other(ethertype) => ethertype,
+        }
+    }
+
+    pub fn from_ethertype(ethertype: u16) -> Self {
+        match ethertype {
+            ETH_P_8021Q => VlanProtocol::Ieee8021Q,
+            ETH_P_8021AD => VlanProtocol::Ieee8021AD,
+            _ => VlanProtocol::Other(ethertype),
+        }
+    }
+}
+
+/// Priority mapping, (from, to) pairs
+pub type QosMap = Vec<(u32, u32)>;
+
+/// Vlan options, the parent link is given with `LinkBuilder::parent`
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Vlan {
+    id: u16,
+    protocol: Option<VlanProtocol>,
+    flags: VlanFlags,
+    mask: VlanFlags,
+    ingress: QosMap,
+    egress: QosMap,
+}
+
+impl Vlan {
+    /// Vlan with tag `id`, 802.1Q unless `protocol` says otherwise
+    pub fn new(id: u16) -> Self {
+        Vlan {
+            id: id,
+            protocol: None,
+            flags: VlanFlags::empty(),
+            mask: VlanFlags::empty(),
+            ingress: vec![],
+            egress: vec![],
+        }
+    }
+
+    pub fn protocol(mut self, protocol: VlanProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Set or clear `flags`, others keep the kernel defaults
+    pub fn flags(mut self, flags: VlanFlags, on: bool) -> Self {
+        self.flags.set(flags, on);
+        self.mask.insert(flags);
+        self
+    }
+
+    /// Map tag priority `from` to packet priority `to` on receive
+    pub fn ingress_map(mut self, from: u32, to: u32) -> Self {
+        self.ingress.push((from, to));
+        self
+    }
+
+    /// Map packet priority `from` to tag priority `to` on transmit
+    pub fn egress_map(mut self, from: u32, to: u32) -> Self {
+        self.egress.push((from, to));
+        self
+    }
+}
+
+fn qos_rta(kind: u16, map: &QosMap) -> RtAttrPacket<'static> {
+    let mappings: Vec<RtAttrPacket<'static>> = map.iter().map(|&(from, to)| {
+        let mapping: &[&dyn ToPayload] = &[&from, &to];
+        RtAttrPacket::create_with_payload(IFLA_VLAN_QOS_MAPPING, mapping)
+    }).collect();
+    nested(kind, &mappings)
+}
+
+impl LinkKind for Vlan {
+    fn kind(&self) -> &str {
+        "vlan"
+    }
+
+    fn info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        let mut data = vec![RtAttrPacket::create_with_payload(IFLA_VLAN_ID, self.id)];
+        if let Some(protocol) = self.protocol {
+            // network byte order, unlike the other attributes
+            data.push(RtAttrPacket::create_with_payload(IFLA_VLAN_PROTOCOL, protocol.ethertype().to_be()));
+        }
+        if !self.mask.is_empty() {
+            let flags: &[&dyn ToPayload] = &[&self.flags.bits(), &self.mask.bits()];
+            data.push(RtAttrPacket::create_with_payload(IFLA_VLAN_FLAGS, flags));
+        }
+        if !self.ingress.is_empty() {
+            data.push(qos_rta(IFLA_VLAN_INGRESS_QOS, &self.ingress));
+        }
+        if !self.egress.is_empty() {
+            data.push(qos_rta(IFLA_VLAN_EGRESS_QOS, &self.egress));
+        }
+        data
+    }
+}
+
+/// Vlan settings of an existing link
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub struct VlanInfo {
+    pub id: u16,
+    pub protocol: VlanProtocol,
+    pub flags: VlanFlags,
+    /// Tag priority to packet priority, only non-zero entries
+    pub ingress_qos: QosMap,
+    /// Packet priority to tag priority
+    pub egress_qos: QosMap,
+}
+
+fn parse_qos(payload: &[u8]) -> QosMap {
+    RtAttrIterator::new(payload)
+        .filter(|rta| rta.get_rta_type() == IFLA_VLAN_QOS_MAPPING && rta.payload().len() >= 8)
+        .map(|rta| {
+            let mapping = rta.payload();
+            (NativeEndian::read_u32(&mapping[0..4]), NativeEndian::read_u32(&mapping[4..8]))
+        })
+        .collect()
+}
+
+impl VlanInfo {
+    /// Parse `IFLA_INFO_DATA` of a "vlan" link, None without a vlan id
+    pub fn from_info_data(data: &[u8]) -> Option<Self> {
+        let mut id = None;
+        let mut info = VlanInfo {
+            id: 0,
+            protocol: VlanProtocol::Ieee8021Q,
+            flags: VlanFlags::empty(),
+            ingress_qos: vec![],
+            egress_qos: vec![],
+        };
+        for rta in RtAttrIterator::new(data) {
+            let payload = rta.payload();
+            match rta.get_rta_type() {
+                IFLA_VLAN_ID if payload.len() >= 2 => id = Some(NativeEndian::read_u16(payload)),
+                IFLA_VLAN_PROTOCOL if payload.len() >= 2 => {
+                    info.protocol = VlanProtocol::from_ethertype(BigEndian::read_u16(payload));
+                },
+                IFLA_VLAN_FLAGS if payload.len() >= 8 => {
+                    let flags = NativeEndian::read_u32(&payload[0..4]);
+                    let mask = NativeEndian::read_u32(&payload[4..8]);
+                    info.flags = VlanFlags::from_bits_truncate(flags & mask);
+                },
+                IFLA_VLAN_INGRESS_QOS => info.ingress_qos = parse_qos(payload),
+                IFLA_VLAN_EGRESS_QOS => info.egress_qos = parse_qos(payload),
+                _ => {},
+            }
+        }
+        id.map(|id| VlanInfo { id: id, ..info })
+    }
+}
+
+mod tests {
+    #[test]
+    fn vlan_encoding() {
+        use crate::packet::route::link::{Link,LinkBuilder};
+        use crate::packet::route::vlan::{Vlan,VlanFlags,VlanInfo,VlanProtocol};
+
+        let vlan = Vlan::new(100).protocol(VlanProtocol::Ieee8021AD)
+            .flags(VlanFlags::REORDER_HDR, false).flags(VlanFlags::MVRP, true)
+            .ingress_map(5, 2).egress_map(0, 3).egress_map(1, 4);
+        let link = Link::from_packet(LinkBuilder::new("pnl-vlan0").parent(1).kind(vlan).request().unwrap()).unwrap();
+        assert_eq!(link.get_kind(), Some("vlan".to_owned()));
+        let info = link.get_vlan().unwrap();
+        assert_eq!(info, VlanInfo {
+            id: 100,
+            protocol: VlanProtocol::Ieee8021AD,
+            flags: VlanFlags::MVRP,
+            ingress_qos: vec![(5, 2)],
+            egress_qos: vec![(0, 3), (1, 4)],
+        });
+        assert!(VlanInfo::from_info_data(&[]).is_none());
+    }
+
+    #[test]
+    // root permissions required
+    fn create_qinq() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::{Links,LinkBuilder,LinkType};
+        use crate::packet::route::vlan::{Vlan,VlanFlags,VlanProtocol};
+
+        let mut conn = NetlinkConnection::new();
+        conn.new_link(&LinkBuilder::new("pnl-qinq0").kind(LinkType::Dummy)).unwrap();
+        let parent = conn.get_link_by_name("pnl-qinq0").unwrap().unwrap();
+        conn.new_link(&LinkBuilder::new("pnl-qinq0.10").parent(parent.get_index())
+                      .kind(Vlan::new(10).protocol(VlanProtocol::Ieee8021AD))).unwrap();
+        let outer = conn.get_link_by_name("pnl-qinq0.10").unwrap().unwrap();
+        conn.new_link(&LinkBuilder::new("pnl-qinq0.10.20").parent(outer.get_index())
+                      .kind(Vlan::new(20).flags(VlanFlags::LOOSE_BINDING, true).egress_map(2, 5))).unwrap();
+        let inner = conn.get_link_by_name("pnl-qinq0.10.20").unwrap().unwrap();
+
+        let info = outer.get_vlan().unwrap();
+        assert_eq!((info.id, info.protocol), (10, VlanProtocol::Ieee8021AD));
+        let info = inner.get_vlan().unwrap();
+        assert_eq!((info.id, info.protocol), (20, VlanProtocol::Ieee8021Q));
+        assert_eq!(inner.get_link(), Some(outer.get_index()));
+        assert!(info.flags.contains(VlanFlags::LOOSE_BINDING | VlanFlags::REORDER_HDR));
+        assert_eq!(info.egress_qos, vec![(2, 5)]);
+        assert!(parent.get_vlan().is_none());
+        // deleting the parent takes the stacked vlans along
+        conn.delete_link(parent).unwrap();
+        assert!(conn.get_link_by_name("pnl-qinq0.10.20").unwrap().is_none());
+    }
+}
@@ -0,0 +1,510 @@
+//! VXLAN overlay links
+//!
+//! # Example
+//! ```no_run
+//! use pnetlink::packet::netlink::NetlinkConnection;
+//! use pnetlink::packet::route::link::{Links,LinkBuilder};
+//! use pnetlink::packet::route::vxlan::Vxlan;
+//!
+//! let mut conn = NetlinkConnection::new();
+//! conn.new_link(&LinkBuilder::new("vxlan100").kind(Vxlan::new(100)
+//!     .local("192.0.2.1".parse().unwrap()).remote("192.0.2.2".parse().unwrap())
+//!     .port(4789).learning(false)).up(true)).unwrap();
+//! // single device for all VNIs, used with lightweight tunnels or tc
+//! conn.new_link(&LinkBuilder::new("vxlan0").kind(Vxlan::new(0).external(true))).unwrap();
+//! ```
+use crate::packet::route::{AddressFamily,RtAttrIterator,RtAttrPacket};
+use crate::packet::route::addr::Addr;
+use crate::packet::route::link::{LinkKind,push_flag,push_opt};
+use crate::packet::route::route::{ToPayload,WithPayload};
+use byteorder::{ByteOrder,BigEndian,NativeEndian};
+use pnet::packet::Packet;
+use std::net::IpAddr;
+use std::time::Duration;
+
+pub const IFLA_VXLAN_UNSPEC: u16 = 0;
+pub const IFLA_VXLAN_ID: u16 = 1;
+pub const IFLA_VXLAN_GROUP: u16 = 2;
+pub const IFLA_VXLAN_LINK: u16 = 3;
+pub const IFLA_VXLAN_LOCAL: u16 = 4;
+pub const IFLA_VXLAN_TTL: u16 = 5;
+pub const IFLA_VXLAN_TOS: u16 = 6;
+pub const IFLA_VXLAN_LEARNING: u16 = 7;
+pub const IFLA_VXLAN_AGEING: u16 = 8;
+pub const IFLA_VXLAN_LIMIT: u16 = 9;
+pub const IFLA_VXLAN_PORT_RANGE: u16 = 10;
+pub const IFLA_VXLAN_PROXY: u16 = 11;
+pub const IFLA_VXLAN_RSC: u16 = 12;
+pub const IFLA_VXLAN_L2MISS: u16 = 13;
+pub const IFLA_VXLAN_L3MISS: u16 = 14;
+pub const IFLA_VXLAN_PORT: u16 = 15;
+pub const IFLA_VXLAN_GROUP6: u16 = 16;
+pub const IFLA_VXLAN_LOCAL6: u16 = 17;
+pub const IFLA_VXLAN_UDP_CSUM: u16 = 18;
+pub const IFLA_VXLAN_UDP_ZERO_CSUM6_TX: u16 = 19;
+pub const IFLA_VXLAN_UDP_ZERO_CSUM6_RX: u16 = 20;
+pub const IFLA_VXLAN_REMCSUM_TX: u16 = 21;
+pub const IFLA_VXLAN_REMCSUM_RX: u16 = 22;
+pub const IFLA_VXLAN_GBP: u16 = 23;
+pub const IFLA_VXLAN_REMCSUM_NOPARTIAL: u16 = 24;
+pub const IFLA_VXLAN_COLLECT_METADATA: u16 = 25;
+pub const IFLA_VXLAN_LABEL: u16 = 26;
+pub const IFLA_VXLAN_GPE: u16 = 27;
+pub const IFLA_VXLAN_TTL_INHERIT: u16 = 28;
+pub const IFLA_VXLAN_DF: u16 = 29;
+
+/// Don't Fragment bit of the outer IPv4 header
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub enum VxlanDf {
+    Unset,
+    Set,
+    /// Copy from the inner IPv4 header
+    Inherit,
+    Other(u8),
+}
+
+impl VxlanDf {
+    pub fn new(val: u8) -> Self {
+        match val {
+            0 => VxlanDf::Unset,
+            1 => VxlanDf::Set,
+            2 => VxlanDf::Inherit,
+            _ => VxlanDf::Other(val),
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match *self {
+            VxlanDf::Unset => 0,
+            VxlanDf::Set => 1,
+            VxlanDf::Inherit => 2,
+            VxlanDf::Other(val) => val,
+        }
+    }
+}
+
+/// Vxlan options, unset ones keep the kernel defaults
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Vxlan {
+    vni: u32,
+    local: Option<IpAddr>,
+    remote: Option<IpAddr>,
+    link: Option<u32>,
+    port: Option<u16>,
+    port_range: Option<(u16, u16)>,
+    ttl: Option<u8>,
+    ttl_inherit: bool,
+    tos: Option<u8>,
+    df: Option<VxlanDf>,
+    label: Option<u32>,
+    learning: Option<bool>,
+    ageing: Option<Duration>,
+    limit: Option<u32>,
+    proxy: Option<bool>,
+    rsc: Option<bool>,
+    l2miss: Option<bool>,
+    l3miss: Option<bool>,
+    external: Option<bool>,
+    udp_csum: Option<bool>,
+    udp_zero_csum6_tx: Option<bool>,
+    udp_zero_csum6_rx: Option<bool>,
+    remcsum_tx: Option<bool>,
+    remcsum_rx: Option<bool>,
+    gbp: bool,
+    gpe: bool,
+}
+
+impl Vxlan {
+    pub fn new(vni: u32) -> Self {
+        Vxlan {
+            vni: vni,
+            local: None,
+            remote: None,
+            link: None,
+            port: None,
+            port_range: None,
+            ttl: None,
+            ttl_inherit: false,
+            tos: None,
+            df: None,
+            label: None,
+            learning: None,
+            ageing: None,
+            limit: None,
+            proxy: None,
+            rsc: None,
+            l2miss: None,
+            l3miss: None,
+            external: None,
+            udp_csum: None,
+            udp_zero_csum6_tx: None,
+            udp_zero_csum6_rx: None,
+            remcsum_tx: None,
+            remcsum_rx: None,
+            gbp: false,
+            gpe: false,
+        }
+    }
+
+    pub fn local(mut self, local: IpAddr) -> Self {
+        self.local = Some(local);
+        self
+    }
+
+    /// Send to unicast `remote`
+    pub fn remote(mut self, remote: IpAddr) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    /// Join multicast `group` on link `index`
+    pub fn group(mut self, group: IpAddr, index: u32) -> Self {
+        self.remote = Some(group);
+        self.link = Some(index);
+        self
+    }
+
+    /// Destination UDP port
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Range of UDP source ports, both included
+    pub fn port_range(mut self, low: u16, high: u16) -> Self {
+        self.port_range = Some((low, high));
+        self
+    }
+
+    /// 0 takes the hop limit of the route, see `ttl_inherit`
+    pub fn ttl(mut self, ttl: u8) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Copy the TTL from the inner packet
+    pub fn ttl_inherit(mut self, on: bool) -> Self {
+        self.ttl_inherit = on;
+        self
+    }
+
+    /// 1 copies the TOS of the inner packet
+    pub fn tos(mut self, tos: u8) -> Self {
+        self.tos = Some(tos);
+        self
+    }
+
+    pub fn df(mut self, df: VxlanDf) -> Self {
+        self.df = Some(df);
+        self
+    }
+
+    /// IPv6 flow label of the outer header
+    pub fn label(mut self, label: u32) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Learn remote VTEPs from received packets into the FDB
+    pub fn learning(mut self, on: bool) -> Self {
+        self.learning = Some(on);
+        self
+    }
+
+    /// Lifetime of learned FDB entries
+    pub fn ageing(mut self, ageing: Duration) -> Self {
+        self.ageing = Some(ageing);
+        self
+    }
+
+    /// Maximum number of FDB entries, 0 for no limit
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Answer ARP and neighbour solicitations from the FDB
+    pub fn proxy(mut self, on: bool) -> Self {
+        self.proxy = Some(on);
+        self
+    }
+
+    /// Route short-circuit, i.e. send routed packets straight to the destination VTEP
+    pub fn rsc(mut self, on: bool) -> Self {
+        self.rsc = Some(on);
+        self
+    }
+
+    /// Notify userspace of FDB and neighbour misses
+    pub fn miss(mut self, l2: bool, l3: bool) -> Self {
+        self.l2miss = Some(l2);
+        self.l3miss = Some(l3);
+        self
+    }
+
+    /// Collect metadata, i.e. VNI and remote are set per packet
+    pub fn external(mut self, on: bool) -> Self {
+        self.external = Some(on);
+        self
+    }
+
+    /// Compute UDP checksums over IPv4
+    pub fn udp_csum(mut self, on: bool) -> Self {
+        self.udp_csum = Some(on);
+        self
+    }
+
+    /// Send and accept zero UDP checksums over IPv6
+    pub fn udp_zero_csum6(mut self, tx: bool, rx: bool) -> Self {
+        self.udp_zero_csum6_tx = Some(tx);
+        self.udp_zero_csum6_rx = Some(rx);
+        self
+    }
+
+    /// Offload checksums of the inner packet to the remote end
+    pub fn remcsum(mut self, tx: bool, rx: bool) -> Self {
+        self.remcsum_tx = Some(tx);
+        self.remcsum_rx = Some(rx);
+        self
+    }
+
+    /// Group Based Policy extension
+    pub fn gbp(mut self, on: bool) -> Self {
+        self.gbp = on;
+        self
+    }
+
+    /// Generic Protocol Extension, for payloads other than Ethernet
+    pub fn gpe(mut self, on: bool) -> Self {
+        self.gpe = on;
+        self
+    }
+}
+
+impl LinkKind for Vxlan {
+    fn kind(&self) -> &str {
+        "vxlan"
+    }
+
+    fn info_data(&self) -> Vec<RtAttrPacket<'static>> {
+        let mut data = vec![RtAttrPacket::create_with_payload(IFLA_VXLAN_ID, self.vni)];
+        if let Some(remote) = self.remote {
+            let kind = if remote.is_ipv4() { IFLA_VXLAN_GROUP } else { IFLA_VXLAN_GROUP6 };
+            data.push(RtAttrPacket::create_with_payload(kind, remote));
+        }
+        if let Some(local) = self.local {
+            let kind = if local.is_ipv4() { IFLA_VXLAN_LOCAL } else { IFLA_VXLAN_LOCAL6 };
+            data.push(RtAttrPacket::create_with_payload(kind, local));
+        }
+        push_opt(&mut data, IFLA_VXLAN_LINK, self.link);
+        // ports and label are in network byte order
+        push_opt(&mut data, IFLA_VXLAN_PORT, self.port.map(|port| port.to_be()));
+        if let Some((low, high)) = self.port_range {
+            let range: &[&dyn ToPayload] = &[&low.to_be(), &high.to_be()];
+            data.push(RtAttrPacket::create_with_payload(IFLA_VXLAN_PORT_RANGE, range));
+        }
+        push_opt(&mut data, IFLA_VXLAN_LABEL, self.label.map(|label| label.to_be()));
+        push_opt(&mut data, IFLA_VXLAN_AGEING, self.ageing.map(|ageing| ageing.as_secs() as u32));
+        push_opt(&mut data, IFLA_VXLAN_LIMIT, self.limit);
+        push_opt(&mut data, IFLA_VXLAN_TTL, self.ttl);
+        push_opt(&mut data, IFLA_VXLAN_TOS, self.tos);
+        push_opt(&mut data, IFLA_VXLAN_DF, self.df.map(|df| df.value()));
+        let bools = [(IFLA_VXLAN_LEARNING, self.learning),
+                     (IFLA_VXLAN_PROXY, self.proxy),
+                     (IFLA_VXLAN_RSC, self.rsc),
+                     (IFLA_VXLAN_L2MISS, self.l2miss),
+                     (IFLA_VXLAN_L3MISS, self.l3miss),
+                     (IFLA_VXLAN_COLLECT_METADATA, self.external),
+                     (IFLA_VXLAN_UDP_CSUM, self.udp_csum),
+                     (IFLA_VXLAN_UDP_ZERO_CSUM6_TX, self.udp_zero_csum6_tx),
+                     (IFLA_VXLAN_UDP_ZERO_CSUM6_RX, self.udp_zero_csum6_rx),
+                     (IFLA_VXLAN_REMCSUM_TX, self.remcsum_tx),
+                     (IFLA_VXLAN_REMCSUM_RX, self.remcsum_rx)];
+        for &(kind, on) in bools.iter() {
+            push_opt(&mut data, kind, on.map(|on| on as u8));
+        }
+        push_flag(&mut data, IFLA_VXLAN_TTL_INHERIT, self.ttl_inherit);
+        push_flag(&mut data, IFLA_VXLAN_GBP, self.gbp);
+        push_flag(&mut data, IFLA_VXLAN_GPE, self.gpe);
+        data
+    }
+}
+
+/// Vxlan settings of an existing link
+#[derive(Debug,Clone,Default,PartialEq,Eq,Hash)]
+pub struct VxlanInfo {
+    pub vni: u32,
+    pub local: Option<IpAddr>,
+    /// Remote VTEP, or multicast group the VTEPs join
+    pub remote: Option<IpAddr>,
+    /// Link used to reach the remote or join the group
+    pub link: Option<u32>,
+    /// Destination UDP port
+    pub port: Option<u16>,
+    /// Range of UDP source ports, both included
+    pub port_range: Option<(u16, u16)>,
+    pub ttl: Option<u8>,
+    pub ttl_inherit: bool,
+    pub tos: Option<u8>,
+    pub df: Option<VxlanDf>,
+    /// IPv6 flow label of the outer header
+    pub label: Option<u32>,
+    /// Learn remote VTEPs from received packets into the FDB
+    pub learning: Option<bool>,
+    /// Lifetime of learned FDB entries
+    pub ageing: Option<Duration>,
+    /// Maximum number of FDB entries, 0 for no limit
+    pub limit: Option<u32>,
+    /// Answer ARP and neighbour solicitations from the FDB
+    pub proxy: Option<bool>,
+    /// Route short-circuit
+    pub rsc: Option<bool>,
+    /// Notify userspace of FDB misses
+    pub l2miss: Option<bool>,
+    /// Notify userspace of neighbour misses
+    pub l3miss: Option<bool>,
+    /// Collect metadata, i.e. VNI and remote are set per packet
+    pub external: Option<bool>,
+    /// Compute UDP checksums over IPv4
+    pub udp_csum: Option<bool>,
+    /// Send zero UDP checksums over IPv6
+    pub udp_zero_csum6_tx: Option<bool>,
+    /// Accept zero UDP checksums over IPv6
+    pub udp_zero_csum6_rx: Option<bool>,
+    /// Offload checksums of the inner packet to the remote end
+    pub remcsum_tx: Option<bool>,
+    /// Accept checksums offloaded by the remote end
+    pub remcsum_rx: Option<bool>,
+    /// Don't fall back to full checksums for partial remote offload
+    pub remcsum_nopartial: bool,
+    /// Group Based Policy extension
+    pub gbp: bool,
+    /// Generic Protocol Extension
+    pub gpe: bool,
+}
+
+impl VxlanInfo {
+    /// Parse `IFLA_INFO_DATA` of a "vxlan" link, None without VNI
+    pub fn from_info_data(data: &[u8]) -> Option<Self> {
+        let mut vni = None;
+        let mut vxlan = VxlanInfo::default();
+        for rta in RtAttrIterator::new(data) {
+            let payload = rta.payload();
+            let flag = payload.first().map(|&val| val != 0);
+            match rta.get_rta_type() {
+                IFLA_VXLAN_ID if payload.len() >= 4 => vni = Some(NativeEndian::read_u32(payload)),
+                IFLA_VXLAN_GROUP if payload.len() >= 4 => {
+                    vxlan.remote = Some(Addr::ip_from_family_and_bytes(AddressFamily::Inet, &payload[..4]));
+                },
+                IFLA_VXLAN_GROUP6 if payload.len() >= 16 => {
+                    vxlan.remote = Some(Addr::ip_from_family_and_bytes(AddressFamily::Inet6, &payload[..16]));
+                },
+                IFLA_VXLAN_LOCAL if payload.len() >= 4 => {
+                    vxlan.local = Some(Addr::ip_from_family_and_bytes(AddressFamily::Inet, &payload[..4]));
+                },
+                IFLA_VXLAN_LOCAL6 if payload.len() >= 16 => {
+                    vxlan.local = Some(Addr::ip_from_family_and_bytes(AddressFamily::Inet6, &payload[..16]));
+                },
+                IFLA_VXLAN_LINK if payload.len() >= 4 => vxlan.link = Some(NativeEndian::read_u32(payload)),
+                IFLA_VXLAN_PORT if payload.len() >= 2 => vxlan.port = Some(BigEndian::read_u16(payload)),
+                IFLA_VXLAN_PORT_RANGE if payload.len() >= 4 => {
+                    vxlan.port_range = Some((BigEndian::read_u16(&payload[0..2]), BigEndian::read_u16(&payload[2..4])));
+                },
+                IFLA_VXLAN_TTL => vxlan.ttl = payload.first().cloned(),
+                // sent as a flag, reported as u8 by the kernel
+                IFLA_VXLAN_TTL_INHERIT => vxlan.ttl_inherit = flag.unwrap_or(true),
+                IFLA_VXLAN_TOS => vxlan.tos = payload.first().cloned(),
+                IFLA_VXLAN_DF => vxlan.df = payload.first().map(|&val| VxlanDf::new(val)),
+                IFLA_VXLAN_LABEL if payload.len() >= 4 => vxlan.label = Some(BigEndian::read_u32(payload)),
+                IFLA_VXLAN_LEARNING => vxlan.learning = flag,
+                IFLA_VXLAN_AGEING if payload.len() >= 4 => {
+                    vxlan.ageing = Some(Duration::from_secs(NativeEndian::read_u32(payload) as u64));
+                },
+                IFLA_VXLAN_LIMIT if payload.len() >= 4 => vxlan.limit = Some(NativeEndian::read_u32(payload)),
+                IFLA_VXLAN_PROXY => vxlan.proxy = flag,
+                IFLA_VXLAN_RSC => vxlan.rsc = flag,
+                IFLA_VXLAN_L2MISS => vxlan.l2miss = flag,
+                IFLA_VXLAN_L3MISS => vxlan.l3miss = flag,
+                IFLA_VXLAN_COLLECT_METADATA => vxlan.external = flag,
+                IFLA_VXLAN_UDP_CSUM => vxlan.udp_csum = flag,
+                IFLA_VXLAN_UDP_ZERO_CSUM6_TX => vxlan.udp_zero_csum6_tx = flag,
+                IFLA_VXLAN_UDP_ZERO_CSUM6_RX => vxlan.udp_zero_csum6_rx = flag,
+                IFLA_VXLAN_REMCSUM_TX => vxlan.remcsum_tx = flag,
+                IFLA_VXLAN_REMCSUM_RX => vxlan.remcsum_rx = flag,
+                IFLA_VXLAN_REMCSUM_NOPARTIAL => vxlan.remcsum_nopartial = true,
+                IFLA_VXLAN_GBP => vxlan.gbp = true,
+                IFLA_VXLAN_GPE => vxlan.gpe = true,
+                _ => {},
+            }
+        }
+        vni.map(|vni| VxlanInfo { vni: vni, ..vxlan })
+    }
+}
+
+mod tests {
+    #[test]
+    fn vxlan_encoding() {
+        use crate::packet::route::link::{Link,LinkBuilder};
+        use crate::packet::route::vxlan::{Vxlan,VxlanDf,VxlanInfo};
+        use std::time::Duration;
+
+        let vxlan = Vxlan::new(5000).local("2001:db8::1".parse().unwrap()).group("ff05::100".parse().unwrap(), 2)
+            .port(4789).port_range(40000, 50000).ttl(16).tos(1).learning(false)
+            .ageing(Duration::from_secs(600)).udp_zero_csum6(true, true).gbp(true)
+            .ttl_inherit(true).df(VxlanDf::Inherit).label(0x12345).limit(1024).proxy(true).rsc(false)
+            .miss(true, false).remcsum(true, true).gpe(true);
+        let link = Link::from_packet(LinkBuilder::new("pnl-vx0").kind(vxlan).request().unwrap()).unwrap();
+        assert_eq!(link.get_vxlan(), Some(VxlanInfo {
+            vni: 5000,
+            local: Some("2001:db8::1".parse().unwrap()),
+            remote: Some("ff05::100".parse().unwrap()),
+            link: Some(2),
+            port: Some(4789),
+            port_range: Some((40000, 50000)),
+            ttl: Some(16),
+            tos: Some(1),
+            learning: Some(false),
+            ageing: Some(Duration::from_secs(600)),
+            udp_zero_csum6_tx: Some(true),
+            udp_zero_csum6_rx: Some(true),
+            gbp: true,
+            ttl_inherit: true,
+            df: Some(VxlanDf::Inherit),
+            label: Some(0x12345),
+            limit: Some(1024),
+            proxy: Some(true),
+            rsc: Some(false),
+            l2miss: Some(true),
+            l3miss: Some(false),
+            remcsum_tx: Some(true),
+            remcsum_rx: Some(true),
+            gpe: true,
+            ..VxlanInfo::default()
+        }));
+        let external = Vxlan::new(0).external(true).udp_csum(true);
+        let link = Link::from_packet(LinkBuilder::new("pnl-vx1").kind(external).request().unwrap()).unwrap();
+        assert_eq!(link.get_vxlan(), Some(VxlanInfo { external: Some(true), udp_csum: Some(true), ..VxlanInfo::default() }));
+    }
+
+    #[test]
+    // root permissions required
+    fn create_vxlan() {
+        use crate::packet::netlink::NetlinkConnection;
+        use crate::packet::route::link::{Links,LinkBuilder};
+        use crate::packet::route::vxlan::Vxlan;
+
+        let mut conn = NetlinkConnection::new();
+        conn.new_link(&LinkBuilder::new("pnl-vx2").kind(Vxlan::new(42)
+            .local("127.0.0.1".parse().unwrap()).remote("127.0.0.2".parse().unwrap())
+            .port(8472).learning(false).ttl(64))).unwrap();
+        let link = conn.get_link_by_name("pnl-vx2").unwrap().unwrap();
+        let vxlan = link.get_vxlan().unwrap();
+        assert_eq!(vxlan.vni, 42);
+        assert_eq!(vxlan.remote, Some("127.0.0.2".parse().unwrap()));
+        assert_eq!(vxlan.port, Some(8472));
+        assert_eq!(vxlan.learning, Some(false));
+        assert_eq!(vxlan.ttl, Some(64));
+        assert!(!vxlan.gbp);
+        conn.delete_link(link).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+//! Waiting for links, addresses and routes to reach a state
+//!
+//! The helpers subscribe to notifications first and then look at the
+//! current state, so a change happening in between is not missed. They
+//! block until the condition holds or the timeout expires, failing with
+//! `TimedOut` then. Async counterparts live in `pnetlink::tokio::wait`.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use pnetlink::packet::route::wait;
+//!
+//! let eth0 = wait::wait_for_link("eth0", Duration::from_secs(10),
+//!                                |link| link.is_up() && link.get_carrier() == Some(true)).unwrap();
+//! let addr = "2001:db8::1".parse().unwrap();
+//! wait::wait_for_addr_dad_complete(&eth0, addr, Duration::from_secs(5)).unwrap();
+//! ```
+use crate::packet::netlink::NetlinkConnection;
+use crate::packet::route::addr::{Addr,IfAddrFlags};
+use crate::packet::route::event::{EventIterator,EventSet,RtnlEvent};
+use crate::packet::route::link::Link;
+use crate::packet::route::prefix::IpPrefix;
+use crate::packet::route::resync::Dump;
+use crate::packet::route::route::Route;
+use crate::packet::route::AddressFamily;
+
+use std::io;
+use std::net::IpAddr;
+use std::time::{Duration,Instant};
+
+/// Wait until `check` returns a value for a current object or a notification
+///
+/// Objects covered by `events` are dumped and passed to `check` as added
+/// events, followed by the notifications. They are dumped again if
+/// notifications are lost.
+pub fn wait_for<T, F>(events: EventSet, timeout: Duration, mut check: F) -> io::Result<T>
+    where F: FnMut(RtnlEvent) -> io::Result<Option<T>> {
+    let deadline = Instant::now() + timeout;
+    let mut iter = EventIterator::new(events)?;
+    let mut conn = NetlinkConnection::new();
+    let mut dump = true;
+    loop {
+        if dump {
+            for event in Dump::new(&mut conn, events)?.into_events() {
+                if let Some(found) = check(event)? {
+                    return Ok(found);
+                }
+            }
+            dump = false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "condition not met in time"));
+        }
+        iter.connection().socket_mut().set_read_timeout(Some(deadline - now))?;
+        match iter.next() {
+            Some(Ok(event)) => {
+                if let Some(found) = check(event)? {
+                    return Ok(found);
+                }
+            },
+            Some(Err(ref e)) if e.raw_os_error() == Some(::libc::ENOBUFS) => dump = true,
+            Some(Err(ref e)) if e.kind() == io::ErrorKind::WouldBlock => {},
+            Some(Err(e)) => return Err(e),
+            None => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "event stream ended")),
+        }
+    }
+}
+
+/// Condition for `wait_for`: link `name` exists and `cond` holds for it
+pub fn link_matching<F>(name: &str, mut cond: F) -> impl FnMut(RtnlEvent) -> io::Result<Option<Link>>
+    where F: FnMut(&Link) -> bool {
+    let name = name.to_owned();
+    move |event| Ok(match event {
+        RtnlEvent::LinkAdded(link) => {
+            if link.get_name().as_ref() == Some(&name) && cond(&link) { Some(link) } else { None }
+        },
+        _ => None,
+    })
+}
+
+/// Condition for `wait_for`: address `ip` on link `index` finished
+/// duplicate address detection, fails if a duplicate was detected
+pub fn addr_dad_complete(index: u32, ip: IpAddr) -> impl FnMut(RtnlEvent) -> io::Result<Option<Addr>> {
+    move |event| match event {
+        RtnlEvent::AddrAdded(addr) => {
+            if addr.get_link_index() != index || addr.get_local_ip().or(addr.get_ip()) != Some(ip) {
+                return Ok(None);
+            }
+            let flags = addr.get_flags();
+            if flags.contains(IfAddrFlags::DADFAILED) {
+                Err(io::Error::new(io::ErrorKind::AddrInUse, format!("duplicate address {} detected", ip)))
+            } else if flags.contains(IfAddrFlags::TENTATIVE) {
+                Ok(None)
+            } else {
+                Ok(Some(addr))
+            }
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Condition for `wait_for`: route to `prefix` exists in `table`
+pub fn route_present(prefix: IpPrefix, table: u32) -> impl FnMut(RtnlEvent) -> io::Result<Option<Route>> {
+    move |event| Ok(match event {
+        RtnlEvent::RouteAdded(route) => {
+            if route.get_table() == table && route.get_destination_prefix() == Some(prefix) { Some(route) } else { None }
+        },
+        _ => None,
+    })
+}
+
+/// Events needed to watch routes of `prefix`'s family
+pub fn route_events(prefix: IpPrefix) -> EventSet {
+    match prefix.family() {
+        AddressFamily::Inet6 => EventSet::IPV6_ROUTE,
+        _ => EventSet::IPV4_ROUTE,
+    }
+}
+
+/// Events needed to watch addresses of `ip`'s family
+pub fn addr_events(ip: IpAddr) -> EventSet {
+    match ip {
+        IpAddr::V4(_) => EventSet::IPV4_ADDR,
+        IpAddr::V6(_) => EventSet::IPV6_ADDR,
+    }
+}
+
+/// Wait for link `name` to exist with `cond` holding, e.g. up with carrier
+pub fn wait_for_link<F>(name: &str, timeout: Duration, cond: F) -> io::Result<Link>
+    where F: FnMut(&Link) -> bool {
+    wait_for(EventSet::LINK, timeout, link_matching(name, cond))
+}
+
+/// Wait for address `ip` on `link` to leave the tentative state
+///
+/// IPv4 addresses are never tentative, so this only waits for them to appear.
+pub fn wait_for_addr_dad_complete(link: &Link, ip: IpAddr, timeout: Duration) -> io::Result<Addr> {
+    wait_for(addr_events(ip), timeout, addr_dad_complete(link.get_index(), ip))
+}
+
+/// Wait for a route to `prefix` in `table`
+pub fn wait_for_route(prefix: IpPrefix, table: u32, timeout: Duration) -> io::Result<Route> {
+    wait_for(route_events(prefix), timeout, route_present(prefix, table))
+}
+
+mod tests {
+    #[test]
+    fn wait_for_lo() {
+        use crate::packet::route::route::RT_TABLE_LOCAL;
+        use crate::packet::route::wait::{wait_for_link,wait_for_route};
+        use std::time::Duration;
+
+        let lo = wait_for_link("lo", Duration::from_secs(1), |link| link.is_up()).unwrap();
+        assert_eq!(lo.get_name(), Some("lo".to_owned()));
+        wait_for_route("127.0.0.1/32".parse().unwrap(), RT_TABLE_LOCAL, Duration::from_secs(1)).unwrap();
+        let err = wait_for_link("nonexistent0", Duration::from_millis(100), |_| true).unwrap_err();
+        assert_eq!(err.kind(), ::std::io::ErrorKind::TimedOut);
+    }
+}
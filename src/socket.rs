@@ -1,28 +1,33 @@
 //! Netlink socket related functions
-extern crate libc;
-extern crate mio;
-
 use libc::c_int;
-use libc::{socket,bind,send,recvfrom,setsockopt,getsockopt};
+use libc::{socket,bind,send,recv,recvfrom,setsockopt,getsockopt};
 use std::os::unix::io::{AsRawFd,RawFd};
 use std::io::{self,Error,Result,Read,Write};
 use std::mem;
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
 
-use self::mio::unix::EventedFd;
-use self::mio::{Evented, Poll, Token, Ready, PollOpt};
+use mio::unix::EventedFd;
+use mio::{Evented, Poll, Token, Ready, PollOpt};
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
 pub enum SockOpt {
 	AddMembership = 1,
 	DropMembership = 2,
 	PktInfo = 3,
 	BroadcastError = 4,
 	NoEnobufs = 5,
+	ListenAllNsid = 8,
+	CapAck = 10,
+	ExtAck = 11,
+	GetStrictChk = 12,
 }
 
 
 /// supported protocols
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
 pub enum NetlinkProtocol {
 	Route = 0,
 	//Unused = 1,
@@ -47,6 +52,15 @@ pub enum NetlinkProtocol {
 	Crypto = 21,
 }
 
+/// Netlink peer address
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct NetlinkAddr {
+	/// Port id of the sender, 0 for the kernel
+	pub pid: u32,
+	/// Multicast groups bitmask, 0 for unicast messages
+	pub groups: u32,
+}
+
 /// Bound Netlink socket.
 #[derive(Debug)]
 pub struct NetlinkSocket {
@@ -72,9 +86,14 @@ impl NetlinkSocket {
 	}
 
 	pub fn bind_with_args(proto: NetlinkProtocol, groups: u32, nonblocking: bool) -> Result<NetlinkSocket> {
+		let pid = unsafe { libc::getpid() } as u32;
+		NetlinkSocket::bind_with_pid(proto, pid, groups, nonblocking)
+	}
+
+	/// Bind socket with port id `pid`, 0 lets the kernel pick a unique one
+	pub fn bind_with_pid(proto: NetlinkProtocol, pid: u32, groups: u32, nonblocking: bool) -> Result<NetlinkSocket> {
 		use std::mem::size_of;
 		use std::mem::transmute;
-		use libc::getpid;
 
 		let mut res = unsafe {
 			socket(libc::PF_NETLINK, libc::SOCK_DGRAM, proto as i32)
@@ -94,7 +113,7 @@ impl NetlinkSocket {
 
 		let mut sockaddr: libc::sockaddr_nl = unsafe { mem::zeroed() };
 		sockaddr.nl_family = libc::PF_NETLINK as libc::sa_family_t;
-		sockaddr.nl_pid = unsafe { getpid() } as u32;
+		sockaddr.nl_pid = pid;
 		sockaddr.nl_groups = groups;
 
 		res = unsafe {
@@ -106,7 +125,7 @@ impl NetlinkSocket {
 		Ok(sock)
 	}
 
-	pub fn send(&mut self, buf: &[u8]) -> Result<usize> {
+	pub fn send(&self, buf: &[u8]) -> Result<usize> {
 		use libc::c_void;
 		let len = buf.len();
 		let res = unsafe {
@@ -118,7 +137,7 @@ impl NetlinkSocket {
 		Ok(res as usize)
 	}
 
-	pub fn recv(&mut self, buf: &mut [u8]) -> Result<usize> {
+	pub fn recv(&self, buf: &mut [u8]) -> Result<usize> {
 		use libc::c_void;
 		use std::ptr::null_mut;
 		use libc::sockaddr;
@@ -133,6 +152,48 @@ impl NetlinkSocket {
 		Ok(res as usize)
 	}
 
+	/// Drop datagrams queued on the socket, without blocking
+	///
+	/// Returns the number of datagrams dropped. Used after `ENOBUFS`: the
+	/// kernel reports the error ahead of the datagrams queued before it.
+	pub fn discard_queued(&self) -> Result<usize> {
+		use std::ptr::null_mut;
+
+		let mut count = 0;
+		loop {
+			let res = unsafe {
+				recv(self.fd, null_mut(), 0, libc::MSG_DONTWAIT | libc::MSG_TRUNC)
+			};
+			if res < 0 {
+				let err = Error::last_os_error();
+				match err.raw_os_error() {
+					Some(libc::EAGAIN) => return Ok(count),
+					Some(libc::EINTR) | Some(libc::ENOBUFS) => continue,
+					_ => return Err(err),
+				}
+			}
+			count += 1;
+		}
+	}
+
+	/// Receive one datagram together with the sender address
+	///
+	/// Multicast notifications have non-zero `groups` in the address.
+	pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, NetlinkAddr)> {
+		use libc::c_void;
+
+		let mut sockaddr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+		let mut addr_len = mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+		let res = unsafe {
+			recvfrom(self.fd, buf.as_mut_ptr() as *mut c_void, buf.len(), 0,
+					 &mut sockaddr as *mut libc::sockaddr_nl as *mut libc::sockaddr, &mut addr_len)
+		};
+		if res < 0 {
+			return Err(Error::last_os_error());
+		}
+		Ok((res as usize, NetlinkAddr { pid: sockaddr.nl_pid, groups: sockaddr.nl_groups }))
+	}
+
 	fn setsockopt_int(&mut self, level: c_int, option: c_int, val: c_int) -> Result<()> {
 		use std::mem;
 		let res = unsafe {
@@ -176,11 +237,108 @@ impl NetlinkSocket {
 		self.getsockopt_int(libc::SOL_SOCKET, libc::SO_RCVBUF)
 	}
 
+	pub fn setsndbuf(&mut self, len: c_int) -> Result<()> {
+		self.setsockopt_int(libc::SOL_SOCKET, libc::SO_SNDBUF, len)
+	}
+
+	pub fn getsndbuf(&mut self) -> Result<u32> {
+		self.getsockopt_int(libc::SOL_SOCKET, libc::SO_SNDBUF)
+	}
+
+	/// Join multicast group `group` (any group number, unlike the bind() bitmask)
+	pub fn add_membership(&mut self, group: u32) -> Result<()> {
+		self.setsockopt_int(libc::SOL_NETLINK, SockOpt::AddMembership as c_int, group as c_int)
+	}
+
+	/// Leave multicast group `group`
+	pub fn drop_membership(&mut self, group: u32) -> Result<()> {
+		self.setsockopt_int(libc::SOL_NETLINK, SockOpt::DropMembership as c_int, group as c_int)
+	}
+
+	/// Set timeout of blocking receives, None to block forever
+	///
+	/// Receives timing out fail with `WouldBlock`.
+	pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+		let timeout = timeout.unwrap_or(Duration::from_secs(0));
+		let mut tv = libc::timeval {
+			tv_sec: timeout.as_secs() as libc::time_t,
+			tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+		};
+		// zero would mean no timeout
+		if tv.tv_sec == 0 && tv.tv_usec == 0 && timeout != Duration::from_secs(0) {
+			tv.tv_usec = 1;
+		}
+		let res = unsafe {
+			setsockopt(self.fd, libc::SOL_SOCKET, libc::SO_RCVTIMEO,
+					   &tv as *const libc::timeval as *const libc::c_void,
+					   mem::size_of::<libc::timeval>() as libc::socklen_t)
+		};
+		if res == -1 {
+			return Err(Error::last_os_error());
+		}
+		Ok(())
+	}
+
+	/// Switch to blocking or non-blocking mode
+	pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+		let mut nonblocking = if nonblocking { 1 } else { 0 } as libc::c_ulong;
+		let res = unsafe {
+			libc::ioctl(self.fd, libc::FIONBIO, &mut nonblocking)
+		};
+		if res < 0 {
+			return Err(Error::last_os_error());
+		}
+		Ok(())
+	}
+
 	pub fn getsockopt(&mut self, option: SockOpt, val: bool) -> Result<u32> {
 		self.getsockopt_int(libc::SOL_NETLINK, option as c_int)
 	}
 }
 
+/// Switches the calling thread back to the namespace it was in, also when unwinding
+struct NetnsGuard(Option<File>);
+
+impl NetnsGuard {
+	fn restore(&mut self) -> Result<()> {
+		if let Some(current) = self.0.take() {
+			if unsafe { libc::setns(current.as_raw_fd(), libc::CLONE_NEWNET) } < 0 {
+				let err = Error::last_os_error();
+				return Err(Error::new(err.kind(), format!("cannot restore network namespace: {}", err)));
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Drop for NetnsGuard {
+	fn drop(&mut self) {
+		// only reached without restore() if `f` panicked, nowhere to report an error
+		let _ = self.restore();
+	}
+}
+
+/// Run `f` with the calling thread switched to network namespace `netns`
+///
+/// `netns` is a namespace file such as `/var/run/netns/NAME` or `/proc/PID/ns/net`.
+/// Sockets created by `f` stay in that namespace; the thread is switched back afterwards,
+/// also if `f` panics.
+///
+/// If switching back fails the result of `f` is dropped and the error returned: the
+/// thread is then still in `netns`, and it is up to the caller whether it may go on.
+pub fn with_netns<P, T, F>(netns: P, f: F) -> Result<T>
+	where P: AsRef<Path>, F: FnOnce() -> Result<T> {
+	let current = File::open("/proc/thread-self/ns/net")?;
+	let target = File::open(netns)?;
+	if unsafe { libc::setns(target.as_raw_fd(), libc::CLONE_NEWNET) } < 0 {
+		return Err(Error::last_os_error());
+	}
+	let mut guard = NetnsGuard(Some(current));
+	let res = f();
+	guard.restore()?;
+	res
+}
+
 impl Read for NetlinkSocket {
 	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
 		self.recv(buf)
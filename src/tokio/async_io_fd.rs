@@ -0,0 +1,61 @@
+//! async-io (smol) reactor support
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use async_io::{Async, Timer};
+use crate::socket;
+use super::Readiness;
+
+/// Netlink socket registered with the async-io reactor
+pub struct AsyncIoFd {
+    io: Async<socket::NetlinkSocket>,
+}
+
+impl Readiness for AsyncIoFd {
+    fn new(sock: socket::NetlinkSocket) -> io::Result<Self> {
+        // Async::new switches the socket to non-blocking mode
+        Ok(AsyncIoFd { io: Async::new(sock)? })
+    }
+
+    fn get_ref(&self) -> &socket::NetlinkSocket {
+        self.io.get_ref()
+    }
+
+    fn poll_read_with<T, F>(&self, cx: &mut Context, mut op: F) -> Poll<io::Result<T>>
+        where F: FnMut(&socket::NetlinkSocket) -> io::Result<T> {
+        loop {
+            match op(self.io.get_ref()) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {},
+                res => return Poll::Ready(res),
+            }
+            match self.io.poll_readable(cx) {
+                Poll::Ready(Ok(())) => continue,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn poll_write_with<T, F>(&self, cx: &mut Context, mut op: F) -> Poll<io::Result<T>>
+        where F: FnMut(&socket::NetlinkSocket) -> io::Result<T> {
+        loop {
+            match op(self.io.get_ref()) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {},
+                res => return Poll::Ready(res),
+            }
+            match self.io.poll_writable(cx) {
+                Poll::Ready(Ok(())) => continue,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn sleep(duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move {
+            Timer::after(duration).await;
+        })
+    }
+}
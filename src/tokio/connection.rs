@@ -0,0 +1,405 @@
+//! Request multiplexer
+//!
+//! `Connection` owns the socket and must be polled (spawned) for any
+//! request to make progress. `Handle`s can be cloned and used concurrently
+//! from many tasks: each request gets its own sequence number and receives
+//! only its own replies. Multicast notifications go to the `Unsolicited` stream.
+//!
+//! The kernel runs one dump per socket at a time, so dump requests are
+//! sent one after another; other requests are not held back by them.
+use byteorder::{ByteOrder, NativeEndian};
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::stream::{Stream, StreamExt};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use pnet::packet::Packet;
+use crate::socket;
+use crate::packet::netlink::{NetlinkPacket, MutableNetlinkPacket, NetlinkMsgFlags, ToIoError};
+use crate::packet::netlink::{NLMSG_NOOP, NLMSG_ERROR, NLMSG_DONE, NLMSG_OVERRUN};
+use super::{NetlinkSocket, Readiness, MAX_DATAGRAM};
+
+/// Stream of multicast notifications
+///
+/// `ENOBUFS` errors (notifications were lost because the socket buffer
+/// was full) are delivered as `Err` items, the stream continues after them.
+/// Notifications still queued on the socket with the error are dropped,
+/// so every item following it is newer than it.
+pub type Unsolicited = UnboundedReceiver<io::Result<NetlinkPacket<'static>>>;
+
+type Replies = UnboundedSender<io::Result<NetlinkPacket<'static>>>;
+
+struct Request {
+    seq: u32,
+    dump: bool,
+    data: Vec<u8>,
+    replies: Replies,
+}
+
+/// Create a connection on an already wrapped socket
+///
+/// Spawn the returned `Connection` on the runtime, then use the `Handle`.
+pub fn connection<R: Readiness + Unpin>(socket: NetlinkSocket<R>) -> (Connection<R>, Handle, Unsolicited) {
+    let (requests_tx, requests_rx) = mpsc::unbounded();
+    let (unsolicited_tx, unsolicited_rx) = mpsc::unbounded();
+    let conn = Connection {
+        socket: socket,
+        requests: Some(requests_rx),
+        outgoing: VecDeque::new(),
+        pending: HashMap::new(),
+        dump: None,
+        overrun: false,
+        unsolicited: unsolicited_tx,
+        buf: vec![0; MAX_DATAGRAM],
+    };
+    let handle = Handle {
+        requests: requests_tx,
+        seq: Arc::new(AtomicUsize::new(0)),
+        sleep: R::sleep,
+    };
+    (conn, handle, unsolicited_rx)
+}
+
+/// Create a connection to `proto` on the default runtime,
+/// receiving notifications for multicast `groups`
+pub fn new_connection(proto: socket::NetlinkProtocol, groups: u32) -> io::Result<(Connection, Handle, Unsolicited)> {
+    // kernel assigned port id, so that connections do not clash
+    let sock = socket::NetlinkSocket::bind_with_pid(proto, 0, groups, true)?;
+    Ok(connection(NetlinkSocket::new(sock)?))
+}
+
+/// Connection driver, a future resolving when all handles and the
+/// unsolicited stream are dropped and all requests are answered
+pub struct Connection<R: Readiness = super::DefaultReadiness> {
+    socket: NetlinkSocket<R>,
+    requests: Option<UnboundedReceiver<Request>>,
+    outgoing: VecDeque<Request>,
+    pending: HashMap<u32, Replies>,
+    /// sequence number of the dump running in the kernel
+    dump: Option<u32>,
+    /// ENOBUFS seen, notifications are dropped until the socket is drained
+    overrun: bool,
+    unsolicited: UnboundedSender<io::Result<NetlinkPacket<'static>>>,
+    buf: Vec<u8>,
+}
+
+impl<R: Readiness + Unpin> Connection<R> {
+    /// Get underlying socket, e.g. to join more multicast groups
+    pub fn socket(&self) -> &NetlinkSocket<R> {
+        &self.socket
+    }
+
+    fn poll_requests(&mut self, cx: &mut Context) {
+        while let Some(ref mut requests) = self.requests {
+            match requests.poll_next_unpin(cx) {
+                Poll::Ready(Some(req)) => self.outgoing.push_back(req),
+                Poll::Ready(None) => self.requests = None,
+                Poll::Pending => break,
+            }
+        }
+    }
+
+    fn poll_send(&mut self, cx: &mut Context) {
+        let mut held = VecDeque::new();
+        while let Some(req) = self.outgoing.pop_front() {
+            if req.replies.is_closed() {
+                continue;
+            }
+            // a second dump on the socket would fail with EBUSY
+            if req.dump && self.dump.is_some() {
+                held.push_back(req);
+                continue;
+            }
+            match self.socket.poll_send(cx, &req.data) {
+                Poll::Ready(Ok(_)) => {
+                    if req.dump {
+                        self.dump = Some(req.seq);
+                    }
+                    self.pending.insert(req.seq, req.replies);
+                },
+                Poll::Ready(Err(e)) => {
+                    let _ = req.replies.unbounded_send(Err(e));
+                },
+                Poll::Pending => {
+                    self.outgoing.push_front(req);
+                    break;
+                },
+            }
+        }
+        // held dumps keep their place ahead of later requests
+        while let Some(req) = held.pop_back() {
+            self.outgoing.push_front(req);
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context) -> Poll<io::Error> {
+        loop {
+            match self.socket.poll_recv_from(cx, &mut self.buf) {
+                Poll::Ready(Ok((len, addr))) => self.dispatch(len, addr.groups != 0),
+                Poll::Ready(Err(ref e)) if e.raw_os_error() == Some(::libc::ENOBUFS) => self.overrun = true,
+                Poll::Ready(Err(e)) => return Poll::Ready(e),
+                Poll::Pending => {
+                    // the kernel reports the error ahead of the notifications
+                    // queued before it, these are gone now
+                    if self.overrun {
+                        self.overrun = false;
+                        let _ = self.unsolicited.unbounded_send(Err(io::Error::from_raw_os_error(::libc::ENOBUFS)));
+                    }
+                    return Poll::Pending;
+                },
+            }
+        }
+    }
+
+    fn dispatch(&mut self, len: usize, multicast: bool) {
+        let mut offset = 0;
+        while offset < len {
+            let pkt = match NetlinkPacket::new(&self.buf[offset..len]) {
+                Some(pkt) => pkt,
+                None => break,
+            };
+            let pkt_len = pkt.get_length() as usize;
+            if pkt_len < MutableNetlinkPacket::minimum_packet_size() || offset + pkt_len > len {
+                break;
+            }
+            let owned = NetlinkPacket::owned(self.buf[offset..offset + pkt_len].to_owned()).unwrap();
+            offset += crate::util::align(pkt_len);
+            if multicast {
+                if !self.overrun {
+                    let _ = self.unsolicited.unbounded_send(Ok(owned));
+                }
+                continue;
+            }
+            let seq = owned.get_seq();
+            let last = match owned.get_kind() {
+                NLMSG_ERROR | NLMSG_DONE | NLMSG_OVERRUN => true,
+                _ => false,
+            };
+            // also when the dump was cancelled, the kernel still finishes it
+            if last && self.dump == Some(seq) {
+                self.dump = None;
+            }
+            let done = match self.pending.get(&seq) {
+                // late reply to a cancelled or timed out request
+                None => continue,
+                Some(replies) => match owned.get_kind() {
+                    NLMSG_NOOP => false,
+                    NLMSG_ERROR => {
+                        if let Some(err) = owned.to_io_error() {
+                            let _ = replies.unbounded_send(Err(err));
+                        }
+                        true
+                    },
+                    NLMSG_DONE => {
+                        let payload = owned.payload();
+                        if payload.len() >= 4 && NativeEndian::read_i32(payload) < 0 {
+                            let err = io::Error::from_raw_os_error(-NativeEndian::read_i32(payload));
+                            let _ = replies.unbounded_send(Err(err));
+                        }
+                        true
+                    },
+                    NLMSG_OVERRUN => {
+                        let _ = replies.unbounded_send(Err(io::Error::new(io::ErrorKind::Other, "netlink overrun")));
+                        true
+                    },
+                    _ => replies.unbounded_send(Ok(owned)).is_err(),
+                },
+            };
+            if done {
+                self.pending.remove(&seq);
+            }
+        }
+    }
+}
+
+impl<R: Readiness + Unpin> Future for Connection<R> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.poll_requests(cx);
+        this.poll_send(cx);
+        if let Poll::Ready(err) = this.poll_recv(cx) {
+            for (_, replies) in this.pending.drain() {
+                let _ = replies.unbounded_send(Err(io::Error::new(err.kind(), err.to_string())));
+            }
+            return Poll::Ready(Err(err));
+        }
+        // a finished dump lets the next one go
+        this.poll_send(cx);
+        this.pending.retain(|_, replies| !replies.is_closed());
+        if this.requests.is_none() && this.outgoing.is_empty()
+            && this.pending.is_empty() && this.unsolicited.is_closed() {
+            return Poll::Ready(Ok(()));
+        }
+        Poll::Pending
+    }
+}
+
+/// Handle for sending requests over a `Connection`
+#[derive(Clone)]
+pub struct Handle {
+    requests: UnboundedSender<Request>,
+    seq: Arc<AtomicUsize>,
+    sleep: fn(Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>,
+}
+
+impl Handle {
+    /// Send `packet` and return the stream of replies
+    ///
+    /// Sequence number is assigned by the handle. Requests that are not
+    /// dumps get `NLM_F_ACK` set, so the stream ends on the ACK; dumps end
+    /// on `NLMSG_DONE`. A netlink error is the last item of the stream.
+    pub fn request(&self, packet: NetlinkPacket) -> io::Result<Responses> {
+        self.send(packet, None)
+    }
+
+    /// Same as `request`, the stream yields `TimedOut` error and ends
+    /// if the request is not complete in `timeout`
+    pub fn request_timeout(&self, packet: NetlinkPacket, timeout: Duration) -> io::Result<Responses> {
+        self.send(packet, Some(timeout))
+    }
+
+    /// Send `packet` and wait for the ACK, ignoring replies
+    pub async fn request_ack(&self, packet: NetlinkPacket<'_>) -> io::Result<()> {
+        let mut responses = self.request(packet)?;
+        while let Some(res) = responses.next().await {
+            res?;
+        }
+        Ok(())
+    }
+
+    /// Send `packet` and collect all replies
+    pub async fn request_all(&self, packet: NetlinkPacket<'_>) -> io::Result<Vec<NetlinkPacket<'static>>> {
+        let mut responses = self.request(packet)?;
+        let mut replies = vec![];
+        while let Some(res) = responses.next().await {
+            replies.push(res?);
+        }
+        Ok(replies)
+    }
+
+    fn next_seq(&self) -> u32 {
+        loop {
+            // 0 is used by the kernel for notifications
+            let seq = self.seq.fetch_add(1, Ordering::Relaxed).wrapping_add(1) as u32;
+            if seq != 0 {
+                return seq;
+            }
+        }
+    }
+
+    fn send(&self, packet: NetlinkPacket, timeout: Option<Duration>) -> io::Result<Responses> {
+        let seq = self.next_seq();
+        let mut data = packet.packet().to_vec();
+        let dump = {
+            let mut pkt = match MutableNetlinkPacket::new(&mut data) {
+                Some(pkt) => pkt,
+                None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "malformed netlink packet")),
+            };
+            let mut flags = pkt.get_flags() | NetlinkMsgFlags::NLM_F_REQUEST;
+            if !flags.contains(NetlinkMsgFlags::NLM_F_DUMP) {
+                flags |= NetlinkMsgFlags::NLM_F_ACK;
+            }
+            pkt.set_flags(flags);
+            pkt.set_seq(seq);
+            flags.contains(NetlinkMsgFlags::NLM_F_DUMP)
+        };
+        let (tx, rx) = mpsc::unbounded();
+        let req = Request { seq: seq, dump: dump, data: data, replies: tx };
+        if self.requests.unbounded_send(req).is_err() {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "netlink connection closed"));
+        }
+        Ok(Responses {
+            replies: rx,
+            timeout: timeout.map(|t| (self.sleep)(t)),
+            done: false,
+        })
+    }
+}
+
+/// Replies to a single request
+pub struct Responses {
+    replies: UnboundedReceiver<io::Result<NetlinkPacket<'static>>>,
+    timeout: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    done: bool,
+}
+
+impl Stream for Responses {
+    type Item = io::Result<NetlinkPacket<'static>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        match this.replies.poll_next_unpin(cx) {
+            Poll::Ready(None) => {
+                this.done = true;
+                return Poll::Ready(None);
+            },
+            Poll::Ready(Some(res)) => return Poll::Ready(Some(res)),
+            Poll::Pending => {},
+        }
+        if let Some(ref mut timeout) = this.timeout {
+            if timeout.as_mut().poll(cx).is_ready() {
+                // dropping the receiver cancels the request in the connection
+                this.done = true;
+                this.replies.close();
+                return Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::TimedOut, "netlink request timed out"))));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+// root permissions required
+fn concurrent_requests() {
+    use futures::future::join_all;
+    use crate::packet::netlink::NetlinkRequestBuilder;
+    use crate::packet::route::MutableIfInfoPacket;
+    use crate::packet::route::link::RTM_GETLINK;
+    use crate::packet::route::addr::RTM_GETADDR;
+
+    const LINKS: usize = 64;
+    let rt = ::tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    rt.block_on(async {
+        let (conn, handle, _) = new_connection(socket::NetlinkProtocol::Route, 0).unwrap();
+        ::tokio::spawn(conn);
+        // enough links for a link dump to take several datagrams,
+        // so that the dumps below would overlap in the kernel
+        for i in 0..LINKS {
+            handle.new_dummy_link(&format!("pnl-mux{}", i)).await.unwrap();
+        }
+        let dump = |kind| NetlinkRequestBuilder::new(kind, NetlinkMsgFlags::NLM_F_DUMP)
+            .append(MutableIfInfoPacket::owned(vec![0; MutableIfInfoPacket::minimum_packet_size()]).unwrap())
+            .build();
+        let kinds: Vec<_> = (0..16).map(|i| if i % 2 == 0 { RTM_GETLINK } else { RTM_GETADDR }).collect();
+        let dumps = join_all(kinds.iter().map(|&kind| handle.request_all(dump(kind)))).await;
+        let mut links = None;
+        for (&kind, replies) in kinds.iter().zip(dumps) {
+            let replies = replies.unwrap();
+            assert!(replies.len() > 0);
+            if kind == RTM_GETLINK {
+                assert!(replies.iter().all(|pkt| pkt.get_kind() == 16 /* NEW LINK */));
+                assert!(replies.len() > LINKS);
+                assert_eq!(*links.get_or_insert(replies.len()), replies.len());
+            } else {
+                assert!(replies.iter().all(|pkt| pkt.get_kind() == 20 /* NEW ADDR */));
+            }
+        }
+        let slow = handle.request_timeout(dump(RTM_GETLINK), Duration::from_secs(5)).unwrap();
+        assert_eq!(Some(slow.count().await), links);
+        for i in 0..LINKS {
+            let link = handle.get_link_by_name(&format!("pnl-mux{}", i)).await.unwrap().unwrap();
+            handle.delete_link(&link).await.unwrap();
+        }
+    });
+}
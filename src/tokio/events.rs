@@ -0,0 +1,138 @@
+//! Async typed rtnetlink notifications
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use crate::socket;
+use crate::packet::route::event::{EventSet, RtnlEvent};
+use crate::packet::route::resync::EventTracker;
+use super::{connection, Connection, Handle, NetlinkSocket, Unsolicited};
+
+/// Stream of `RtnlEvent`s, see `EventIterator` for the blocking variant
+///
+/// Notifications of kinds not covered by `RtnlEvent` are skipped,
+/// `ENOBUFS` errors are passed through.
+pub struct EventStream {
+    unsolicited: Unsolicited,
+}
+
+impl EventStream {
+    /// Wrap the unsolicited stream of a connection
+    pub fn new(unsolicited: Unsolicited) -> Self {
+        EventStream { unsolicited: unsolicited }
+    }
+}
+
+/// Event stream resynchronizing after overflows, see `resync`
+pub type ResyncStream = Pin<Box<dyn Stream<Item = io::Result<RtnlEvent>> + Send>>;
+
+struct Resync {
+    events: EventStream,
+    handle: Handle,
+    tracker: EventTracker,
+    queue: VecDeque<RtnlEvent>,
+    synced: bool,
+}
+
+impl Resync {
+    async fn resync(&mut self) -> io::Result<()> {
+        let dump = self.handle.dump_tables(self.tracker.events()).await?;
+        self.queue.extend(self.tracker.resync(dump));
+        self.synced = true;
+        Ok(())
+    }
+}
+
+impl EventStream {
+    /// Resynchronize after overflows, like the blocking `ResyncIterator`
+    ///
+    /// `events` must be the set the stream is subscribed to. The stream
+    /// starts with added events for the current state, dumped with `handle`,
+    /// and emits the differences instead of `ENOBUFS` errors.
+    pub fn resync(self, handle: Handle, events: EventSet) -> ResyncStream {
+        let state = Resync {
+            events: self,
+            handle: handle,
+            tracker: EventTracker::new(events),
+            queue: VecDeque::new(),
+            synced: false,
+        };
+        Box::pin(stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(event) = state.queue.pop_front() {
+                    return Some((Ok(event), state));
+                }
+                if !state.synced {
+                    if let Err(e) = state.resync().await {
+                        return Some((Err(e), state));
+                    }
+                    continue;
+                }
+                match state.events.next().await {
+                    Some(Ok(event)) => {
+                        state.tracker.apply(&event);
+                        return Some((Ok(event), state));
+                    },
+                    Some(Err(ref e)) if e.raw_os_error() == Some(::libc::ENOBUFS) => state.synced = false,
+                    Some(Err(e)) => return Some((Err(e), state)),
+                    None => return None,
+                }
+            }
+        }))
+    }
+}
+
+impl Stream for EventStream {
+    type Item = io::Result<RtnlEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.unsolicited.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(pkt))) => {
+                    if let Some(event) = RtnlEvent::from_packet(pkt) {
+                        return Poll::Ready(Some(Ok(event)));
+                    }
+                },
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Create an rtnetlink connection subscribed to `events`
+///
+/// The `Handle` can be used for requests as with `new_connection`.
+pub fn new_event_connection(events: EventSet) -> io::Result<(Connection, Handle, EventStream)> {
+    let mut sock = socket::NetlinkSocket::bind_with_pid(socket::NetlinkProtocol::Route, 0, 0, true)?;
+    for group in events.groups() {
+        sock.add_membership(group)?;
+    }
+    let (conn, handle, unsolicited) = connection(NetlinkSocket::new(sock)?);
+    Ok((conn, handle, EventStream::new(unsolicited)))
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+// root permissions required
+fn async_link_events() {
+    let rt = ::tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    rt.block_on(async {
+        let (conn, handle, mut events) = new_event_connection(EventSet::LINK).unwrap();
+        ::tokio::spawn(conn);
+        handle.new_dummy_link("pnl-aevent0").await.unwrap();
+        let link = handle.get_link_by_name("pnl-aevent0").await.unwrap().unwrap();
+        handle.delete_link(&link).await.unwrap();
+        let mut added = false;
+        while let Some(event) = events.next().await {
+            match event.unwrap() {
+                RtnlEvent::LinkAdded(ref link) if link.get_name() == Some("pnl-aevent0".to_owned()) => added = true,
+                RtnlEvent::LinkRemoved(ref link) if link.get_name() == Some("pnl-aevent0".to_owned()) => break,
+                _ => {},
+            }
+        }
+        assert!(added);
+    });
+}
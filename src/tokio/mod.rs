@@ -0,0 +1,385 @@
+//! Async netlink socket, runtime-neutral
+//!
+//! The socket only needs readiness notifications from the runtime, see
+//! `Readiness`. `TokioFd` (feature `tokio`, default) and `AsyncIoFd`
+//! (feature `async-io`, for smol and other async-io based runtimes) are provided.
+#[cfg(feature = "tokio")]
+use bytes::{Buf, BytesMut};
+use futures::future::poll_fn;
+use std::io;
+use std::ops::Range;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use crate::socket;
+use crate::packet::netlink::{NetlinkPacket,MutableNetlinkPacket,NetlinkRequestBuilder,NetlinkMsgFlags,self};
+use crate::packet::route::MutableIfInfoPacket;
+use pnet::packet::Packet;
+
+mod readiness;
+mod connection;
+mod events;
+mod rtnl;
+pub mod wait;
+#[cfg(feature = "tokio")]
+mod tokio_fd;
+#[cfg(feature = "async-io")]
+mod async_io_fd;
+
+pub use self::readiness::Readiness;
+pub use self::connection::{connection,new_connection,Connection,Handle,Responses,Unsolicited};
+pub use self::events::{new_event_connection,EventStream,ResyncStream};
+#[cfg(feature = "tokio")]
+pub use self::tokio_fd::TokioFd;
+#[cfg(feature = "async-io")]
+pub use self::async_io_fd::AsyncIoFd;
+
+/// Runtime used by `NetlinkSocket` unless another one is given
+#[cfg(feature = "tokio")]
+pub type DefaultReadiness = TokioFd;
+/// Runtime used by `NetlinkSocket` unless another one is given
+#[cfg(all(feature = "async-io", not(feature = "tokio")))]
+pub type DefaultReadiness = AsyncIoFd;
+
+/// Largest datagram the kernel sends on a netlink socket
+const MAX_DATAGRAM: usize = 32768;
+
+/// Async netlink socket
+///
+/// With the `tokio` feature it must be created from within a tokio runtime.
+pub struct NetlinkSocket<R: Readiness = DefaultReadiness> {
+    io: R,
+    // receive buffer for `poll_read` calls with a smaller buffer, allocated
+    // on first use and kept, and the datagram bytes not handed out yet
+    read_buf: Vec<u8>,
+    pending: Range<usize>,
+}
+
+impl NetlinkSocket {
+    /// Bind socket on the default runtime
+    pub fn bind(proto: socket::NetlinkProtocol, groups: u32) -> io::Result<NetlinkSocket> {
+        let sock = socket::NetlinkSocket::bind(proto, groups)?;
+        NetlinkSocket::new(sock)
+    }
+
+    /// Wrap a bound socket for the default runtime
+    pub fn new(socket: socket::NetlinkSocket) -> io::Result<NetlinkSocket> {
+        NetlinkSocket::from_socket(socket)
+    }
+}
+
+impl<R: Readiness> NetlinkSocket<R> {
+    /// Wrap a bound socket for runtime `R`,
+    /// e.g. `NetlinkSocket::<AsyncIoFd>::from_socket(sock)`
+    pub fn from_socket(socket: socket::NetlinkSocket) -> io::Result<NetlinkSocket<R>> {
+        Ok(NetlinkSocket { io: R::new(socket)?, read_buf: vec![], pending: 0..0 })
+    }
+
+    /// Get underlying socket
+    pub fn get_ref(&self) -> &socket::NetlinkSocket {
+        self.io.get_ref()
+    }
+
+    /// Send one datagram
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_send(cx, buf)).await
+    }
+
+    /// Receive one datagram, it is truncated if `buf` is too small
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_recv(cx, buf)).await
+    }
+
+    /// Receive one datagram together with the sender address
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, socket::NetlinkAddr)> {
+        poll_fn(|cx| self.poll_recv_from(cx, buf)).await
+    }
+
+    /// Poll version of `send`
+    pub fn poll_send(&self, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.io.poll_write_with(cx, |sock| sock.send(buf))
+    }
+
+    /// Poll version of `recv`
+    pub fn poll_recv(&self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.io.poll_read_with(cx, |sock| sock.recv(buf))
+    }
+
+    /// Poll version of `recv_from`
+    pub fn poll_recv_from(&self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<(usize, socket::NetlinkAddr)>> {
+        self.io.poll_read_with(cx, |sock| sock.recv_from(buf))
+    }
+
+    /// Reads datagrams as a byte stream. Datagrams larger than `buf`
+    /// are received whole and handed out over several reads.
+    fn poll_read_bytes(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if self.pending.start == self.pending.end {
+            if buf.len() >= MAX_DATAGRAM {
+                return self.poll_recv(cx, buf);
+            }
+            if self.read_buf.is_empty() {
+                self.read_buf = vec![0; MAX_DATAGRAM];
+            }
+            let read_buf = &mut self.read_buf;
+            let n = match self.io.poll_read_with(cx, |sock| sock.recv(&mut read_buf[..])) {
+                Poll::Ready(Ok(n)) => n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            self.pending = 0..n;
+        }
+        let n = ::std::cmp::min(buf.len(), self.pending.end - self.pending.start);
+        let start = self.pending.start;
+        buf[..n].copy_from_slice(&self.read_buf[start..start + n]);
+        self.pending.start += n;
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<R: Readiness + Unpin> futures::io::AsyncRead for NetlinkSocket<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_read_bytes(cx, buf)
+    }
+}
+
+impl<R: Readiness + Unpin> futures::io::AsyncWrite for NetlinkSocket<R> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.poll_send(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: Readiness + Unpin> ::tokio::io::AsyncRead for NetlinkSocket<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: &mut ::tokio::io::ReadBuf) -> Poll<io::Result<()>> {
+        let n = match self.get_mut().poll_read_bytes(cx, buf.initialize_unfilled()) {
+            Poll::Ready(Ok(n)) => n,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        };
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: Readiness + Unpin> ::tokio::io::AsyncWrite for NetlinkSocket<R> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.poll_send(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Codec splitting a netlink byte stream into packets,
+/// use with `tokio_util::codec::Framed`
+#[cfg(feature = "tokio")]
+#[derive(Debug,Default,Clone,Copy)]
+pub struct NetlinkCodec {}
+
+#[cfg(feature = "tokio")]
+impl tokio_util::codec::Decoder for NetlinkCodec {
+    type Item = NetlinkPacket<'static>;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> io::Result<Option<Self::Item>> {
+        let (owned_pkt, len) = {
+            if let Some(pkt) = NetlinkPacket::new(buf) {
+                let pkt_len = pkt.get_length() as usize;
+                if pkt_len < MutableNetlinkPacket::minimum_packet_size() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed netlink packet"))
+                }
+                let aligned_len = crate::util::align(pkt_len);
+                if aligned_len > buf.len() {
+                    // need more bytes
+                    return Ok(None);
+                }
+                (NetlinkPacket::owned(buf[..pkt_len].to_owned()), aligned_len)
+            } else {
+                // need at least a header
+                return Ok(None);
+            }
+        };
+        buf.advance(len);
+        Ok(owned_pkt)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<'a> tokio_util::codec::Encoder<NetlinkPacket<'a>> for NetlinkCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, msg: NetlinkPacket<'a>, buf: &mut BytesMut) -> io::Result<()> {
+        buf.extend_from_slice(msg.packet());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn try_tokio_conn() {
+    use futures::{SinkExt,StreamExt};
+    use tokio_util::codec::Framed;
+    use crate::packet::route::link::Link;
+
+    let rt = ::tokio::runtime::Builder::new_current_thread().enable_io().build().unwrap();
+    rt.block_on(async {
+        let sock = NetlinkSocket::bind(socket::NetlinkProtocol::Route, 0).unwrap();
+        println!("Netlink socket bound");
+        let mut framed = Framed::new(sock, NetlinkCodec {});
+
+        let pkt = NetlinkRequestBuilder::new(18 /* RTM GETLINK */, NetlinkMsgFlags::NLM_F_DUMP).append(
+            {
+                let len = MutableIfInfoPacket::minimum_packet_size();
+                let data = vec![0; len];
+                MutableIfInfoPacket::owned(data).unwrap()
+            }
+        ).build();
+        framed.send(pkt).await.unwrap();
+        while let Some(frame) = framed.next().await {
+            let frame = frame.unwrap();
+            println!("RECEIVED FRAME: {:?}", frame);
+            if frame.get_kind() == netlink::NLMSG_DONE {
+                break;
+            }
+            if frame.get_kind() == 16 /* NEW LINK */ {
+                Link::dump_link(frame);
+            }
+        }
+    });
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn try_tokio_send_recv() {
+    let rt = ::tokio::runtime::Builder::new_current_thread().enable_io().build().unwrap();
+    rt.block_on(async {
+        let sock = NetlinkSocket::bind(socket::NetlinkProtocol::Route, 0).unwrap();
+        let pkt = NetlinkRequestBuilder::new(18 /* RTM GETLINK */, NetlinkMsgFlags::NLM_F_DUMP)
+            .append(MutableIfInfoPacket::owned(vec![0; MutableIfInfoPacket::minimum_packet_size()]).unwrap())
+            .build();
+        sock.send(pkt.packet()).await.unwrap();
+        let mut buf = vec![0; MAX_DATAGRAM];
+        let n = sock.recv(&mut buf).await.unwrap();
+        let reply = NetlinkPacket::new(&buf[..n]).unwrap();
+        assert_eq!(reply.get_kind(), 16 /* NEW LINK */);
+    });
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+fn try_async_io_send_recv() {
+    async_io::block_on(async {
+        let sock = socket::NetlinkSocket::bind(socket::NetlinkProtocol::Route, 0).unwrap();
+        let sock = NetlinkSocket::<AsyncIoFd>::from_socket(sock).unwrap();
+        let pkt = NetlinkRequestBuilder::new(18 /* RTM GETLINK */, NetlinkMsgFlags::NLM_F_DUMP)
+            .append(MutableIfInfoPacket::owned(vec![0; MutableIfInfoPacket::minimum_packet_size()]).unwrap())
+            .build();
+        sock.send(pkt.packet()).await.unwrap();
+        let mut buf = vec![0; MAX_DATAGRAM];
+        let n = sock.recv(&mut buf).await.unwrap();
+        let reply = NetlinkPacket::new(&buf[..n]).unwrap();
+        assert_eq!(reply.get_kind(), 16 /* NEW LINK */);
+    });
+}
+
+#[test]
+fn try_mio_conn() {
+    use mio::*;
+
+    let poll = Poll::new().unwrap();
+    let mut sock = socket::NetlinkSocket::bind(socket::NetlinkProtocol::Route, 0).unwrap();
+    poll.register(&sock, Token(0), Ready::writable() | Ready::readable(),
+              PollOpt::edge()).unwrap();
+
+    let pkt = NetlinkRequestBuilder::new(18 /* RTM GETLINK */, NetlinkMsgFlags::NLM_F_DUMP).append(
+        {
+            let len = MutableIfInfoPacket::minimum_packet_size();
+            let mut data = vec![0; len];
+            MutableIfInfoPacket::owned(data).unwrap()
+        }
+    ).build();
+
+    let mut buf = vec![0;4096];
+    let mut pos: usize = 0;
+    let mut events = Events::with_capacity(1024);
+    let mut written = false;
+    loop {
+        poll.poll(&mut events, None).unwrap();
+        for event in events.iter() {
+            match event.token() {
+                Token(0) => {
+                    println!("EVENT: {:?}", event);
+                    if event.kind() == Ready::writable() {
+                        use std::io::Write;
+                        if !written {
+                            println!("WRITABLE");
+                            sock.write(pkt.packet()).unwrap();
+                            written = true;
+                        }
+                    }
+                    if event.kind() & Ready::readable() == Ready::readable() {
+                        use std::io::Read;
+                        println!("Reading");
+                        'read: loop {
+                            match sock.read(&mut buf[pos..]) {
+                                Ok(n) => {
+                                    if n == 0 {
+                                        break 'read;
+                                    }
+                                    pos += n;
+                                    println!("read {}", n);
+                                    if pos >= buf.len() - 1 {
+                                        println!("Growing buf: len: {} pos: {}", buf.len(), pos);
+                                        for _ in 0..buf.len() {
+                                            buf.push(0);
+                                        }
+                                        println!("Growing buf: new len: {} pos: {}", buf.len(), pos);
+                                    }
+                                },
+                                Err(e) => {
+                                     println!("err: {:?}", e);
+                                     break 'read;
+                                },
+                            }
+                        }
+                        if let Some(pkt) = NetlinkPacket::new(&buf) {
+                            println!("PKT: {:?}", pkt);
+                            let mut cursor = 0;
+                            let total_len = buf.len();
+
+                            let mut aligned_len = crate::util::align(pkt.get_length() as usize);
+                            loop {
+                                cursor += aligned_len;
+                                if cursor >= total_len {
+                                    break;
+                                }
+                                println!("NEXT PKT @ {:?}", cursor);
+                                if let Some(next_pkt) = NetlinkPacket::new(&buf[cursor..]) {
+                                    println!("PKT: {:?}", next_pkt);
+                                    aligned_len = crate::util::align(next_pkt.get_length() as usize);
+                                    if aligned_len == 0 {
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+}
\ No newline at end of file
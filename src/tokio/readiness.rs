@@ -0,0 +1,30 @@
+//! Runtime readiness abstraction
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use crate::socket;
+
+/// Readiness notifications for a non-blocking netlink socket, and timers
+///
+/// Implemented for the reactor of each supported runtime, this is the only
+/// runtime-specific part of `pnetlink::tokio`.
+pub trait Readiness: Sized {
+    /// Register `sock` with the reactor, switching it to non-blocking mode
+    fn new(sock: socket::NetlinkSocket) -> io::Result<Self>;
+
+    /// Get registered socket
+    fn get_ref(&self) -> &socket::NetlinkSocket;
+
+    /// Run `op` once the socket is readable, retrying it while it fails with `WouldBlock`
+    fn poll_read_with<T, F>(&self, cx: &mut Context, op: F) -> Poll<io::Result<T>>
+        where F: FnMut(&socket::NetlinkSocket) -> io::Result<T>;
+
+    /// Run `op` once the socket is writable, retrying it while it fails with `WouldBlock`
+    fn poll_write_with<T, F>(&self, cx: &mut Context, op: F) -> Poll<io::Result<T>>
+        where F: FnMut(&socket::NetlinkSocket) -> io::Result<T>;
+
+    /// Future completing after `duration`, used for request timeouts
+    fn sleep(duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
@@ -0,0 +1,243 @@
+//! Async rtnetlink operations
+//!
+//! Counterparts of the `Links`, `Addresses`, `Neighbours`, `Routes` and
+//! `Rules` traits on `Handle`, built from the same requests. Dumps are
+//! streams, mutations are futures resolving on the kernel ACK.
+use futures::future::ready;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use std::io;
+use std::net::IpAddr;
+use crate::packet::netlink::{NetlinkPacket,NetlinkMsgFlags};
+use crate::packet::route::AddressFamily;
+use crate::packet::route::addr::{Addr,AddrInfo,Scope,RTM_DELADDR};
+use crate::packet::route::bridge::{BridgePort,StpState};
+use crate::packet::route::bridge_vlan::{BridgeVlan,RTM_NEWVLAN};
+use crate::packet::route::event::EventSet;
+use crate::packet::route::link::{Link,LinkBuilder,LinkKind};
+use crate::packet::route::neighbour::{Neighbour,NeighbourInfo};
+use crate::packet::route::prefix::IpPrefix;
+use crate::packet::route::resync::Dump;
+use crate::packet::route::route::{Route,RouteInfo};
+use crate::packet::route::rule::{Rule,RuleInfo};
+use super::Handle;
+
+impl Handle {
+    /// Dump all links
+    pub fn links(&self) -> impl Stream<Item = io::Result<Link>> + Send + 'static {
+        self.dump(Link::dump_request(), Link::from_packet)
+    }
+
+    /// Get link by index, None if it does not exist
+    pub async fn get_link_by_index(&self, index: u32) -> io::Result<Option<Link>> {
+        self.get_link(Link::get_by_index_request(index)).await
+    }
+
+    /// Get link by name, None if it does not exist
+    pub async fn get_link_by_name(&self, name: &str) -> io::Result<Option<Link>> {
+        self.get_link(Link::get_by_name_request(name)).await
+    }
+
+    /// Create dummy link
+    pub async fn new_dummy_link(&self, name: &str) -> io::Result<()> {
+        self.request_ack(Link::new_dummy_request(name)).await
+    }
+
+    /// Create link described by `builder`
+    pub async fn new_link(&self, builder: &LinkBuilder) -> io::Result<()> {
+        self.request_ack(builder.request()?).await
+    }
+
+    /// Delete link
+    pub async fn delete_link(&self, link: &Link) -> io::Result<()> {
+        self.request_ack(Link::delete_request(link.get_index())).await
+    }
+
+    /// Set link up
+    pub async fn link_set_up(&self, index: u32) -> io::Result<()> {
+        self.request_ack(Link::set_up_down_request(index, true)).await
+    }
+
+    /// Set link down
+    pub async fn link_set_down(&self, index: u32) -> io::Result<()> {
+        self.request_ack(Link::set_up_down_request(index, false)).await
+    }
+
+    /// Enslave link to `master`, or release it with None
+    pub async fn link_set_master(&self, index: u32, master: Option<u32>) -> io::Result<()> {
+        self.request_ack(Link::set_master_request(index, master)).await
+    }
+
+    /// Change settings of bridge port `index`
+    pub async fn link_set_bridge_port(&self, index: u32, port: &BridgePort) -> io::Result<()> {
+        self.request_ack(Link::set_bridge_port_request(index, port)).await
+    }
+
+    /// Dump VLANs of bridge port `index`, or of all ports and bridges
+    pub async fn bridge_vlans(&self, index: Option<u32>) -> io::Result<Vec<BridgeVlan>> {
+        let replies = self.request_all(BridgeVlan::dump_request()).await?;
+        Ok(replies.into_iter()
+           .flat_map(BridgeVlan::from_link_packet)
+           .filter(|vlan| index.map_or(true, |index| vlan.index == index))
+           .collect())
+    }
+
+    /// Dump VLANs with their STP state, needs `RTM_GETVLAN` support
+    pub async fn bridge_vlan_states(&self, index: Option<u32>) -> io::Result<Vec<BridgeVlan>> {
+        let replies = self.request_all(BridgeVlan::dump_states_request(index)).await?;
+        let bridges: Vec<u32> = self.links()
+            .try_filter(|link| ready(link.get_kind().as_ref().map(|kind| kind.as_str()) == Some("bridge")))
+            .map_ok(|link| link.get_index())
+            .try_collect().await?;
+        Ok(replies.into_iter()
+           .filter(|pkt| pkt.get_kind() == RTM_NEWVLAN)
+           .flat_map(BridgeVlan::from_vlan_packet)
+           .map(|mut vlan| { vlan.bridge_self = bridges.contains(&vlan.index); vlan })
+           .collect())
+    }
+
+    /// Add bridge VLAN
+    pub async fn add_bridge_vlan(&self, vlan: &BridgeVlan) -> io::Result<()> {
+        self.request_ack(vlan.add_request()).await
+    }
+
+    /// Delete bridge VLAN
+    pub async fn delete_bridge_vlan(&self, vlan: &BridgeVlan) -> io::Result<()> {
+        self.request_ack(vlan.delete_request()).await
+    }
+
+    /// Set STP state of an existing bridge VLAN
+    pub async fn set_bridge_vlan_state(&self, vlan: &BridgeVlan, state: StpState) -> io::Result<()> {
+        self.request_ack(vlan.set_state_request(state)).await
+    }
+
+    /// Change kind specific options of an existing link
+    pub async fn link_set_options(&self, index: u32, kind: &(dyn LinkKind + Sync)) -> io::Result<()> {
+        self.request_ack(Link::set_options_request(index, kind)?).await
+    }
+
+    /// Dump addresses of `family`, or all if None
+    pub fn addrs(&self, family: Option<AddressFamily>) -> impl Stream<Item = io::Result<Addr>> + Send + 'static {
+        self.dump(Addr::dump_request(family), Addr::from_packet)
+    }
+
+    /// Add address, see `Addresses::add_addr`
+    pub async fn add_addr(&self, link: &Link, prefix: IpPrefix, dst_addr: Option<IpAddr>, scope: Scope) -> io::Result<()> {
+        let req = Addr::add_request(link.get_index(), prefix, dst_addr, scope)?;
+        self.request_ack(req).await
+    }
+
+    /// Delete address
+    pub async fn delete_addr(&self, addr: &AddrInfo) -> io::Result<()> {
+        self.request_ack(addr.to_request(RTM_DELADDR, NetlinkMsgFlags::NLM_F_ACK)).await
+    }
+
+    /// Dump neighbours of `family` on `link`, None for all
+    pub fn neighbours(&self, family: Option<AddressFamily>, link: Option<&Link>) -> impl Stream<Item = io::Result<Neighbour>> + Send + 'static {
+        self.dump(Neighbour::dump_request(family, link.map(|link| link.get_index())), Neighbour::from_packet)
+    }
+
+    /// Add or replace neighbour
+    pub async fn add_neighbour(&self, neighbour: &NeighbourInfo) -> io::Result<()> {
+        self.request_ack(neighbour.add_request()).await
+    }
+
+    /// Delete neighbour
+    pub async fn delete_neighbour(&self, neighbour: &NeighbourInfo) -> io::Result<()> {
+        self.request_ack(neighbour.delete_request()).await
+    }
+
+    /// Dump routes of `family`, or all if None
+    pub fn routes(&self, family: Option<AddressFamily>) -> impl Stream<Item = io::Result<Route>> + Send + 'static {
+        self.dump(Route::dump_request(family), Route::from_packet)
+    }
+
+    /// Add route, fails if it already exists
+    pub async fn add_route(&self, route: &RouteInfo) -> io::Result<()> {
+        self.request_ack(route.add_request()?).await
+    }
+
+    /// Delete route
+    pub async fn delete_route(&self, route: &RouteInfo) -> io::Result<()> {
+        self.request_ack(route.delete_request()?).await
+    }
+
+    /// Dump rules of `family`, or all if None
+    pub fn rules(&self, family: Option<AddressFamily>) -> impl Stream<Item = io::Result<Rule>> + Send + 'static {
+        self.dump(Rule::dump_request(family), Rule::from_packet)
+    }
+
+    /// Add rule, fails if an identical rule exists
+    pub async fn add_rule(&self, rule: &RuleInfo) -> io::Result<()> {
+        self.request_ack(rule.add_request()?).await
+    }
+
+    /// Delete first rule matching `rule`
+    pub async fn delete_rule(&self, rule: &RuleInfo) -> io::Result<()> {
+        self.request_ack(rule.delete_request()?).await
+    }
+
+    /// Dump the tables covered by `events`, see `Dump::new`
+    pub async fn dump_tables(&self, events: EventSet) -> io::Result<Dump> {
+        let mut dump = Dump::default();
+        if events.contains(EventSet::LINK) {
+            dump.links = self.links().try_collect().await?;
+        }
+        if events.intersects(EventSet::ADDR) {
+            dump.addrs = self.addrs(None).try_collect().await?;
+        }
+        if events.intersects(EventSet::ROUTE) {
+            dump.routes = self.routes(None).try_collect().await?;
+        }
+        if events.contains(EventSet::NEIGH) {
+            dump.neighbours = self.neighbours(None, None).try_collect().await?;
+        }
+        if events.intersects(EventSet::RULE) {
+            dump.rules = self.rules(None).try_collect().await?;
+        }
+        Ok(dump)
+    }
+
+    fn dump<T>(&self, req: NetlinkPacket<'static>, parse: fn(NetlinkPacket<'static>) -> Option<T>)
+        -> impl Stream<Item = io::Result<T>> + Send + 'static where T: Send + 'static {
+        match self.request(req) {
+            Ok(responses) => responses.filter_map(move |res| ready(match res {
+                Ok(pkt) => parse(pkt).map(Ok),
+                Err(e) => Some(Err(e)),
+            })).left_stream(),
+            Err(e) => stream::once(ready(Err(e))).right_stream(),
+        }
+    }
+
+    async fn get_link(&self, req: NetlinkPacket<'static>) -> io::Result<Option<Link>> {
+        match self.request_all(req).await {
+            Ok(replies) => Ok(replies.into_iter().filter_map(Link::from_packet).last()),
+            Err(ref e) if e.raw_os_error() == Some(::libc::ENODEV) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_dumps() {
+    use futures::TryStreamExt;
+    use super::new_connection;
+    use crate::socket::NetlinkProtocol;
+
+    let rt = ::tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    rt.block_on(async {
+        let (conn, handle, _) = new_connection(NetlinkProtocol::Route, 0).unwrap();
+        ::tokio::spawn(conn);
+        let links: Vec<Link> = handle.links().try_collect().await.unwrap();
+        let lo = links.into_iter().find(|link| link.get_name() == Some("lo".to_owned())).unwrap();
+        let by_name = handle.get_link_by_name("lo").await.unwrap().unwrap();
+        assert_eq!(by_name.get_index(), lo.get_index());
+        assert!(handle.get_link_by_name("nonexistent0").await.unwrap().is_none());
+        let addrs: Vec<Addr> = handle.addrs(None).try_collect().await.unwrap();
+        assert!(addrs.iter().any(|addr| addr.get_link_index() == lo.get_index()));
+        let routes: Vec<Route> = handle.routes(None).try_collect().await.unwrap();
+        assert!(routes.len() > 0);
+        let _: Vec<Rule> = handle.rules(None).try_collect().await.unwrap();
+        let _: Vec<Neighbour> = handle.neighbours(None, None).try_collect().await.unwrap();
+    });
+}
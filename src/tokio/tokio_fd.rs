@@ -0,0 +1,61 @@
+//! tokio reactor support
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use ::tokio::io::unix::AsyncFd;
+use crate::socket;
+use super::Readiness;
+
+/// Netlink socket registered with the tokio reactor
+///
+/// Must be created from within a tokio runtime with IO enabled.
+pub struct TokioFd {
+    io: AsyncFd<socket::NetlinkSocket>,
+}
+
+impl Readiness for TokioFd {
+    fn new(mut sock: socket::NetlinkSocket) -> io::Result<Self> {
+        sock.set_nonblocking(true)?;
+        Ok(TokioFd { io: AsyncFd::new(sock)? })
+    }
+
+    fn get_ref(&self) -> &socket::NetlinkSocket {
+        self.io.get_ref()
+    }
+
+    fn poll_read_with<T, F>(&self, cx: &mut Context, mut op: F) -> Poll<io::Result<T>>
+        where F: FnMut(&socket::NetlinkSocket) -> io::Result<T> {
+        loop {
+            let mut guard = match self.io.poll_read_ready(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            match guard.try_io(|inner| op(inner.get_ref())) {
+                Ok(res) => return Poll::Ready(res),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_write_with<T, F>(&self, cx: &mut Context, mut op: F) -> Poll<io::Result<T>>
+        where F: FnMut(&socket::NetlinkSocket) -> io::Result<T> {
+        loop {
+            let mut guard = match self.io.poll_write_ready(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            match guard.try_io(|inner| op(inner.get_ref())) {
+                Ok(res) => return Poll::Ready(res),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn sleep(duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(::tokio::time::sleep(duration))
+    }
+}
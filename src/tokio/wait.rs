@@ -0,0 +1,87 @@
+//! Async counterparts of `packet::route::wait`
+//!
+//! Each call opens its own subscribed connection and drives it while
+//! waiting, so nothing has to be spawned.
+use futures::future::{select, Either};
+use futures::stream::StreamExt;
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+use crate::packet::route::addr::Addr;
+use crate::packet::route::event::{EventSet, RtnlEvent};
+use crate::packet::route::link::Link;
+use crate::packet::route::prefix::IpPrefix;
+use crate::packet::route::route::Route;
+use crate::packet::route::wait;
+use super::{new_event_connection, DefaultReadiness, EventStream, Handle, Readiness};
+
+async fn wait_on<T, F>(handle: Handle, mut events: EventStream, set: EventSet, mut check: F) -> io::Result<T>
+    where F: FnMut(RtnlEvent) -> io::Result<Option<T>> {
+    let mut dump = true;
+    loop {
+        if dump {
+            for event in handle.dump_tables(set).await?.into_events() {
+                if let Some(found) = check(event)? {
+                    return Ok(found);
+                }
+            }
+            dump = false;
+        }
+        match events.next().await {
+            Some(Ok(event)) => {
+                if let Some(found) = check(event)? {
+                    return Ok(found);
+                }
+            },
+            Some(Err(ref e)) if e.raw_os_error() == Some(::libc::ENOBUFS) => dump = true,
+            Some(Err(e)) => return Err(e),
+            None => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "event stream ended")),
+        }
+    }
+}
+
+/// Wait until `check` returns a value, see `packet::route::wait::wait_for`
+pub async fn wait_for<T, F>(events: EventSet, timeout: Duration, check: F) -> io::Result<T>
+    where F: FnMut(RtnlEvent) -> io::Result<Option<T>> {
+    let (conn, handle, stream) = new_event_connection(events)?;
+    let waiting = Box::pin(wait_on(handle, stream, events, check));
+    let sleep = DefaultReadiness::sleep(timeout);
+    match select(select(waiting, sleep), Box::pin(conn)).await {
+        Either::Left((Either::Left((res, _)), _)) => res,
+        Either::Left((Either::Right(((), _)), _)) => {
+            Err(io::Error::new(io::ErrorKind::TimedOut, "condition not met in time"))
+        },
+        Either::Right((res, _)) => {
+            res?;
+            Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"))
+        },
+    }
+}
+
+/// Wait for link `name` to exist with `cond` holding
+pub async fn wait_for_link<F>(name: &str, timeout: Duration, cond: F) -> io::Result<Link>
+    where F: FnMut(&Link) -> bool {
+    wait_for(EventSet::LINK, timeout, wait::link_matching(name, cond)).await
+}
+
+/// Wait for address `ip` on `link` to leave the tentative state
+pub async fn wait_for_addr_dad_complete(link: &Link, ip: IpAddr, timeout: Duration) -> io::Result<Addr> {
+    wait_for(wait::addr_events(ip), timeout, wait::addr_dad_complete(link.get_index(), ip)).await
+}
+
+/// Wait for a route to `prefix` in `table`
+pub async fn wait_for_route(prefix: IpPrefix, table: u32, timeout: Duration) -> io::Result<Route> {
+    wait_for(wait::route_events(prefix), timeout, wait::route_present(prefix, table)).await
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_wait_for_lo() {
+    let rt = ::tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    rt.block_on(async {
+        let lo = wait_for_link("lo", Duration::from_secs(1), |link| link.is_up()).await.unwrap();
+        assert_eq!(lo.get_name(), Some("lo".to_owned()));
+        let err = wait_for_link("nonexistent0", Duration::from_millis(100), |_| true).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    });
+}